@@ -13,8 +13,8 @@
 //  0. You just DO WHAT THE FUCK YOU WANT TO.
 
 use futures::{SinkExt, StreamExt};
-use packet::{builder::Builder, icmp, ip, Packet};
 use tokio::sync::mpsc::Receiver;
+use tun2::packet::{IpPacket, Transport, ICMP_ECHO_REPLY, ICMP_ECHO_REQUEST};
 use tun2::{self, BoxError, Configuration};
 
 #[tokio::main]
@@ -22,10 +22,10 @@ async fn main() -> Result<(), BoxError> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("trace")).init();
     let (tx, rx) = tokio::sync::mpsc::channel::<()>(1);
 
-    ctrlc2::set_async_handler(async move {
-        tx.send(()).await.expect("Signal error");
-    })
-    .await;
+    ctrlc2::set_handler(move || {
+        tx.try_send(()).expect("Signal error");
+        true
+    })?;
 
     main_entry(rx).await?;
     Ok(())
@@ -63,30 +63,26 @@ async fn main_entry(mut quit: Receiver<()>) -> Result<(), BoxError> {
                 break;
             }
             Some(packet) = framed.next() => {
-                let pkt: Vec<u8> = packet?;
-                match ip::Packet::new(pkt) {
-                    Ok(ip::Packet::V4(pkt)) => {
-                        if let Ok(icmp) = icmp::Packet::new(pkt.payload()) {
-                            if let Ok(icmp) = icmp.echo() {
-                                println!("{:?} - {:?}", icmp.sequence(), pkt.destination());
-                                let reply = ip::v4::Builder::default()
-                                    .id(0x42)?
-                                    .ttl(64)?
-                                    .source(pkt.destination())?
-                                    .destination(pkt.source())?
-                                    .icmp()?
-                                    .echo()?
-                                    .reply()?
-                                    .identifier(icmp.identifier())?
-                                    .sequence(icmp.sequence())?
-                                    .payload(icmp.payload())?
-                                    .build()?;
-                                framed.send(reply).await?;
-                            }
-                        }
+                let mut pkt = packet?;
+                let mut ip = match IpPacket::new(&mut pkt[..]) {
+                    Ok(ip) => ip,
+                    Err(err) => {
+                        println!("Received an invalid packet: {:?}", err);
+                        continue;
+                    }
+                };
+                let (source, destination) = (ip.source(), ip.destination());
+                if let Ok(Transport::Icmp(mut icmp)) = ip.transport_mut() {
+                    if icmp.msg_type() == ICMP_ECHO_REQUEST {
+                        println!("{:?} - {:?}", icmp.sequence(), destination);
+                        // Answer in place, swapping the addresses.
+                        icmp.set_msg_type(ICMP_ECHO_REPLY);
+                        ip.set_source(destination)?;
+                        ip.set_destination(source)?;
+                        ip.set_hop_limit(64);
+                        ip.fill_checksums()?;
+                        framed.send(pkt).await?;
                     }
-                    Err(err) => println!("Received an invalid packet: {:?}", err),
-                    _ => {}
                 }
             }
         }
@@ -21,10 +21,10 @@ async fn main() -> Result<(), BoxError> {
     env_logger::Builder::from_env(env_logger::Env::default().default_filter_or("trace")).init();
     let (tx, rx) = tokio::sync::mpsc::channel::<()>(1);
 
-    ctrlc2::set_async_handler(async move {
-        tx.send(()).await.expect("Signal error");
-    })
-    .await;
+    ctrlc2::set_handler(move || {
+        tx.try_send(()).expect("Signal error");
+        true
+    })?;
 
     main_entry(rx).await?;
     Ok(())
@@ -13,12 +13,12 @@
 //  0. You just DO WHAT THE FUCK YOU WANT TO.
 
 use crate::error::{Error, Result};
-use std::net::{IpAddr, Ipv4Addr};
-use std::net::{SocketAddr, SocketAddrV4};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::net::{SocketAddr, SocketAddrV4, SocketAddrV6};
 
-/// Helper trait to convert things into IPv4 addresses.
+/// Helper trait to convert things into IP addresses.
 pub trait ToAddress {
-    /// Convert the type to an `Ipv4Addr`.
+    /// Convert the type to an `IpAddr`.
     fn to_address(&self) -> Result<IpAddr>;
 }
 
@@ -51,7 +51,7 @@ impl ToAddress for str {
     }
 }
 
-impl<'a> ToAddress for &'a str {
+impl ToAddress for &str {
     fn to_address(&self) -> Result<IpAddr> {
         (*self).to_address()
     }
@@ -63,7 +63,7 @@ impl ToAddress for String {
     }
 }
 
-impl<'a> ToAddress for &'a String {
+impl ToAddress for &String {
     fn to_address(&self) -> Result<IpAddr> {
         self.as_str().to_address()
     }
@@ -75,7 +75,19 @@ impl ToAddress for Ipv4Addr {
     }
 }
 
-impl<'a> ToAddress for &'a Ipv4Addr {
+impl ToAddress for &Ipv4Addr {
+    fn to_address(&self) -> Result<IpAddr> {
+        (*self).to_address()
+    }
+}
+
+impl ToAddress for Ipv6Addr {
+    fn to_address(&self) -> Result<IpAddr> {
+        Ok(IpAddr::V6(*self))
+    }
+}
+
+impl ToAddress for &Ipv6Addr {
     fn to_address(&self) -> Result<IpAddr> {
         (*self).to_address()
     }
@@ -87,7 +99,7 @@ impl ToAddress for IpAddr {
     }
 }
 
-impl<'a> ToAddress for &'a IpAddr {
+impl ToAddress for &IpAddr {
     fn to_address(&self) -> Result<IpAddr> {
         (*self).to_address()
     }
@@ -99,7 +111,19 @@ impl ToAddress for SocketAddrV4 {
     }
 }
 
-impl<'a> ToAddress for &'a SocketAddrV4 {
+impl ToAddress for &SocketAddrV4 {
+    fn to_address(&self) -> Result<IpAddr> {
+        (*self).to_address()
+    }
+}
+
+impl ToAddress for SocketAddrV6 {
+    fn to_address(&self) -> Result<IpAddr> {
+        Ok(IpAddr::V6(*self.ip()))
+    }
+}
+
+impl ToAddress for &SocketAddrV6 {
     fn to_address(&self) -> Result<IpAddr> {
         (*self).to_address()
     }
@@ -111,7 +135,7 @@ impl ToAddress for SocketAddr {
     }
 }
 
-impl<'a> ToAddress for &'a SocketAddr {
+impl ToAddress for &SocketAddr {
     fn to_address(&self) -> Result<IpAddr> {
         (*self).to_address()
     }
@@ -11,23 +11,29 @@
 //   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
 //
 //  0. You just DO WHAT THE FUCK YOU WANT TO.
-use bytes::{BufMut, BytesMut};
+use crate::configuration::Layer;
+use crate::virtio::VirtioNetHdr;
+use bytes::{Buf, BufMut, BytesMut};
+use std::time::SystemTime;
 use tokio_util::codec::{Decoder, Encoder};
 
 /// A TUN packet Encoder/Decoder.
+///
+/// The packets are decoded as `BytesMut` split off the read buffer, without copying them, and
+/// any `AsRef<[u8]>` packet, such as `Bytes`, `BytesMut` or `Vec<u8>`, can be encoded.
+/// A `Buf` made of several chunks is encoded once wrapped in a `BufPacket`.
 #[derive(Debug, Default)]
 pub struct TunPacketCodec(usize);
 
 impl TunPacketCodec {
-    /// Create a new `TunPacketCodec` specifying whether the underlying
-    ///  tunnel Device has enabled the packet information header.
+    /// Create a new `TunPacketCodec` reserving room for packets of `mtu` bytes.
     pub fn new(mtu: usize) -> TunPacketCodec {
         TunPacketCodec(mtu)
     }
 }
 
 impl Decoder for TunPacketCodec {
-    type Item = Vec<u8>;
+    type Item = BytesMut;
     type Error = std::io::Error;
 
     fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
@@ -37,17 +43,290 @@ impl Decoder for TunPacketCodec {
         let pkt = buf.split_to(buf.len());
         //reserve enough space for the next packet
         buf.reserve(self.0);
-        Ok(Some(pkt.freeze().to_vec()))
+        Ok(Some(pkt))
     }
 }
 
-impl Encoder<Vec<u8>> for TunPacketCodec {
+impl<T: AsRef<[u8]>> Encoder<T> for TunPacketCodec {
     type Error = std::io::Error;
 
-    fn encode(&mut self, item: Vec<u8>, dst: &mut BytesMut) -> Result<(), Self::Error> {
-        let bytes = item.as_slice();
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let bytes = item.as_ref();
         dst.reserve(bytes.len());
         dst.put(bytes);
         Ok(())
     }
 }
+
+/// A packet held in a `Buf`, possibly made of several chunks such as a header chained to a
+/// payload, to be encoded by a `TunPacketCodec`.
+#[derive(Debug)]
+pub struct BufPacket<B>(pub B);
+
+impl<B: Buf> Encoder<BufPacket<B>> for TunPacketCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: BufPacket<B>, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(item.0.remaining());
+        dst.put(item.0);
+        Ok(())
+    }
+}
+
+/// A `TunPacketCodec` decoding packets as `Vec<u8>`, for the code written before it moved to
+/// `BytesMut`, such as with `framed.map_codec(VecPacketCodec::from)`.
+///
+/// \[Note: Every packet is copied into its own `Vec<u8>`, which `TunPacketCodec` avoids. -- end note]
+#[derive(Debug, Default)]
+pub struct VecPacketCodec(TunPacketCodec);
+
+impl VecPacketCodec {
+    /// Create a new `VecPacketCodec` reserving room for packets of `mtu` bytes.
+    pub fn new(mtu: usize) -> VecPacketCodec {
+        VecPacketCodec(TunPacketCodec::new(mtu))
+    }
+}
+
+impl From<TunPacketCodec> for VecPacketCodec {
+    fn from(codec: TunPacketCodec) -> Self {
+        VecPacketCodec(codec)
+    }
+}
+
+impl Decoder for VecPacketCodec {
+    type Item = Vec<u8>;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        Ok(self.0.decode(buf)?.map(Vec::from))
+    }
+}
+
+impl<T: AsRef<[u8]>> Encoder<T> for VecPacketCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: T, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        self.0.encode(item, dst)
+    }
+}
+
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86dd;
+
+/// The protocol of the packet information, an address family on macOS and an EtherType elsewhere.
+fn pi_protocol(pi: u32) -> u16 {
+    #[cfg(target_os = "macos")]
+    return match pi as i32 {
+        libc::AF_INET => ETH_P_IP,
+        libc::AF_INET6 => ETH_P_IPV6,
+        _ => 0,
+    };
+    #[cfg(not(target_os = "macos"))]
+    return pi as u16;
+}
+
+/// The packet information of `protocol`, the reverse of `pi_protocol`.
+fn packet_information(protocol: u16) -> u32 {
+    #[cfg(target_os = "macos")]
+    return match protocol {
+        ETH_P_IPV6 => libc::AF_INET6 as u32,
+        _ => libc::AF_INET as u32,
+    };
+    #[cfg(not(target_os = "macos"))]
+    return protocol as u32;
+}
+
+/// A packet along with the metadata of the headers in front of it, the item of a `TunFrameCodec`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TunPacket {
+    /// The IP packet, or the Ethernet frame of a `Layer::L2` device.
+    pub payload: BytesMut,
+    /// The EtherType of the packet, such as `ETH_P_IP` or `ETH_P_IPV6`, from the packet
+    /// information when the device has it, even on macOS where it holds the address family.
+    /// Zero is derived from the payload on encode.
+    pub protocol: u16,
+    /// The virtio header of a device with `vnet_hdr` enabled, `None` sends a default header.
+    pub vnet_hdr: Option<VirtioNetHdr>,
+    /// When the packet was received, `None` for the packets built to be sent.
+    pub received_at: Option<SystemTime>,
+}
+
+impl TunPacket {
+    /// Create a packet to be sent, its protocol derived from the IP version of `payload`.
+    pub fn new(payload: impl Into<BytesMut>) -> Self {
+        let payload = payload.into();
+        TunPacket {
+            protocol: protocol_of(&payload, false),
+            payload,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_ipv4(&self) -> bool {
+        self.protocol == ETH_P_IP
+    }
+
+    pub fn is_ipv6(&self) -> bool {
+        self.protocol == ETH_P_IPV6
+    }
+}
+
+/// The EtherType of an IP packet from its version, or of an Ethernet frame, zero if unknown.
+fn protocol_of(payload: &[u8], l2: bool) -> u16 {
+    if l2 {
+        return payload
+            .get(12..14)
+            .map_or(0, |ty| u16::from_be_bytes([ty[0], ty[1]]));
+    }
+    match payload.first().map(|byte| byte >> 4) {
+        Some(4) => ETH_P_IP,
+        Some(6) => ETH_P_IPV6,
+        _ => 0,
+    }
+}
+
+/// A TUN packet Encoder/Decoder of `TunPacket`s, exchanging the packets along with the packet
+/// information and virtio header in front of them, which the device leaves to the codec in the
+/// `Framed` returned by `AsyncDevice::into_framed_packets`.
+///
+/// The packet information is the Linux `struct tun_pi`, whose protocol is an EtherType, or the
+/// address family in front of the packets of a macOS utun device, which is translated from and to
+/// the EtherType of the `TunPacket`s.
+#[derive(Debug, Default)]
+pub struct TunFrameCodec {
+    mtu: usize,
+    packet_information: bool,
+    vnet_hdr_len: usize,
+    l2: bool,
+}
+
+impl TunFrameCodec {
+    /// Create a new `TunFrameCodec` reserving room for packets of `mtu` bytes, without any
+    /// header in front of them.
+    pub fn new(mtu: usize) -> TunFrameCodec {
+        TunFrameCodec {
+            mtu,
+            ..Default::default()
+        }
+    }
+
+    /// Set whether the packets are preceded by the packet information.
+    pub fn packet_information(&mut self, value: bool) -> &mut Self {
+        self.packet_information = value;
+        self
+    }
+
+    /// Set the length of the virtio header preceding the packets, zero without any.
+    pub fn vnet_hdr_len(&mut self, value: usize) -> &mut Self {
+        self.vnet_hdr_len = value;
+        self
+    }
+
+    /// Set the layer of the device, whose Ethernet frames carry their protocol.
+    pub fn layer(&mut self, value: Layer) -> &mut Self {
+        self.l2 = value == Layer::L2;
+        self
+    }
+
+    fn pi_len(&self) -> usize {
+        if self.packet_information {
+            crate::PACKET_INFORMATION_LENGTH
+        } else {
+            0
+        }
+    }
+}
+
+impl Decoder for TunFrameCodec {
+    type Item = TunPacket;
+    type Error = std::io::Error;
+
+    fn decode(&mut self, buf: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
+        if buf.is_empty() {
+            return Ok(None);
+        }
+        let received_at = SystemTime::now();
+        let mut payload = buf.split_to(buf.len());
+        //reserve enough space for the next packet
+        buf.reserve(self.mtu + self.pi_len() + self.vnet_hdr_len);
+
+        if payload.len() < self.pi_len() + self.vnet_hdr_len {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Truncated packet headers",
+            ));
+        }
+        let pi = payload.split_to(self.pi_len());
+        let vnet_hdr = (self.vnet_hdr_len != 0).then(|| {
+            let hdr = VirtioNetHdr::decode(&payload[..self.vnet_hdr_len]);
+            payload.advance(self.vnet_hdr_len);
+            hdr.unwrap_or_default()
+        });
+        let protocol = match pi[..] {
+            [a, b, c, d] => pi_protocol(u32::from_be_bytes([a, b, c, d])),
+            _ => 0,
+        };
+        // Without packet information, the packet itself tells its protocol.
+        let protocol = match protocol {
+            0 => protocol_of(&payload, self.l2),
+            protocol => protocol,
+        };
+        Ok(Some(TunPacket {
+            payload,
+            protocol,
+            vnet_hdr,
+            received_at: Some(received_at),
+        }))
+    }
+}
+
+impl Encoder<TunPacket> for TunFrameCodec {
+    type Error = std::io::Error;
+
+    fn encode(&mut self, item: TunPacket, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        dst.reserve(self.pi_len() + self.vnet_hdr_len + item.payload.len());
+        if self.packet_information {
+            let protocol = match item.protocol {
+                0 => protocol_of(&item.payload, self.l2),
+                protocol => protocol,
+            };
+            dst.put_u32(packet_information(protocol));
+        }
+        if self.vnet_hdr_len != 0 {
+            let start = dst.len();
+            dst.put_bytes(0, self.vnet_hdr_len);
+            item.vnet_hdr.unwrap_or_default().encode(&mut dst[start..]);
+        }
+        dst.put(item.payload);
+        Ok(())
+    }
+}
+
+#[test]
+fn test_codec() {
+    let mut codec = TunPacketCodec::new(1500);
+    let mut buf = BytesMut::with_capacity(1500);
+    buf.put_slice(&[0x45; 60]);
+    let start = buf.as_ptr();
+    let packet = codec.decode(&mut buf).unwrap().unwrap();
+    // The packet is the read buffer itself, not a copy of it.
+    assert_eq!(packet.as_ptr(), start);
+    assert_eq!(packet, [0x45; 60][..]);
+    assert!(buf.is_empty() && buf.capacity() >= 1500);
+    assert!(codec.decode(&mut buf).unwrap().is_none());
+
+    let mut dst = BytesMut::new();
+    codec.encode(packet.freeze(), &mut dst).unwrap();
+    codec.encode(vec![1, 2], &mut dst).unwrap();
+    codec.encode(&[3][..], &mut dst).unwrap();
+    let chained = bytes::Bytes::from_static(&[4, 5]).chain(&[6][..]);
+    codec.encode(BufPacket(chained), &mut dst).unwrap();
+    assert_eq!(dst.len(), 66);
+    assert_eq!(dst[60..], [1, 2, 3, 4, 5, 6]);
+
+    let mut codec = VecPacketCodec::from(codec);
+    let packet: Vec<u8> = codec.decode(&mut dst).unwrap().unwrap();
+    assert_eq!(packet.len(), 66);
+    codec.encode(packet, &mut dst).unwrap();
+    assert_eq!(dst.len(), 66);
+}
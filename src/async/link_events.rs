@@ -0,0 +1,67 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use futures_core::{ready, Stream};
+use std::collections::VecDeque;
+use tokio::io::unix::AsyncFd;
+
+use crate::platform::linux::events::LinkMonitor;
+use crate::LinkEvent;
+
+/// A Stream of the changes of an interface, returned by [`AsyncDevice::events`](super::AsyncDevice::events).
+///
+/// It ends once the interface is deleted, after yielding `LinkEvent::Deleted`.
+///
+/// [Note: When the notifications come faster than they are consumed, the kernel drops some
+/// of them and the Stream yields an `ENOBUFS` error. -- end note]
+pub struct LinkEvents {
+    inner: AsyncFd<LinkMonitor>,
+    pending: VecDeque<LinkEvent>,
+}
+
+impl LinkEvents {
+    pub(crate) fn new(monitor: LinkMonitor) -> std::io::Result<Self> {
+        // SAFETY: `LinkMonitor` owns its socket and keeps it open until dropped.
+        let inner = unsafe { AsyncFd::register(monitor)? };
+        Ok(LinkEvents {
+            inner,
+            pending: VecDeque::new(),
+        })
+    }
+}
+
+impl Stream for LinkEvents {
+    type Item = std::io::Result<LinkEvent>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        loop {
+            if let Some(event) = this.pending.pop_front() {
+                return Poll::Ready(Some(Ok(event)));
+            }
+            if this.inner.get_ref().is_deleted() {
+                return Poll::Ready(None);
+            }
+            let mut guard = ready!(this.inner.poll_read_ready_mut(cx))?;
+            let pending = &mut this.pending;
+            match guard.try_io(|inner| inner.get_mut().recv(pending)) {
+                Ok(Ok(())) => continue,
+                Ok(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
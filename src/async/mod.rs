@@ -20,12 +20,22 @@ use crate::configuration::Configuration;
 use crate::platform::create;
 
 mod codec;
-pub use codec::TunPacketCodec;
+pub use codec::{BufPacket, TunFrameCodec, TunPacket, TunPacketCodec, VecPacketCodec};
 
 #[cfg(unix)]
 mod unix_device;
 #[cfg(unix)]
-pub use unix_device::{AsyncDevice, DeviceReader, DeviceWriter};
+pub use unix_device::{AsyncDevice, DeviceReader, DeviceWriter, FramedBatch};
+
+#[cfg(target_os = "linux")]
+mod link_events;
+#[cfg(target_os = "linux")]
+pub use link_events::LinkEvents;
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring_device;
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub use uring_device::UringDevice;
 
 #[cfg(target_os = "windows")]
 mod win_device;
@@ -37,3 +47,21 @@ pub fn create_as_async(configuration: &Configuration) -> Result<AsyncDevice, err
     let device = create(configuration)?;
     AsyncDevice::new(device).map_err(|err| err.into())
 }
+
+/// Create a TUN device with the given name, driven by io_uring.
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+pub fn create_as_uring(configuration: &Configuration) -> Result<UringDevice, error::Error> {
+    let device = create(configuration)?;
+    UringDevice::new(device).map_err(|err| err.into())
+}
+
+/// Create a multi-queue TUN device, returning one `AsyncDevice` per queue.
+#[cfg(target_os = "linux")]
+pub fn create_queues_as_async(
+    configuration: &Configuration,
+) -> Result<Vec<AsyncDevice>, error::Error> {
+    crate::platform::create_queues(configuration)?
+        .into_iter()
+        .map(|device| AsyncDevice::new(device).map_err(|err| err.into()))
+        .collect()
+}
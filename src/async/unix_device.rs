@@ -14,21 +14,34 @@
 
 use core::pin::Pin;
 use core::task::{Context, Poll};
-use futures_core::ready;
+use futures_core::{ready, Stream};
+use futures_sink::Sink;
+use std::collections::VecDeque;
 use std::io::{IoSlice, Read, Write};
 use tokio::io::unix::AsyncFd;
 use tokio::io::Interest;
 use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 use tokio_util::codec::Framed;
 
+#[cfg(target_os = "linux")]
+use super::LinkEvents;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use super::TunFrameCodec;
 use super::TunPacketCodec;
 use crate::device::AbstractDevice;
-use crate::platform::posix::{Reader, Writer};
+use crate::platform::posix::{IoStats, Reader, Writer};
 use crate::platform::Device;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use crate::Layer;
+#[cfg(target_os = "linux")]
+use crate::VirtioNetHdr;
 
 /// An async TUN device wrapper around a TUN device.
 pub struct AsyncDevice {
     inner: AsyncFd<Device>,
+    /// Whether the packet information and virtio header are left to a `TunFrameCodec`.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    frames: bool,
 }
 
 /// Returns a shared reference to the underlying Device object.
@@ -47,22 +60,60 @@ impl core::ops::DerefMut for AsyncDevice {
     }
 }
 
+impl AsRef<dyn AbstractDevice + 'static> for AsyncDevice {
+    fn as_ref(&self) -> &(dyn AbstractDevice + 'static) {
+        self.inner.get_ref()
+    }
+}
+
 impl AsyncDevice {
     /// Create a new `AsyncDevice` wrapping around a `Device`.
     pub fn new(device: Device) -> std::io::Result<AsyncDevice> {
         device.set_nonblock()?;
+        // SAFETY: `Device` owns its file descriptor and keeps it open until dropped.
+        let inner = unsafe { AsyncFd::register(device)? };
         Ok(AsyncDevice {
-            inner: AsyncFd::new(device)?,
+            inner,
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            frames: false,
         })
     }
 
     /// Consumes this AsyncDevice and return a Framed object (unified Stream and Sink interface)
     pub fn into_framed(self) -> Framed<Self, TunPacketCodec> {
-        let mtu = self.mtu().unwrap_or(crate::DEFAULT_MTU);
-        let codec = TunPacketCodec::new(mtu as usize);
+        let mtu = self.mtu().unwrap_or(crate::DEFAULT_MTU) as usize;
+        // leave room for the Ethernet header of the frames of a TAP device
+        let capacity = mtu + crate::MAX_ETHERNET_HEADER_LENGTH;
+        let codec = TunPacketCodec::new(capacity);
         // associate mtu with the capacity of ReadBuf
-        Framed::with_capacity(self, codec, mtu as usize)
+        Framed::with_capacity(self, codec, capacity)
+    }
+
+    /// Consumes this AsyncDevice and return a Framed object of `TunPacket`s, which carry the
+    /// protocol, virtio header and receive time of the packets, and whose packet information and
+    /// virtio header are encoded from the `TunPacket`s sent.
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn into_framed_packets(mut self) -> Framed<Self, TunFrameCodec> {
+        let tun = self.tun();
+        let mtu = self.mtu().unwrap_or(crate::DEFAULT_MTU) as usize;
+        // leave room for the Ethernet header of the frames of a TAP device
+        let mtu = mtu + crate::MAX_ETHERNET_HEADER_LENGTH;
+        let mut codec = TunFrameCodec::new(mtu);
+        codec
+            .packet_information(tun.packet_information())
+            .vnet_hdr_len(tun.reader.vnet_hdr_len)
+            .layer(if tun.writer.l2 { Layer::L2 } else { Layer::L3 });
+        let capacity = mtu + tun.reader.offset;
+        self.frames = true;
+        Framed::with_capacity(self, codec, capacity)
     }
+
+    /// Consumes this AsyncDevice and return a `FramedBatch` object, a Stream yielding all the
+    /// packets available on every wakeup, up to `capacity`, and a Sink of packet batches.
+    pub fn into_framed_batch(self, capacity: usize) -> FramedBatch {
+        FramedBatch::new(self, capacity)
+    }
+
     pub fn split(self) -> std::io::Result<(DeviceWriter, DeviceReader)> {
         let device = self.inner.into_inner();
         let (reader, writer) = device.split();
@@ -86,6 +137,56 @@ impl AsyncDevice {
             .async_io(Interest::WRITABLE, |inner| inner.send(buf))
             .await
     }
+
+    /// Recv all the packets available from tun device into `bufs`, waiting for the first one,
+    /// their lengths are stored in `sizes`
+    pub async fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        let guard = self.inner.readable().await?;
+        guard
+            .get_ref()
+            .async_io(Interest::READABLE, |inner| inner.recv_many(bufs, sizes))
+            .await
+    }
+
+    /// Send packets to tun device until it would block, waiting for the first one to be sent
+    pub async fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        let guard = self.inner.writable().await?;
+        guard
+            .get_ref()
+            .async_io(Interest::WRITABLE, |inner| inner.send_many(packets))
+            .await
+    }
+
+    /// Subscribe to the changes of the interface, such as being brought down by someone else,
+    /// as a Stream of `LinkEvent`s.
+    #[cfg(target_os = "linux")]
+    pub fn events(&self) -> std::io::Result<LinkEvents> {
+        LinkEvents::new(self.inner.get_ref().link_monitor()?)
+    }
+
+    /// Recv a packet and its virtio header from tun device
+    #[cfg(target_os = "linux")]
+    pub async fn recv_with_hdr(&self, buf: &mut [u8]) -> std::io::Result<(VirtioNetHdr, usize)> {
+        let guard = self.inner.readable().await?;
+        guard
+            .get_ref()
+            .async_io(Interest::READABLE, |inner| inner.recv_with_hdr(buf))
+            .await
+    }
+
+    /// Send a packet and its virtio header to tun device
+    #[cfg(target_os = "linux")]
+    pub async fn send_with_hdr(&self, hdr: &VirtioNetHdr, buf: &[u8]) -> std::io::Result<usize> {
+        let guard = self.inner.writable().await?;
+        guard
+            .get_ref()
+            .async_io(Interest::WRITABLE, |inner| inner.send_with_hdr(hdr, buf))
+            .await
+    }
 }
 
 impl AsyncRead for AsyncDevice {
@@ -94,9 +195,18 @@ impl AsyncRead for AsyncDevice {
         cx: &mut Context<'_>,
         buf: &mut ReadBuf,
     ) -> Poll<std::io::Result<()>> {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        let frames = self.frames;
         loop {
             let mut guard = ready!(self.inner.poll_read_ready_mut(cx))?;
             let rbuf = buf.initialize_unfilled();
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            if frames {
+                match guard.try_io(|inner| inner.get_ref().tun().reader.recv_frame(rbuf)) {
+                    Ok(res) => return Poll::Ready(res.map(|n| buf.advance(n))),
+                    Err(_wb) => continue,
+                }
+            }
             match guard.try_io(|inner| inner.get_mut().read(rbuf)) {
                 Ok(res) => return Poll::Ready(res.map(|n| buf.advance(n))),
                 Err(_wb) => continue,
@@ -111,8 +221,17 @@ impl AsyncWrite for AsyncDevice {
         cx: &mut Context<'_>,
         buf: &[u8],
     ) -> Poll<std::io::Result<usize>> {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        let frames = self.frames;
         loop {
             let mut guard = ready!(self.inner.poll_write_ready_mut(cx))?;
+            #[cfg(any(target_os = "linux", target_os = "macos"))]
+            if frames {
+                match guard.try_io(|inner| inner.get_ref().tun().writer.send_frame(buf)) {
+                    Ok(res) => return Poll::Ready(res),
+                    Err(_wb) => continue,
+                }
+            }
             match guard.try_io(|inner| inner.get_mut().write(buf)) {
                 Ok(res) => return Poll::Ready(res),
                 Err(_wb) => continue,
@@ -139,6 +258,14 @@ impl AsyncWrite for AsyncDevice {
         cx: &mut Context<'_>,
         bufs: &[IoSlice<'_>],
     ) -> Poll<std::io::Result<usize>> {
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        if self.frames {
+            let buf = bufs
+                .iter()
+                .find(|buf| !buf.is_empty())
+                .map_or(&[][..], |buf| &**buf);
+            return self.poll_write(cx, buf);
+        }
         loop {
             let mut guard = ready!(self.inner.poll_write_ready_mut(cx))?;
             match guard.try_io(|inner| inner.get_mut().write_vectored(bufs)) {
@@ -149,6 +276,11 @@ impl AsyncWrite for AsyncDevice {
     }
 
     fn is_write_vectored(&self) -> bool {
+        // The frames are written one buffer at a time.
+        #[cfg(any(target_os = "linux", target_os = "macos"))]
+        if self.frames {
+            return false;
+        }
         true
     }
 }
@@ -157,11 +289,30 @@ pub struct DeviceReader {
 }
 impl DeviceReader {
     fn new(reader: Reader) -> std::io::Result<Self> {
-        Ok(Self {
-            inner: AsyncFd::new(reader)?,
-        })
+        // SAFETY: the `Reader` shares ownership of the file descriptor, which stays open until dropped.
+        let inner = unsafe { AsyncFd::register(reader)? };
+        Ok(Self { inner })
+    }
+}
+impl DeviceReader {
+    /// Get the counters of the packets read so far.
+    pub fn stats(&self) -> IoStats {
+        self.inner.get_ref().stats()
+    }
+
+    /// Recv all the packets available into `bufs`, waiting for the first one,
+    /// their lengths are stored in `sizes`
+    pub async fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        self.inner
+            .async_io(Interest::READABLE, |inner| inner.recv_many(bufs, sizes))
+            .await
     }
 }
+
 impl AsyncRead for DeviceReader {
     fn poll_read(
         mut self: Pin<&mut Self>,
@@ -184,9 +335,23 @@ pub struct DeviceWriter {
 }
 impl DeviceWriter {
     fn new(writer: Writer) -> std::io::Result<Self> {
-        Ok(Self {
-            inner: AsyncFd::new(writer)?,
-        })
+        // SAFETY: the `Writer` shares ownership of the file descriptor, which stays open until dropped.
+        let inner = unsafe { AsyncFd::register(writer)? };
+        Ok(Self { inner })
+    }
+}
+
+impl DeviceWriter {
+    /// Get the counters of the packets written so far.
+    pub fn stats(&self) -> IoStats {
+        self.inner.get_ref().stats()
+    }
+
+    /// Send packets until the device would block, waiting for the first one to be sent
+    pub async fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        self.inner
+            .async_io(Interest::WRITABLE, |inner| inner.send_many(packets))
+            .await
     }
 }
 
@@ -237,3 +402,119 @@ impl AsyncWrite for DeviceWriter {
         true
     }
 }
+
+/// A Stream and Sink of packet batches, the batched counterpart of the `Framed` returned by
+/// [`AsyncDevice::into_framed`].
+///
+/// Every item yielded holds all the packets available when the device became readable, and
+/// the packets of every batch sent are written with as few wakeups as possible. A packet which
+/// cannot be sent is dropped, its error being returned when flushing.
+pub struct FramedBatch {
+    device: AsyncDevice,
+    mtu: usize,
+    capacity: usize,
+    bufs: Vec<Vec<u8>>,
+    sizes: Vec<usize>,
+    pending: VecDeque<Vec<u8>>,
+}
+
+impl FramedBatch {
+    /// Create a new `FramedBatch` yielding batches of up to `capacity` packets.
+    pub fn new(device: AsyncDevice, capacity: usize) -> Self {
+        let mtu =
+            device.mtu().unwrap_or(crate::DEFAULT_MTU) as usize + crate::MAX_ETHERNET_HEADER_LENGTH;
+        let capacity = capacity.max(1);
+        FramedBatch {
+            device,
+            mtu,
+            capacity,
+            bufs: Vec::with_capacity(capacity),
+            sizes: vec![0; capacity],
+            pending: VecDeque::new(),
+        }
+    }
+
+    /// Returns a reference to the underlying device.
+    pub fn get_ref(&self) -> &AsyncDevice {
+        &self.device
+    }
+
+    /// Returns a mutable reference to the underlying device.
+    pub fn get_mut(&mut self) -> &mut AsyncDevice {
+        &mut self.device
+    }
+
+    /// Consumes the `FramedBatch`, returning the underlying device, the packets not flushed yet are lost.
+    pub fn into_inner(self) -> AsyncDevice {
+        self.device
+    }
+}
+
+impl Stream for FramedBatch {
+    type Item = std::io::Result<Vec<Vec<u8>>>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mtu = this.mtu;
+        this.bufs.resize_with(this.capacity, || vec![0; mtu]);
+        loop {
+            let mut guard = ready!(this.device.inner.poll_read_ready(cx))?;
+            let (bufs, sizes) = (&mut this.bufs, &mut this.sizes);
+            match guard.try_io(|inner| inner.get_ref().recv_many(bufs, sizes)) {
+                Ok(Ok(count)) => {
+                    let mut packets: Vec<Vec<u8>> = this.bufs.drain(..count).collect();
+                    for (packet, size) in packets.iter_mut().zip(&this.sizes) {
+                        packet.truncate(*size);
+                    }
+                    return Poll::Ready(Some(Ok(packets)));
+                }
+                Ok(Err(err)) => return Poll::Ready(Some(Err(err))),
+                Err(_would_block) => continue,
+            }
+        }
+    }
+}
+
+impl FramedBatch {
+    fn poll_send_pending(&mut self, cx: &mut Context<'_>) -> Poll<std::io::Result<()>> {
+        while !self.pending.is_empty() {
+            let mut guard = ready!(self.device.inner.poll_write_ready(cx))?;
+            let packets = self.pending.make_contiguous();
+            match guard.try_io(|inner| inner.get_ref().send_many(packets)) {
+                Ok(Ok(count)) => drop(self.pending.drain(..count)),
+                // The packet failing is dropped, so that the next ones can still be sent.
+                Ok(Err(err)) => {
+                    self.pending.pop_front();
+                    return Poll::Ready(Err(err));
+                }
+                Err(_would_block) => continue,
+            }
+        }
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl Sink<Vec<Vec<u8>>> for FramedBatch {
+    type Error = std::io::Error;
+
+    fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        let this = self.get_mut();
+        if this.pending.len() >= this.capacity {
+            ready!(this.poll_send_pending(cx))?;
+        }
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, item: Vec<Vec<u8>>) -> Result<(), Self::Error> {
+        self.get_mut().pending.extend(item);
+        Ok(())
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.get_mut().poll_send_pending(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.poll_flush(cx)
+    }
+}
@@ -0,0 +1,435 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use core::pin::{pin, Pin};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Mutex, MutexGuard, PoisonError};
+use tokio::io::unix::AsyncFd;
+use tokio::sync::futures::Notified;
+use tokio::sync::Notify;
+
+use crate::device::AbstractDevice;
+use crate::platform::linux::uring::{
+    cancel, rw_fixed, Ring, IORING_OP_READ_FIXED, IORING_OP_WRITE_FIXED,
+};
+use crate::platform::posix::Fd;
+use crate::platform::Device;
+use crate::VirtioNetHdr;
+
+/// User data of the cancellations issued when dropping the ring.
+const CANCEL: u64 = u64::MAX;
+
+/// The ring and its registered buffers, the first `reads` of which always have a read in flight.
+struct State {
+    ring: Ring,
+    buffers: Vec<Box<[u8]>>,
+    reads: usize,
+    fd: RawFd,
+    /// Completed reads, in order.
+    received: VecDeque<(usize, i32)>,
+    free_writes: Vec<usize>,
+    written: HashMap<usize, i32>,
+    /// Writes whose sender is gone, their buffer is freed on completion.
+    abandoned: HashSet<usize>,
+    in_flight: usize,
+    /// Whether a cancellation failed, leaving its request in flight.
+    cancel_failed: bool,
+}
+
+fn queue_full() -> std::io::Error {
+    std::io::Error::other("io_uring submission queue is full")
+}
+
+impl State {
+    fn push(&mut self, opcode: u8, index: usize, len: usize) -> std::io::Result<()> {
+        let addr = self.buffers[index].as_ptr();
+        let sqe = rw_fixed(opcode, self.fd, addr, len, index as u16, index as u64);
+        if !self.ring.push(sqe) {
+            return Err(queue_full());
+        }
+        self.in_flight += 1;
+        Ok(())
+    }
+
+    fn push_read(&mut self, index: usize) -> std::io::Result<()> {
+        self.push(IORING_OP_READ_FIXED, index, self.buffers[index].len())
+    }
+
+    fn reap(&mut self) {
+        let State {
+            ring,
+            reads,
+            received,
+            free_writes,
+            written,
+            abandoned,
+            in_flight,
+            cancel_failed,
+            ..
+        } = self;
+        ring.reap(|user_data, res| {
+            if user_data == CANCEL {
+                // The request is already done, or being interrupted, otherwise it stays in flight.
+                *cancel_failed |= res < 0 && res != -libc::ENOENT && res != -libc::EALREADY;
+                return;
+            }
+            *in_flight -= 1;
+            let index = user_data as usize;
+            if index < *reads {
+                received.push_back((index, res));
+            } else if abandoned.remove(&index) {
+                free_writes.push(index);
+            } else {
+                written.insert(index, res);
+            }
+        });
+    }
+
+    /// Copy the packet read in the buffer `index`, which is read into again.
+    fn take_packet(
+        &mut self,
+        index: usize,
+        res: i32,
+        offset: usize,
+        buf: &mut [u8],
+    ) -> std::io::Result<usize> {
+        let result = if res < 0 {
+            Err(std::io::Error::from_raw_os_error(-res))
+        } else {
+            let packet = &self.buffers[index][offset.min(res as usize)..res as usize];
+            let len = packet.len().min(buf.len());
+            buf[..len].copy_from_slice(&packet[..len]);
+            Ok(len)
+        };
+        self.push_read(index)?;
+        result
+    }
+}
+
+impl State {
+    /// Cancel the requests in flight one by one, cancelling them all at once needs Linux 5.19,
+    /// and wait for them, returning `false` if they may still be in flight.
+    fn cancel_all(&mut self) -> bool {
+        self.reap();
+        let done: HashSet<usize> = (self.received.iter().map(|(index, _)| *index))
+            .chain(self.free_writes.iter().copied())
+            .chain(self.written.keys().copied())
+            .collect();
+        for index in (0..self.buffers.len()).filter(|index| !done.contains(index)) {
+            let sqe = cancel(index as u64, CANCEL);
+            if !self.ring.push(sqe) && (self.ring.submit(0).is_err() || !self.ring.push(sqe)) {
+                return false;
+            }
+        }
+        while self.in_flight > 0 {
+            if self.cancel_failed || self.ring.submit(1).is_err() {
+                return false;
+            }
+            self.reap();
+        }
+        true
+    }
+}
+
+impl Drop for State {
+    fn drop(&mut self) {
+        // The kernel must be done with the buffers before they are freed, they are leaked otherwise.
+        if self.in_flight > 0 && !self.cancel_all() {
+            std::mem::forget(std::mem::take(&mut self.buffers));
+        }
+    }
+}
+
+/// The write buffers of a batch, released even if the sender stops waiting for them.
+struct Writes<'a> {
+    device: &'a UringDevice,
+    indices: Vec<usize>,
+}
+
+impl Drop for Writes<'_> {
+    fn drop(&mut self) {
+        let mut state = self.device.state();
+        for index in self.indices.drain(..) {
+            if state.written.remove(&index).is_some() {
+                state.free_writes.push(index);
+            } else {
+                state.abandoned.insert(index);
+            }
+        }
+        drop(state);
+        self.device.notify.notify_waiters();
+    }
+}
+
+/// An async TUN device driven by io_uring, with the same API as `AsyncDevice`.
+///
+/// Reads are kept in flight on pre-registered buffers, so packets are received
+/// without a syscall per packet, and writes are submitted in batches.
+///
+/// [Note: The device is switched to blocking mode, io_uring reports non-blocking reads which
+/// would block as errors. The device cannot be borrowed mutably, as the buffers are sized for
+/// its MTU and header when wrapped. -- end note]
+pub struct UringDevice {
+    state: Mutex<State>,
+    notify: Notify,
+    event: AsyncFd<Fd>,
+    offset: usize,
+    device: Device,
+}
+
+/// Returns a shared reference to the underlying Device object.
+impl core::ops::Deref for UringDevice {
+    type Target = Device;
+
+    fn deref(&self) -> &Self::Target {
+        &self.device
+    }
+}
+
+impl UringDevice {
+    /// Create a new `UringDevice` wrapping around a `Device`, with 32 read and 32 write buffers.
+    pub fn new(device: Device) -> std::io::Result<UringDevice> {
+        Self::with_buffers(device, 32)
+    }
+
+    /// Create a new `UringDevice` wrapping around a `Device`, with `count` buffers for the reads
+    /// kept in flight and as many for the writes, each as large as the MTU of the device
+    /// plus an Ethernet header, or as a super-packet with the segmentation offloads.
+    pub fn with_buffers(device: Device, count: usize) -> std::io::Result<UringDevice> {
+        let count = count.clamp(1, 1024);
+        let segmentation =
+            libc::TUN_F_TSO4 | libc::TUN_F_TSO6 | libc::TUN_F_USO4 | libc::TUN_F_USO6;
+        let mtu = if device.offload() & segmentation != 0 {
+            u16::MAX as usize
+        } else {
+            device.mtu().unwrap_or(crate::DEFAULT_MTU) as usize
+        };
+        let mtu = mtu + crate::MAX_ETHERNET_HEADER_LENGTH;
+        let offset = device.tun().reader.offset;
+        device.tun().reader.fd.clear_nonblock()?;
+        let mut buffers: Vec<Box<[u8]>> = (0..count * 2)
+            .map(|_| vec![0_u8; mtu + offset].into_boxed_slice())
+            .collect();
+
+        let ring = Ring::new(count as u32 * 2)?;
+        let iovecs: Vec<libc::iovec> = buffers
+            .iter_mut()
+            .map(|buffer| libc::iovec {
+                iov_base: buffer.as_mut_ptr() as *mut libc::c_void,
+                iov_len: buffer.len(),
+            })
+            .collect();
+        ring.register_buffers(&iovecs)?;
+
+        let event = unsafe { libc::eventfd(0, libc::EFD_NONBLOCK | libc::EFD_CLOEXEC) };
+        let event = Fd::new(event, true).map_err(|_| std::io::Error::last_os_error())?;
+        ring.register_eventfd(event.as_raw_fd())?;
+
+        let mut state = State {
+            ring,
+            buffers,
+            reads: count,
+            fd: device.as_raw_fd(),
+            received: VecDeque::with_capacity(count),
+            free_writes: (count..count * 2).collect(),
+            written: HashMap::new(),
+            abandoned: HashSet::new(),
+            in_flight: 0,
+            cancel_failed: false,
+        };
+        for index in 0..count {
+            state.push_read(index)?;
+        }
+        state.ring.submit(0)?;
+
+        // SAFETY: the eventfd is owned by the `Fd`, which stays open until dropped.
+        let event = unsafe { AsyncFd::register(event)? };
+        Ok(UringDevice {
+            state: Mutex::new(state),
+            notify: Notify::new(),
+            event,
+            offset,
+            device,
+        })
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wait for new completions, or for another task to have reaped some.
+    async fn wait(&self, notified: Pin<&mut Notified<'_>>) -> std::io::Result<()> {
+        tokio::select! {
+            guard = self.event.readable() => {
+                let mut guard = guard?;
+                let mut value = 0_u64;
+                unsafe { libc::read(self.event.as_raw_fd(), &mut value as *mut u64 as *mut _, 8) };
+                guard.clear_ready();
+                self.notify.notify_waiters();
+            }
+            _ = notified => {}
+        }
+        Ok(())
+    }
+
+    /// Reap the completions and call `f` until it returns a result.
+    async fn poll_state<T, F>(&self, mut f: F) -> std::io::Result<T>
+    where
+        F: FnMut(&mut State) -> Option<std::io::Result<T>>,
+    {
+        loop {
+            let mut notified = pin!(self.notify.notified());
+            notified.as_mut().enable();
+            let result = {
+                let mut state = self.state();
+                state.reap();
+                f(&mut state)
+            };
+            if let Some(result) = result {
+                return result;
+            }
+            self.wait(notified).await?;
+        }
+    }
+
+    /// Recv a packet from tun device
+    pub async fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let offset = self.offset;
+        self.poll_state(|state| {
+            let (index, res) = state.received.pop_front()?;
+            let result = state.take_packet(index, res, offset, buf);
+            Some(state.ring.submit(0).and(result))
+        })
+        .await
+    }
+
+    /// Recv all the packets available from tun device into `bufs`, waiting for the first one,
+    /// their lengths are stored in `sizes`
+    pub async fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        let count = bufs.len().min(sizes.len());
+        if count == 0 {
+            return Ok(0);
+        }
+        let offset = self.offset;
+        self.poll_state(|state| {
+            if state.received.is_empty() {
+                return None;
+            }
+            let mut received = 0;
+            while let Some(&(index, res)) = state.received.front() {
+                // An error is reported by the next call.
+                if received == count || (res < 0 && received > 0) {
+                    break;
+                }
+                state.received.pop_front();
+                match state.take_packet(index, res, offset, bufs[received].as_mut()) {
+                    Ok(size) => sizes[received] = size,
+                    Err(err) => return Some(state.ring.submit(0).and(Err(err))),
+                }
+                received += 1;
+            }
+            Some(state.ring.submit(0).map(|_| received))
+        })
+        .await
+    }
+
+    /// Write as many packets as there are free buffers, waiting for the first one to be free,
+    /// and return the results of the writes.
+    async fn write<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<Vec<i32>> {
+        let offset = self.offset;
+        let writer = &self.device.tun().writer;
+        let indices = self
+            .poll_state(|state| {
+                state.free_writes.last()?;
+                let mut indices = Vec::with_capacity(packets.len());
+                for packet in packets {
+                    let packet = packet.as_ref();
+                    let Some(index) = state.free_writes.pop() else {
+                        break;
+                    };
+                    let buffer = &mut state.buffers[index];
+                    let len = offset + packet.len();
+                    let filled = if len > buffer.len() {
+                        Err(std::io::ErrorKind::InvalidInput.into())
+                    } else {
+                        let hdr = VirtioNetHdr::default();
+                        buffer[offset..len].copy_from_slice(packet);
+                        writer.fill_prefix(&mut buffer[..offset], packet, &hdr)
+                    };
+                    if let Err(err) =
+                        filled.and_then(|_| state.push(IORING_OP_WRITE_FIXED, index, len))
+                    {
+                        state.free_writes.push(index);
+                        if indices.is_empty() {
+                            return Some(Err(err));
+                        }
+                        break;
+                    }
+                    indices.push(index);
+                }
+                Some(state.ring.submit(0).map(|_| indices))
+            })
+            .await?;
+
+        let mut writes = Writes {
+            device: self,
+            indices,
+        };
+        let results = self
+            .poll_state(|state| {
+                if !writes
+                    .indices
+                    .iter()
+                    .all(|index| state.written.contains_key(index))
+                {
+                    return None;
+                }
+                let results = writes.indices.iter().map(|index| {
+                    state.free_writes.push(*index);
+                    state.written.remove(index).unwrap_or_default()
+                });
+                Some(Ok(results.collect::<Vec<_>>()))
+            })
+            .await?;
+        writes.indices.clear();
+        Ok(results)
+    }
+
+    /// Send a packet to tun device
+    pub async fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.write(&[buf]).await?[0] {
+            res if res < 0 => Err(std::io::Error::from_raw_os_error(-res)),
+            res => Ok((res as usize).saturating_sub(self.offset)),
+        }
+    }
+
+    /// Send packets to tun device, as many as there are free buffers, waiting for the first one
+    /// to be sent, and return the number of packets sent before the first failure
+    pub async fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        if packets.is_empty() {
+            return Ok(0);
+        }
+        let results = self.write(packets).await?;
+        match results.iter().position(|res| *res < 0) {
+            Some(0) => Err(std::io::Error::from_raw_os_error(-results[0])),
+            Some(count) => Ok(count),
+            None => Ok(results.len()),
+        }
+    }
+}
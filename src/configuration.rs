@@ -15,6 +15,8 @@
 use std::net::IpAddr;
 #[cfg(unix)]
 use std::os::unix::io::RawFd;
+#[cfg(target_os = "linux")]
+use std::path::{Path, PathBuf};
 
 use crate::address::ToAddress;
 use crate::platform::PlatformConfig;
@@ -43,10 +45,12 @@ pub struct Configuration {
     pub(crate) tun_name: Option<String>,
     pub(crate) platform_config: PlatformConfig,
     pub(crate) address: Option<IpAddr>,
+    pub(crate) addresses: Vec<(IpAddr, u8)>,
     pub(crate) destination: Option<IpAddr>,
     pub(crate) broadcast: Option<IpAddr>,
     pub(crate) netmask: Option<IpAddr>,
     pub(crate) mtu: Option<u16>,
+    pub(crate) mac_address: Option<[u8; 6]>,
     pub(crate) enabled: Option<bool>,
     pub(crate) layer: Option<Layer>,
     pub(crate) queues: Option<usize>,
@@ -62,6 +66,8 @@ pub struct Configuration {
     pub(crate) metric: Option<u16>,
     #[cfg(unix)]
     pub(crate) close_fd_on_drop: Option<bool>,
+    #[cfg(target_os = "linux")]
+    pub(crate) netns: Option<PathBuf>,
 }
 
 impl Configuration {
@@ -98,6 +104,18 @@ impl Configuration {
         self
     }
 
+    /// Add an address with the given prefix length, in addition to the one set by `address`.
+    ///
+    /// It can be called several times, with addresses of both families.
+    ///
+    /// [Note: Extra addresses are supported on Linux, macOS and FreeBSD, creating the device fails
+    /// on the other platforms. -- end note]
+    pub fn add_address<A: ToAddress>(&mut self, value: A, prefix_len: u8) -> &mut Self {
+        self.addresses
+            .push((value.to_address().unwrap(), prefix_len));
+        self
+    }
+
     /// Set the destination address.
     pub fn destination<A: ToAddress>(&mut self, value: A) -> &mut Self {
         self.destination = Some(value.to_address().unwrap());
@@ -122,6 +140,14 @@ impl Configuration {
         self
     }
 
+    /// Set the link-layer (MAC) address, only meaningful for `Layer::L2` interfaces.
+    ///
+    /// [Note: This is only supported on Linux, creating the device fails on the other platforms. -- end note]
+    pub fn mac_address(&mut self, value: [u8; 6]) -> &mut Self {
+        self.mac_address = Some(value);
+        self
+    }
+
     /// Set the interface to be enabled once created.
     pub fn up(&mut self) -> &mut Self {
         self.enabled = Some(true);
@@ -141,8 +167,9 @@ impl Configuration {
     }
 
     /// Set the number of queues.
-    /// Note: The queues must be 1, otherwise will failed.
-    #[deprecated(since = "1.0.0", note = "The queues will always be 1.")]
+    ///
+    /// [Note: Only Linux supports more than one queue, through `create_queues`.
+    /// On all the other platforms and with `create`, the queues must be 1, otherwise will failed. -- end note]
     pub fn queues(&mut self, value: usize) -> &mut Self {
         self.queues = Some(value);
         self
@@ -183,4 +210,17 @@ impl Configuration {
         self.close_fd_on_drop = Some(value);
         self
     }
+
+    /// Create and configure the interface in the network namespace of the file `path`,
+    /// such as `/var/run/netns/NAME` or `/proc/PID/ns/net`.
+    ///
+    /// The calling thread returns to its own namespace before `create` returns,
+    /// and the device keeps configuring the interface in the target one.
+    ///
+    /// [Note: Switching the namespace requires `CAP_SYS_ADMIN`. -- end note]
+    #[cfg(target_os = "linux")]
+    pub fn netns<P: AsRef<Path>>(&mut self, path: P) -> &mut Self {
+        self.netns = Some(path.as_ref().into());
+        self
+    }
 }
@@ -13,7 +13,8 @@
 //  0. You just DO WHAT THE FUCK YOU WANT TO.
 
 use crate::configuration::Configuration;
-use crate::error::Result;
+use crate::error::{Error, Result};
+use crate::route::{Cidr, Route};
 use std::io::{Read, Write};
 use std::net::IpAddr;
 
@@ -41,6 +42,14 @@ pub trait AbstractDevice: Read + Write {
             self.set_mtu(mtu)?;
         }
 
+        if let Some(mac) = config.mac_address {
+            self.set_mac_address(mac)?;
+        }
+
+        for (ip, prefix_len) in config.addresses.iter() {
+            self.add_address(*ip, *prefix_len)?;
+        }
+
         if let Some(enabled) = config.enabled {
             self.enabled(enabled)?;
         }
@@ -63,6 +72,43 @@ pub trait AbstractDevice: Read + Write {
     /// Set the address.
     fn set_address(&mut self, value: IpAddr) -> Result<()>;
 
+    /// Get all the addresses of the interface, of both families, with their prefix length.
+    fn addresses(&self) -> Result<Vec<(IpAddr, u8)>> {
+        Err(Error::NotImplemented)
+    }
+
+    /// Add an address with the given prefix length, keeping the existing ones.
+    fn add_address(&mut self, _value: IpAddr, _prefix_len: u8) -> Result<()> {
+        Err(Error::NotImplemented)
+    }
+
+    /// Remove an address from the interface.
+    fn remove_address(&mut self, _value: IpAddr) -> Result<()> {
+        Err(Error::NotImplemented)
+    }
+
+    /// Add a route to `destination` through the interface, via `gateway` if given.
+    ///
+    /// [Note: The metric is ignored on macOS and FreeBSD. -- end note]
+    fn add_route(
+        &mut self,
+        _destination: Cidr,
+        _gateway: Option<IpAddr>,
+        _metric: u32,
+    ) -> Result<()> {
+        Err(Error::NotImplemented)
+    }
+
+    /// Delete the route to `destination` through the interface, via `gateway` if given.
+    fn delete_route(&mut self, _destination: Cidr, _gateway: Option<IpAddr>) -> Result<()> {
+        Err(Error::NotImplemented)
+    }
+
+    /// List the routes bound to the interface.
+    fn routes(&self) -> Result<Vec<Route>> {
+        Err(Error::NotImplemented)
+    }
+
     /// Get the destination address.
     fn destination(&self) -> Result<IpAddr>;
 
@@ -89,6 +135,18 @@ pub trait AbstractDevice: Read + Write {
     /// [Note: This setting has no effect on the Windows platform due to the mtu of wintun is always 65535. --end note]
     fn set_mtu(&mut self, value: u16) -> Result<()>;
 
+    /// Get the link-layer (MAC) address of a `Layer::L2` device.
+    fn mac_address(&self) -> Result<[u8; 6]> {
+        Err(Error::NotImplemented)
+    }
+
+    /// Set the link-layer (MAC) address of a `Layer::L2` device.
+    ///
+    /// [Note: Some platforms require the interface to be down to change it. -- end note]
+    fn set_mac_address(&mut self, _value: [u8; 6]) -> Result<()> {
+        Err(Error::NotImplemented)
+    }
+
     /// Return whether the underlying tun device on the platform has packet information
     ///
     /// [Note: This value is not used to specify whether the packets delivered from/to tun2 have packet information. -- end note]
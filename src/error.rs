@@ -38,6 +38,9 @@ pub enum Error {
     #[error("invalid queues number")]
     InvalidQueuesNumber,
 
+    #[error("malformed packet: {0}")]
+    MalformedPacket(&'static str),
+
     #[error("out of range integral type conversion attempted")]
     TryFromIntError,
 
@@ -50,6 +53,17 @@ pub enum Error {
     #[error(transparent)]
     ParseNum(#[from] std::num::ParseIntError),
 
+    #[cfg(target_os = "linux")]
+    #[error("{0} is not supported by the kernel")]
+    UnsupportedFeature(&'static str),
+
+    #[cfg(target_os = "linux")]
+    #[error("{message} ({source})")]
+    Netlink {
+        source: std::io::Error,
+        message: String,
+    },
+
     #[cfg(target_os = "windows")]
     #[error(transparent)]
     WintunError(#[from] wintun_bindings::Error),
@@ -80,7 +94,9 @@ impl From<Error> for std::io::Error {
     fn from(value: Error) -> Self {
         match value {
             Error::Io(err) => err,
-            _ => std::io::Error::new(std::io::ErrorKind::Other, value),
+            #[cfg(target_os = "linux")]
+            Error::Netlink { ref source, .. } => std::io::Error::new(source.kind(), value),
+            _ => std::io::Error::other(value),
         }
     }
 }
@@ -21,6 +21,34 @@ pub use crate::address::ToAddress;
 mod device;
 pub use crate::device::AbstractDevice;
 
+mod route;
+pub use crate::route::{Cidr, Route};
+
+mod virtio;
+pub use crate::virtio::VirtioNetHdr;
+
+mod offload;
+pub use crate::offload::{gro_coalesce, gso_split};
+
+pub mod packet;
+
+mod pcap;
+pub use crate::pcap::{
+    Capture, Direction, PcapngWriter, Record, Replayer, LINKTYPE_ETHERNET, LINKTYPE_RAW,
+};
+
+#[cfg(unix)]
+mod tap;
+#[cfg(unix)]
+pub use crate::tap::TapAdapter;
+
+#[cfg(feature = "testing")]
+mod mock;
+#[cfg(all(target_os = "linux", feature = "testing"))]
+pub use crate::mock::Wire;
+#[cfg(feature = "testing")]
+pub use crate::mock::{MockDevice, MockHandle};
+
 mod configuration;
 pub use crate::configuration::{Configuration, Layer};
 
@@ -44,3 +72,8 @@ pub const DEFAULT_MTU: u16 = 1500;
 pub const DEFAULT_MTU: u16 = wintun_bindings::MAX_IP_PACKET_SIZE as _; // u16::MAX
 
 pub const PACKET_INFORMATION_LENGTH: usize = 4;
+
+/// The longest link-layer header in front of the packets of a `Layer::L2` device,
+/// an Ethernet header carrying an 802.1Q tag.
+#[cfg(unix)]
+pub(crate) const MAX_ETHERNET_HEADER_LENGTH: usize = 18;
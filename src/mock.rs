@@ -0,0 +1,500 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! An in-memory device, to test the code written against `AbstractDevice` without privileges.
+
+use crate::configuration::{Configuration, Layer};
+use crate::device::AbstractDevice;
+use crate::error::{Error, Result};
+#[cfg(target_os = "linux")]
+use crate::platform::posix::Fd;
+use crate::route::{Cidr, Route};
+use std::collections::VecDeque;
+use std::io::{Read, Write};
+use std::net::IpAddr;
+#[cfg(target_os = "linux")]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+#[derive(Debug, Default)]
+struct State {
+    tun_name: String,
+    enabled: bool,
+    layer: Layer,
+    address: Option<IpAddr>,
+    destination: Option<IpAddr>,
+    broadcast: Option<IpAddr>,
+    netmask: Option<IpAddr>,
+    addresses: Vec<(IpAddr, u8)>,
+    routes: Vec<Route>,
+    mtu: u16,
+    mac_address: Option<[u8; 6]>,
+    /// The packets to be read from the device.
+    inbound: VecDeque<Vec<u8>>,
+    /// The packets written to the device.
+    outbound: VecDeque<Vec<u8>>,
+}
+
+/// A device keeping its configuration and packets in memory.
+///
+/// The packets injected through its `MockHandle` are read from it, and the packets written
+/// to it are drained through its `MockHandle`.
+#[derive(Debug)]
+pub struct MockDevice {
+    state: Arc<Mutex<State>>,
+}
+
+/// The test harness side of a `MockDevice`, which stays usable once the device is moved
+/// into the code under test.
+#[derive(Clone, Debug)]
+pub struct MockHandle {
+    state: Arc<Mutex<State>>,
+}
+
+fn lock(state: &Mutex<State>) -> MutexGuard<'_, State> {
+    // A panicking test must not hide the state from the others.
+    state.lock().unwrap_or_else(|err| err.into_inner())
+}
+
+/// The error reported when the device has no address of the requested kind.
+fn no_address() -> Error {
+    std::io::Error::from(std::io::ErrorKind::AddrNotAvailable).into()
+}
+
+impl MockDevice {
+    /// Create a new `MockDevice` for the given `Configuration`.
+    pub fn new(config: &Configuration) -> Result<Self> {
+        let state = State {
+            tun_name: config.tun_name.clone().unwrap_or_else(|| "mock0".into()),
+            layer: config.layer.unwrap_or_default(),
+            mtu: crate::DEFAULT_MTU,
+            ..Default::default()
+        };
+        let mut device = MockDevice {
+            state: Arc::new(Mutex::new(state)),
+        };
+        device.configure(config)?;
+        Ok(device)
+    }
+
+    /// Get a handle to inject and drain the packets of the device.
+    pub fn handle(&self) -> MockHandle {
+        MockHandle {
+            state: self.state.clone(),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, State> {
+        lock(&self.state)
+    }
+}
+
+impl MockHandle {
+    /// Queue a packet to be read from the device.
+    pub fn inject<P: Into<Vec<u8>>>(&self, packet: P) {
+        lock(&self.state).inbound.push_back(packet.into());
+    }
+
+    /// Take all the packets written to the device so far.
+    pub fn drain(&self) -> Vec<Vec<u8>> {
+        lock(&self.state).outbound.drain(..).collect()
+    }
+
+    /// The number of injected packets not read yet.
+    pub fn pending(&self) -> usize {
+        lock(&self.state).inbound.len()
+    }
+
+    /// Whether the device is enabled.
+    pub fn is_enabled(&self) -> bool {
+        lock(&self.state).enabled
+    }
+
+    /// Get the OSI layer of operation of the device.
+    pub fn layer(&self) -> Layer {
+        lock(&self.state).layer
+    }
+}
+
+/// Reading pops the oldest injected packet, truncated to the buffer like a TUN device does,
+/// or fails with `WouldBlock` when there is none.
+impl Read for MockDevice {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let packet = self.state().inbound.pop_front();
+        let packet = packet.ok_or(std::io::ErrorKind::WouldBlock)?;
+        let len = packet.len().min(buf.len());
+        buf[..len].copy_from_slice(&packet[..len]);
+        Ok(len)
+    }
+}
+
+/// Every write is one packet.
+impl Write for MockDevice {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.state().outbound.push_back(buf.to_vec());
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl AbstractDevice for MockDevice {
+    fn tun_name(&self) -> Result<String> {
+        Ok(self.state().tun_name.clone())
+    }
+
+    fn set_tun_name(&mut self, value: &str) -> Result<()> {
+        self.state().tun_name = value.into();
+        Ok(())
+    }
+
+    fn enabled(&mut self, value: bool) -> Result<()> {
+        self.state().enabled = value;
+        Ok(())
+    }
+
+    fn address(&self) -> Result<IpAddr> {
+        self.state().address.ok_or_else(no_address)
+    }
+
+    fn set_address(&mut self, value: IpAddr) -> Result<()> {
+        self.state().address = Some(value);
+        Ok(())
+    }
+
+    fn addresses(&self) -> Result<Vec<(IpAddr, u8)>> {
+        let state = self.state();
+        let primary = state.address.map(|address| {
+            let prefix_len = match state.netmask {
+                Some(IpAddr::V4(mask)) => u32::from(mask).leading_ones(),
+                Some(IpAddr::V6(mask)) => u128::from(mask).leading_ones(),
+                None if address.is_ipv4() => 32,
+                None => 128,
+            };
+            (address, prefix_len as u8)
+        });
+        Ok(primary.into_iter().chain(state.addresses.clone()).collect())
+    }
+
+    fn add_address(&mut self, value: IpAddr, prefix_len: u8) -> Result<()> {
+        let mut state = self.state();
+        state.addresses.retain(|(address, _)| *address != value);
+        state.addresses.push((value, prefix_len));
+        Ok(())
+    }
+
+    fn remove_address(&mut self, value: IpAddr) -> Result<()> {
+        let mut state = self.state();
+        if state.address == Some(value) {
+            state.address = None;
+            return Ok(());
+        }
+        let len = state.addresses.len();
+        state.addresses.retain(|(address, _)| *address != value);
+        if state.addresses.len() == len {
+            return Err(no_address());
+        }
+        Ok(())
+    }
+
+    fn add_route(&mut self, destination: Cidr, gateway: Option<IpAddr>, metric: u32) -> Result<()> {
+        self.state().routes.push(Route {
+            destination,
+            gateway,
+            metric,
+        });
+        Ok(())
+    }
+
+    fn delete_route(&mut self, destination: Cidr, gateway: Option<IpAddr>) -> Result<()> {
+        let mut state = self.state();
+        let position = state.routes.iter().position(|route| {
+            route.destination == destination && (gateway.is_none() || route.gateway == gateway)
+        });
+        let position = position.ok_or(std::io::Error::from(std::io::ErrorKind::NotFound))?;
+        state.routes.remove(position);
+        Ok(())
+    }
+
+    fn routes(&self) -> Result<Vec<Route>> {
+        Ok(self.state().routes.clone())
+    }
+
+    fn destination(&self) -> Result<IpAddr> {
+        self.state().destination.ok_or_else(no_address)
+    }
+
+    fn set_destination(&mut self, value: IpAddr) -> Result<()> {
+        self.state().destination = Some(value);
+        Ok(())
+    }
+
+    fn broadcast(&self) -> Result<IpAddr> {
+        self.state().broadcast.ok_or_else(no_address)
+    }
+
+    fn set_broadcast(&mut self, value: IpAddr) -> Result<()> {
+        self.state().broadcast = Some(value);
+        Ok(())
+    }
+
+    fn netmask(&self) -> Result<IpAddr> {
+        self.state().netmask.ok_or_else(no_address)
+    }
+
+    fn set_netmask(&mut self, value: IpAddr) -> Result<()> {
+        self.state().netmask = Some(value);
+        Ok(())
+    }
+
+    fn mtu(&self) -> Result<u16> {
+        Ok(self.state().mtu)
+    }
+
+    fn set_mtu(&mut self, value: u16) -> Result<()> {
+        self.state().mtu = value;
+        Ok(())
+    }
+
+    fn mac_address(&self) -> Result<[u8; 6]> {
+        let state = self.state();
+        if state.layer != Layer::L2 {
+            return Err(Error::UnsupportedLayer);
+        }
+        Ok(state.mac_address.unwrap_or_default())
+    }
+
+    fn set_mac_address(&mut self, value: [u8; 6]) -> Result<()> {
+        let mut state = self.state();
+        if state.layer != Layer::L2 {
+            return Err(Error::UnsupportedLayer);
+        }
+        state.mac_address = Some(value);
+        Ok(())
+    }
+
+    fn packet_information(&self) -> bool {
+        false
+    }
+}
+
+/// The other end of a `Device` created by `Device::socketpair`, standing for the kernel side
+/// of the interface: the packets sent on the wire are read from the device, and the packets
+/// written to the device are received from the wire.
+#[cfg(target_os = "linux")]
+pub struct Wire {
+    fd: Fd,
+}
+
+#[cfg(target_os = "linux")]
+impl Wire {
+    pub(crate) fn new(fd: Fd) -> Self {
+        Wire { fd }
+    }
+
+    /// Send a packet to be read from the device.
+    pub fn send(&self, packet: &[u8]) -> std::io::Result<usize> {
+        self.fd.write(packet)
+    }
+
+    /// Receive a packet written to the device.
+    pub fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.fd.read(buf)
+    }
+
+    /// Make `send` and `recv` fail with `WouldBlock` rather than wait.
+    pub fn set_nonblock(&self) -> std::io::Result<()> {
+        self.fd.set_nonblock()
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl AsRawFd for Wire {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsRef<dyn AbstractDevice + 'static> for MockDevice {
+    fn as_ref(&self) -> &(dyn AbstractDevice + 'static) {
+        self
+    }
+}
+
+impl AsMut<dyn AbstractDevice + 'static> for MockDevice {
+    fn as_mut(&mut self) -> &mut (dyn AbstractDevice + 'static) {
+        self
+    }
+}
+
+#[test]
+fn test_mock_device() {
+    use std::net::Ipv4Addr;
+
+    let mut config = Configuration::default();
+    config
+        .tun_name("mock7")
+        .address((10, 7, 0, 1))
+        .netmask((255, 255, 255, 0))
+        .add_address("fd00::1", 64)
+        .mtu(1400)
+        .up();
+    let device = MockDevice::new(&config).unwrap();
+    let handle = device.handle();
+    assert!(handle.is_enabled());
+
+    // The code under test only knows the device through the trait.
+    let mut device: Box<dyn AbstractDevice> = Box::new(device);
+    assert_eq!("mock7", device.tun_name().unwrap());
+    assert_eq!(1400, device.mtu().unwrap());
+    let addresses = device.addresses().unwrap();
+    assert_eq!(Ipv4Addr::new(10, 7, 0, 1), addresses[0].0);
+    assert_eq!(24, addresses[0].1);
+    assert_eq!(
+        (IpAddr::from([0xfd00, 0, 0, 0, 0, 0, 0, 1]), 64),
+        addresses[1]
+    );
+    assert!(matches!(device.mac_address(), Err(Error::UnsupportedLayer)));
+    device.enabled(false).unwrap();
+    assert!(!handle.is_enabled());
+
+    handle.inject(vec![0x45; 20]);
+    handle.inject(vec![0x60; 40]);
+    let mut buf = [0; 32];
+    assert_eq!(20, device.read(&mut buf).unwrap());
+    assert_eq!(32, device.read(&mut buf).unwrap());
+    let err = device.read(&mut buf).unwrap_err();
+    assert_eq!(std::io::ErrorKind::WouldBlock, err.kind());
+    assert_eq!(0, handle.pending());
+
+    device.write_all(&[0x45; 28]).unwrap();
+    assert_eq!(vec![vec![0x45; 28]], handle.drain());
+    assert!(handle.drain().is_empty());
+
+    let destination = "10.8.0.0/16".parse().unwrap();
+    device.add_route(destination, None, 10).unwrap();
+    assert_eq!(destination, device.routes().unwrap()[0].destination);
+    device.delete_route(destination, None).unwrap();
+    assert!(device.delete_route(destination, None).is_err());
+}
+
+#[cfg(all(target_os = "linux", feature = "async"))]
+#[test]
+fn test_socketpair_device() {
+    use crate::{AsyncDevice, Device, VirtioNetHdr};
+    use futures::{SinkExt, StreamExt};
+
+    let ipv4 = |len: usize| {
+        let mut packet = vec![0; len];
+        packet[0] = 0x45;
+        packet
+    };
+
+    let mut config = Configuration::default();
+    config.platform_config(|config| {
+        config.vnet_hdr(true);
+    });
+    let (device, wire) = Device::socketpair(&config).unwrap();
+    let mut buf = [0; 1500];
+    wire.send(&[&[0; VirtioNetHdr::LEN][..], &ipv4(40)].concat())
+        .unwrap();
+    assert_eq!(40, device.recv(&mut buf).unwrap());
+    assert_eq!(28, device.send(&ipv4(28)).unwrap());
+    assert_eq!(VirtioNetHdr::LEN + 28, wire.recv(&mut buf).unwrap());
+
+    let (device, wire) = Device::socketpair(&Configuration::default()).unwrap();
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let mut framed = AsyncDevice::new(device).unwrap().into_framed();
+        wire.send(&ipv4(60)).unwrap();
+        wire.send(&ipv4(20)).unwrap();
+        assert_eq!(ipv4(60), framed.next().await.unwrap().unwrap());
+        assert_eq!(ipv4(20), framed.next().await.unwrap().unwrap());
+
+        framed.send(ipv4(1500)).await.unwrap();
+        assert_eq!(1500, wire.recv(&mut buf).unwrap());
+
+        let (rx, tx) = framed.get_ref().io_stats();
+        assert_eq!((2, 80), (rx.packets, rx.bytes));
+        assert_eq!((1, 1500), (tx.packets, tx.bytes));
+    });
+}
+
+#[cfg(all(target_os = "linux", feature = "async"))]
+#[test]
+fn test_socketpair_framed_packets() {
+    use crate::{AsyncDevice, Device, TunPacket, VirtioNetHdr};
+    use futures::{SinkExt, StreamExt};
+
+    let mut config = Configuration::default();
+    config.platform_config(|config| {
+        #[allow(deprecated)]
+        config.packet_information(true);
+        config.vnet_hdr(true);
+    });
+    let (device, wire) = Device::socketpair(&config).unwrap();
+    let hdr = VirtioNetHdr {
+        flags: VirtioNetHdr::F_NEEDS_CSUM,
+        csum_start: 40,
+        csum_offset: 6,
+        ..Default::default()
+    };
+    let mut prefix = [0; 4 + VirtioNetHdr::LEN];
+    prefix[2..4].copy_from_slice(&0x86dd_u16.to_be_bytes());
+    hdr.encode(&mut prefix[4..]);
+    let mut ipv6 = vec![0; 48];
+    ipv6[0] = 0x60;
+
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .unwrap();
+    runtime.block_on(async {
+        let mut framed = AsyncDevice::new(device).unwrap().into_framed_packets();
+        wire.send(&[&prefix[..], &ipv6].concat()).unwrap();
+        let packet = framed.next().await.unwrap().unwrap();
+        assert!(packet.is_ipv6());
+        assert_eq!(Some(hdr), packet.vnet_hdr);
+        assert_eq!(ipv6, packet.payload);
+        assert!(packet.received_at.is_some());
+
+        // The headers are encoded from the packet, the protocol derived from its IP version.
+        let mut ipv4 = vec![0; 20];
+        ipv4[0] = 0x45;
+        let mut packet = TunPacket::new(&ipv4[..]);
+        packet.vnet_hdr = Some(hdr);
+        framed.send(packet).await.unwrap();
+        let mut buf = [0; 1500];
+        let len = wire.recv(&mut buf).unwrap();
+        assert_eq!([0, 0, 0x08, 0x00], buf[..4]);
+        assert_eq!(
+            Some(hdr),
+            VirtioNetHdr::decode(&buf[4..4 + VirtioNetHdr::LEN])
+        );
+        assert_eq!(ipv4, buf[4 + VirtioNetHdr::LEN..len]);
+
+        // A truncated frame is an error.
+        wire.send(&prefix[..6]).unwrap();
+        assert!(framed.next().await.unwrap().is_err());
+
+        let (rx, tx) = framed.get_ref().io_stats();
+        assert_eq!((2, 48), (rx.packets, rx.bytes));
+        assert_eq!((1, 20), (tx.packets, tx.bytes));
+    });
+}
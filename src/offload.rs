@@ -0,0 +1,528 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Segmentation and coalescing of the TCP and UDP super-packets exchanged with an offloading device.
+
+use crate::virtio::VirtioNetHdr;
+use std::borrow::Cow;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+
+/// The kernel drops UDP super-packets made of more segments.
+const UDP_MAX_SEGMENTS: usize = 64;
+
+const TCP_FIN: u8 = 0x01;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+const TCP_CWR: u8 = 0x80;
+
+fn invalid(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message)
+}
+
+/// Add the big-endian 16 bits words of `data` to the one's complement sum `sum`.
+pub(crate) fn checksum_add(mut sum: u64, data: &[u8]) -> u64 {
+    let mut chunks = data.chunks_exact(2);
+    for chunk in &mut chunks {
+        sum += u16::from_be_bytes([chunk[0], chunk[1]]) as u64;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u64) << 8;
+    }
+    sum
+}
+
+/// Fold a one's complement sum into 16 bits.
+pub(crate) fn checksum_fold(mut sum: u64) -> u16 {
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    sum as u16
+}
+
+/// Sum of the pseudo header of the IP packet `ip`, for a transport payload of `len` bytes.
+pub(crate) fn pseudo_header_sum(ip: &[u8], protocol: u8, len: usize) -> u64 {
+    let addresses = if ip[0] >> 4 == 6 {
+        &ip[8..40]
+    } else {
+        &ip[12..20]
+    };
+    checksum_add(0, addresses) + protocol as u64 + len as u64
+}
+
+fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+/// Set the IPv4 total length or IPv6 payload length, updating the IPv4 header checksum.
+fn set_ip_len(packet: &mut [u8], ip_len: usize) {
+    let len = packet.len();
+    if packet[0] >> 4 == 6 {
+        put_u16(packet, 4, (len - 40) as u16);
+    } else {
+        put_u16(packet, 2, len as u16);
+        put_u16(packet, 10, 0);
+        let checksum = !checksum_fold(checksum_add(0, &packet[..ip_len]));
+        put_u16(packet, 10, checksum);
+    }
+}
+
+/// Complete the partial checksum the kernel leaves in packets flagged with
+/// [`VirtioNetHdr::F_NEEDS_CSUM`], the field already holds the pseudo header sum.
+fn complete_checksum(packet: &mut [u8], start: usize, offset: usize) -> std::io::Result<()> {
+    if start + offset + 2 > packet.len() {
+        return Err(invalid("checksum out of the packet"));
+    }
+    let mut checksum = !checksum_fold(checksum_add(0, &packet[start..]));
+    // A zero UDP checksum means no checksum at all.
+    if offset == 6 && checksum == 0 {
+        checksum = 0xffff;
+    }
+    put_u16(packet, start + offset, checksum);
+    Ok(())
+}
+
+/// Split a packet received along with `hdr` into packets ready to be sent on the wire,
+/// which are appended to `segments`.
+///
+/// TCP and UDP super-packets are cut into segments of `hdr.gso_size` bytes of payload with
+/// their own headers and checksums, other packets are passed through with their checksum
+/// completed if the kernel left it to the application.
+pub fn gso_split(
+    hdr: &VirtioNetHdr,
+    packet: &[u8],
+    segments: &mut Vec<Vec<u8>>,
+) -> std::io::Result<()> {
+    let protocol = match hdr.gso_type & !VirtioNetHdr::GSO_ECN {
+        VirtioNetHdr::GSO_NONE => {
+            let mut packet = packet.to_vec();
+            if hdr.flags & VirtioNetHdr::F_NEEDS_CSUM != 0 {
+                complete_checksum(
+                    &mut packet,
+                    hdr.csum_start as usize,
+                    hdr.csum_offset as usize,
+                )?;
+            }
+            segments.push(packet);
+            return Ok(());
+        }
+        VirtioNetHdr::GSO_TCPV4 | VirtioNetHdr::GSO_TCPV6 => IPPROTO_TCP,
+        VirtioNetHdr::GSO_UDP_L4 => IPPROTO_UDP,
+        _ => return Err(invalid("unsupported GSO type")),
+    };
+
+    let version = packet.first().map(|byte| byte >> 4);
+    let ipv6 = match hdr.gso_type & !VirtioNetHdr::GSO_ECN {
+        VirtioNetHdr::GSO_TCPV4 => false,
+        VirtioNetHdr::GSO_TCPV6 => true,
+        _ => version == Some(6),
+    };
+    if version != Some(if ipv6 { 6 } else { 4 }) {
+        return Err(invalid("IP version does not match the GSO type"));
+    }
+    let ip_len = match hdr.csum_start as usize {
+        0 if ipv6 => 40,
+        0 => (packet[0] & 0x0f) as usize * 4,
+        start => start,
+    };
+    if ip_len < if ipv6 { 40 } else { 20 } || ip_len > packet.len() {
+        return Err(invalid("invalid IP header length"));
+    }
+    let header_len = match protocol {
+        IPPROTO_TCP if packet.len() >= ip_len + 20 => {
+            ip_len + (packet[ip_len + 12] >> 4) as usize * 4
+        }
+        IPPROTO_UDP => ip_len + 8,
+        _ => return Err(invalid("truncated TCP packet")),
+    };
+    let gso_size = hdr.gso_size as usize;
+    let min_len = if protocol == IPPROTO_TCP { 20 } else { 8 };
+    if header_len < ip_len + min_len || header_len > packet.len() || gso_size == 0 {
+        return Err(invalid("invalid super-packet"));
+    }
+
+    let payload = &packet[header_len..];
+    let count = payload.len().div_ceil(gso_size).max(1);
+    let id = get_u16(packet, 4);
+    let seq = get_u32(packet, ip_len + 4);
+    segments.reserve(count);
+
+    for i in 0..count {
+        let start = (i * gso_size).min(payload.len());
+        let chunk = &payload[start..(start + gso_size).min(payload.len())];
+        let mut segment = Vec::with_capacity(header_len + chunk.len());
+        segment.extend_from_slice(&packet[..header_len]);
+        segment.extend_from_slice(chunk);
+
+        if !ipv6 {
+            put_u16(&mut segment, 4, id.wrapping_add(i as u16));
+        }
+        set_ip_len(&mut segment, ip_len);
+
+        let l4_len = segment.len() - ip_len;
+        let l4 = &mut segment[ip_len..];
+        let checksum_offset = if protocol == IPPROTO_TCP {
+            let seq = seq.wrapping_add(start as u32);
+            l4[4..8].copy_from_slice(&seq.to_be_bytes());
+            if i + 1 != count {
+                l4[13] &= !(TCP_FIN | TCP_PSH);
+            }
+            if i != 0 {
+                l4[13] &= !TCP_CWR;
+            }
+            16
+        } else {
+            put_u16(l4, 4, l4_len as u16);
+            6
+        };
+        // Start from the pseudo header sum, like the kernel does for partial checksums.
+        let sum = pseudo_header_sum(&segment, protocol, l4_len);
+        put_u16(&mut segment, ip_len + checksum_offset, checksum_fold(sum));
+        complete_checksum(&mut segment, ip_len, checksum_offset)?;
+
+        segments.push(segment);
+    }
+    Ok(())
+}
+
+/// A run of segments of the same flow being merged into one super-packet.
+struct Run {
+    packet: Vec<u8>,
+    protocol: u8,
+    ip_len: usize,
+    header_len: usize,
+    gso_size: usize,
+    next_seq: u32,
+    count: usize,
+    /// No segment can follow a short or pushed one.
+    closed: bool,
+}
+
+/// Get the protocol, IP and transport header lengths of a packet which can be coalesced,
+/// UDP datagrams are only considered if `udp` is set.
+fn coalescable(packet: &[u8], udp: bool) -> Option<(u8, usize, usize)> {
+    let (protocol, ip_len) = match packet.first()? >> 4 {
+        // No options nor fragments.
+        4 if packet[0] & 0x0f == 5 && packet.len() >= 20 => {
+            if get_u16(packet, 6) & 0x3fff != 0 || get_u16(packet, 2) as usize != packet.len() {
+                return None;
+            }
+            (packet[9], 20)
+        }
+        // No extension headers.
+        6 if packet.len() >= 40 => {
+            if get_u16(packet, 4) as usize + 40 != packet.len() {
+                return None;
+            }
+            (packet[6], 40)
+        }
+        _ => return None,
+    };
+    let header_len = match protocol {
+        IPPROTO_TCP if packet.len() >= ip_len + 20 => {
+            let tcp_len = (packet[ip_len + 12] >> 4) as usize * 4;
+            if tcp_len < 20 || packet[ip_len + 13] & !TCP_PSH != TCP_ACK {
+                return None;
+            }
+            ip_len + tcp_len
+        }
+        IPPROTO_UDP if udp && packet.len() >= ip_len + 8 => {
+            if get_u16(packet, ip_len + 4) as usize != packet.len() - ip_len {
+                return None;
+            }
+            ip_len + 8
+        }
+        _ => return None,
+    };
+    if header_len >= packet.len() {
+        return None;
+    }
+    Some((protocol, ip_len, header_len))
+}
+
+impl Run {
+    fn new(packet: &[u8], protocol: u8, ip_len: usize, header_len: usize) -> Self {
+        let tcp = protocol == IPPROTO_TCP;
+        Run {
+            packet: packet.to_vec(),
+            protocol,
+            ip_len,
+            header_len,
+            gso_size: packet.len() - header_len,
+            next_seq: if tcp {
+                get_u32(packet, ip_len + 4).wrapping_add((packet.len() - header_len) as u32)
+            } else {
+                0
+            },
+            count: 1,
+            closed: tcp && packet[ip_len + 13] & TCP_PSH != 0,
+        }
+    }
+
+    /// Whether `packet` belongs to the same flow.
+    fn same_flow(&self, packet: &[u8], protocol: u8, ip_len: usize) -> bool {
+        let ip = self.ip_len;
+        let addresses = if ip == 40 { 8..40 } else { 12..20 };
+        protocol == self.protocol
+            && ip_len == ip
+            && self.packet[addresses.clone()] == packet[addresses]
+            && self.packet[ip..ip + 4] == packet[ip..ip + 4]
+    }
+
+    /// Whether `packet` is the next segment of the flow with identical headers.
+    fn accepts(&self, packet: &[u8], header_len: usize) -> bool {
+        let (run, ip) = (&self.packet[..], self.ip_len);
+        let payload_len = packet.len() - header_len;
+        let same_ip = if ip == 40 {
+            run[..4] == packet[..4] && run[6..8] == packet[6..8]
+        } else {
+            run[..2] == packet[..2] && run[6..10] == packet[6..10]
+        };
+        let same_l4 = match self.protocol {
+            IPPROTO_TCP => {
+                run[ip + 8..ip + 13] == packet[ip + 8..ip + 13]
+                    && run[ip + 14..ip + 16] == packet[ip + 14..ip + 16]
+                    && run[ip + 18..self.header_len] == packet[ip + 18..header_len]
+                    && get_u32(packet, ip + 4) == self.next_seq
+            }
+            _ => self.count < UDP_MAX_SEGMENTS,
+        };
+        let max_len = u16::MAX as usize + if ip == 40 { 40 } else { 0 };
+        !self.closed
+            && header_len == self.header_len
+            && same_ip
+            && same_l4
+            && payload_len <= self.gso_size
+            && self.packet.len() + payload_len <= max_len
+    }
+
+    fn push(&mut self, packet: &[u8]) {
+        let payload = &packet[self.header_len..];
+        self.packet.extend_from_slice(payload);
+        self.count += 1;
+        self.closed = payload.len() < self.gso_size;
+        if self.protocol == IPPROTO_TCP {
+            self.next_seq = self.next_seq.wrapping_add(payload.len() as u32);
+            let flags = packet[self.ip_len + 13];
+            self.packet[self.ip_len + 13] |= flags & TCP_PSH;
+            self.closed |= flags & TCP_PSH != 0;
+        }
+    }
+
+    fn finish(mut self) -> (VirtioNetHdr, Vec<u8>) {
+        let ip_len = self.ip_len;
+        let l4_len = self.packet.len() - ip_len;
+        set_ip_len(&mut self.packet, ip_len);
+        let (gso_type, csum_offset) = match self.protocol {
+            IPPROTO_TCP if ip_len == 40 => (VirtioNetHdr::GSO_TCPV6, 16),
+            IPPROTO_TCP => (VirtioNetHdr::GSO_TCPV4, 16),
+            _ => {
+                put_u16(&mut self.packet, ip_len + 4, l4_len as u16);
+                (VirtioNetHdr::GSO_UDP_L4, 6)
+            }
+        };
+        let sum = pseudo_header_sum(&self.packet, self.protocol, l4_len);
+        put_u16(&mut self.packet, ip_len + csum_offset, checksum_fold(sum));
+        let hdr = VirtioNetHdr {
+            flags: VirtioNetHdr::F_NEEDS_CSUM,
+            gso_type,
+            hdr_len: self.header_len as u16,
+            gso_size: self.gso_size as u16,
+            csum_start: ip_len as u16,
+            csum_offset: csum_offset as u16,
+            num_buffers: 0,
+        };
+        (hdr, self.packet)
+    }
+}
+
+/// Coalesce consecutive TCP segments of the same flow into super-packets, to be
+/// written along with their header to a device with `IFF_VNET_HDR`.
+///
+/// UDP datagrams of the same flow and size are coalesced as well when `udp` is set,
+/// which requires the device to have the USO offloads enabled.
+///
+/// Packets which cannot be coalesced are returned as is with an empty header, the order
+/// of the packets of a flow is preserved.
+pub fn gro_coalesce<'a>(packets: &[&'a [u8]], udp: bool) -> Vec<(VirtioNetHdr, Cow<'a, [u8]>)> {
+    enum Entry<'a> {
+        Packet(&'a [u8]),
+        Run(Run),
+    }
+
+    let mut entries: Vec<Entry<'a>> = Vec::with_capacity(packets.len());
+    for &packet in packets {
+        let Some((protocol, ip_len, header_len)) = coalescable(packet, udp) else {
+            entries.push(Entry::Packet(packet));
+            continue;
+        };
+        // Only the latest run of the flow can be extended, and none before a packet
+        // which cannot be coalesced, so no packet is reordered within its flow.
+        let run = entries
+            .iter_mut()
+            .rev()
+            .map_while(|entry| match entry {
+                Entry::Run(run) => Some(run),
+                Entry::Packet(_) => None,
+            })
+            .find(|run| run.same_flow(packet, protocol, ip_len))
+            .filter(|run| run.accepts(packet, header_len));
+        match run {
+            Some(run) => run.push(packet),
+            None => entries.push(Entry::Run(Run::new(packet, protocol, ip_len, header_len))),
+        }
+    }
+
+    entries
+        .into_iter()
+        .map(|entry| match entry {
+            Entry::Packet(packet) => (VirtioNetHdr::default(), Cow::Borrowed(packet)),
+            Entry::Run(run) if run.count == 1 => (VirtioNetHdr::default(), Cow::Owned(run.packet)),
+            Entry::Run(run) => {
+                let (hdr, packet) = run.finish();
+                (hdr, Cow::Owned(packet))
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+fn test_packet(protocol: u8, payload_len: usize) -> Vec<u8> {
+    let l4_len = if protocol == IPPROTO_TCP { 20 } else { 8 };
+    let mut packet = vec![0_u8; 20 + l4_len + payload_len];
+    packet[..20].copy_from_slice(&[
+        0x45, 0, 0, 0, 0, 1, 0x40, 0, 64, protocol, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+    ]);
+    packet[20..24].copy_from_slice(&[0x30, 0x39, 0, 80]);
+    if protocol == IPPROTO_TCP {
+        packet[24..28].copy_from_slice(&0x1000_u32.to_be_bytes());
+        packet[32] = 0x50;
+        packet[33] = TCP_ACK | TCP_PSH;
+        put_u16(&mut packet, 34, 0xffff);
+    } else {
+        put_u16(&mut packet, 24, (8 + payload_len) as u16);
+    }
+    for (i, byte) in packet[20 + l4_len..].iter_mut().enumerate() {
+        *byte = i as u8;
+    }
+    set_ip_len(&mut packet, 20);
+    packet
+}
+
+#[cfg(test)]
+fn assert_valid(segment: &[u8], protocol: u8) {
+    assert_eq!(0, !checksum_fold(checksum_add(0, &segment[..20])));
+    let sum = pseudo_header_sum(segment, protocol, segment.len() - 20);
+    assert_eq!(0, !checksum_fold(checksum_add(sum, &segment[20..])));
+}
+
+#[test]
+fn test_gso_split_and_coalesce() {
+    let packet = test_packet(IPPROTO_TCP, 2500);
+    let hdr = VirtioNetHdr {
+        flags: VirtioNetHdr::F_NEEDS_CSUM,
+        gso_type: VirtioNetHdr::GSO_TCPV4,
+        hdr_len: 40,
+        gso_size: 1000,
+        csum_start: 20,
+        csum_offset: 16,
+        num_buffers: 0,
+    };
+    let mut segments = vec![];
+    gso_split(&hdr, &packet, &mut segments).unwrap();
+    assert_eq!(
+        vec![1040, 1040, 540],
+        segments.iter().map(Vec::len).collect::<Vec<_>>()
+    );
+    for (i, segment) in segments.iter().enumerate() {
+        assert_valid(segment, IPPROTO_TCP);
+        assert_eq!(0x1000 + i as u32 * 1000, get_u32(segment, 24));
+        assert_eq!(i == 2, segment[33] & TCP_PSH != 0);
+    }
+
+    let segments: Vec<&[u8]> = segments.iter().map(Vec::as_slice).collect();
+    let coalesced = gro_coalesce(&segments, false);
+    assert_eq!(1, coalesced.len());
+    let (coalesced_hdr, coalesced_packet) = &coalesced[0];
+    assert_eq!(hdr, *coalesced_hdr);
+    assert_eq!(packet.len(), coalesced_packet.len());
+    assert_eq!(packet[40..], coalesced_packet[40..]);
+    assert_eq!(TCP_ACK | TCP_PSH, coalesced_packet[33]);
+
+    // A segment out of sequence starts a new run.
+    let coalesced = gro_coalesce(&[segments[0], segments[2]], false);
+    assert_eq!(2, coalesced.len());
+    assert_eq!(VirtioNetHdr::default(), coalesced[0].0);
+
+    // Checksum starts inside the IP header are rejected.
+    let mut ipv4 = vec![0; 10];
+    ipv4[0] = 0x45;
+    let hdr = VirtioNetHdr {
+        gso_type: VirtioNetHdr::GSO_UDP_L4,
+        gso_size: 1000,
+        csum_start: 2,
+        ..Default::default()
+    };
+    assert!(gso_split(&hdr, &ipv4, &mut vec![]).is_err());
+    let mut ipv6 = vec![0; 30];
+    ipv6[0] = 0x60;
+    let hdr = VirtioNetHdr {
+        gso_type: VirtioNetHdr::GSO_TCPV6,
+        gso_size: 1000,
+        csum_start: 1,
+        ..Default::default()
+    };
+    assert!(gso_split(&hdr, &ipv6, &mut vec![]).is_err());
+}
+
+#[test]
+fn test_udp_split_and_coalesce() {
+    let datagram = test_packet(IPPROTO_UDP, 1200);
+    let last = test_packet(IPPROTO_UDP, 500);
+    let datagrams = [&datagram[..], &datagram[..], &last[..]];
+
+    // Without USO every datagram is written on its own.
+    let coalesced = gro_coalesce(&datagrams, false);
+    assert_eq!(3, coalesced.len());
+
+    let coalesced = gro_coalesce(&datagrams, true);
+    assert_eq!(1, coalesced.len());
+    let (hdr, packet) = &coalesced[0];
+    assert_eq!(VirtioNetHdr::GSO_UDP_L4, hdr.gso_type);
+    assert_eq!(1200, hdr.gso_size);
+    assert_eq!(20 + 8 + 2900, packet.len());
+
+    let mut segments = vec![];
+    gso_split(hdr, packet, &mut segments).unwrap();
+    assert_eq!(3, segments.len());
+    for (segment, datagram) in segments.iter().zip(datagrams) {
+        assert_valid(segment, IPPROTO_UDP);
+        assert_eq!(datagram[20..26], segment[20..26]);
+        assert_eq!(datagram[28..], segment[28..]);
+    }
+}
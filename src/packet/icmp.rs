@@ -0,0 +1,143 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use super::{get_u16, put_u16, transport_checksum, IPPROTO_ICMPV6};
+use crate::error::{Error, Result};
+use crate::offload::{checksum_add, checksum_fold};
+use std::net::IpAddr;
+
+const HEADER_LEN: usize = 8;
+
+pub const ICMP_ECHO_REPLY: u8 = 0;
+pub const ICMP_ECHO_REQUEST: u8 = 8;
+pub const ICMPV6_ECHO_REQUEST: u8 = 128;
+pub const ICMPV6_ECHO_REPLY: u8 = 129;
+
+/// The accessors shared by ICMP and ICMPv6 messages, of the same layout.
+macro_rules! icmp_view {
+    ($name:ident, $what:literal, $truncated:literal) => {
+        impl<T: AsRef<[u8]>> $name<T> {
+            #[doc = concat!("View `buf`, the payload of an IP packet, as an ", $what, " message.")]
+            pub fn new(buf: T) -> Result<Self> {
+                if buf.as_ref().len() < HEADER_LEN {
+                    return Err(Error::MalformedPacket($truncated));
+                }
+                Ok($name { buf })
+            }
+
+            /// Consumes the view, returning the underlying buffer.
+            pub fn into_inner(self) -> T {
+                self.buf
+            }
+
+            pub fn as_bytes(&self) -> &[u8] {
+                self.buf.as_ref()
+            }
+
+            pub fn msg_type(&self) -> u8 {
+                self.buf.as_ref()[0]
+            }
+
+            pub fn code(&self) -> u8 {
+                self.buf.as_ref()[1]
+            }
+
+            pub fn checksum(&self) -> u16 {
+                get_u16(self.buf.as_ref(), 2)
+            }
+
+            /// The identifier of an echo request or reply.
+            pub fn identifier(&self) -> u16 {
+                get_u16(self.buf.as_ref(), 4)
+            }
+
+            /// The sequence number of an echo request or reply.
+            pub fn sequence(&self) -> u16 {
+                get_u16(self.buf.as_ref(), 6)
+            }
+
+            pub fn payload(&self) -> &[u8] {
+                &self.buf.as_ref()[HEADER_LEN..]
+            }
+        }
+
+        impl<T: AsRef<[u8]> + AsMut<[u8]>> $name<T> {
+            pub fn set_msg_type(&mut self, msg_type: u8) {
+                self.buf.as_mut()[0] = msg_type;
+            }
+
+            pub fn set_code(&mut self, code: u8) {
+                self.buf.as_mut()[1] = code;
+            }
+
+            pub fn set_identifier(&mut self, identifier: u16) {
+                put_u16(self.buf.as_mut(), 4, identifier);
+            }
+
+            pub fn set_sequence(&mut self, sequence: u16) {
+                put_u16(self.buf.as_mut(), 6, sequence);
+            }
+
+            pub fn payload_mut(&mut self) -> &mut [u8] {
+                &mut self.buf.as_mut()[HEADER_LEN..]
+            }
+        }
+    };
+}
+
+/// A view of an ICMP message.
+#[derive(Clone, Copy, Debug)]
+pub struct IcmpPacket<T> {
+    buf: T,
+}
+
+icmp_view!(IcmpPacket, "ICMP", "truncated ICMP header");
+
+impl<T: AsRef<[u8]>> IcmpPacket<T> {
+    pub fn verify_checksum(&self) -> bool {
+        !checksum_fold(checksum_add(0, self.buf.as_ref())) == 0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> IcmpPacket<T> {
+    pub fn fill_checksum(&mut self) {
+        put_u16(self.buf.as_mut(), 2, 0);
+        let checksum = !checksum_fold(checksum_add(0, self.buf.as_ref()));
+        put_u16(self.buf.as_mut(), 2, checksum);
+    }
+}
+
+/// A view of an ICMPv6 message.
+#[derive(Clone, Copy, Debug)]
+pub struct Icmpv6Packet<T> {
+    buf: T,
+}
+
+icmp_view!(Icmpv6Packet, "ICMPv6", "truncated ICMPv6 header");
+
+impl<T: AsRef<[u8]>> Icmpv6Packet<T> {
+    /// Verify the checksum, computed over the pseudo header of the IPv6 packet carrying the message.
+    pub fn verify_checksum(&self, source: IpAddr, destination: IpAddr) -> bool {
+        transport_checksum(source, destination, IPPROTO_ICMPV6, self.buf.as_ref()) == 0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Icmpv6Packet<T> {
+    /// Update the checksum, computed over the pseudo header of the IPv6 packet carrying the message.
+    pub fn fill_checksum(&mut self, source: IpAddr, destination: IpAddr) {
+        put_u16(self.buf.as_mut(), 2, 0);
+        let checksum = transport_checksum(source, destination, IPPROTO_ICMPV6, self.buf.as_ref());
+        put_u16(self.buf.as_mut(), 2, checksum);
+    }
+}
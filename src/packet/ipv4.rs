@@ -0,0 +1,175 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use super::{get_u16, put_u16};
+use crate::error::{Error, Result};
+use crate::offload::{checksum_add, checksum_fold};
+use std::net::Ipv4Addr;
+
+const HEADER_LEN: usize = 20;
+
+/// A view of an IPv4 packet.
+#[derive(Clone, Copy, Debug)]
+pub struct Ipv4Packet<T> {
+    buf: T,
+}
+
+impl<T: AsRef<[u8]>> Ipv4Packet<T> {
+    /// View `buf` as an IPv4 packet, checking its header and lengths. The bytes past the total
+    /// length, such as an Ethernet padding, are ignored.
+    pub fn new(buf: T) -> Result<Self> {
+        let data = buf.as_ref();
+        if data.len() < HEADER_LEN {
+            return Err(Error::MalformedPacket("truncated IPv4 header"));
+        }
+        if data[0] >> 4 != 4 {
+            return Err(Error::MalformedPacket("not an IPv4 packet"));
+        }
+        let header_len = (data[0] & 0x0f) as usize * 4;
+        if header_len < HEADER_LEN || header_len > data.len() {
+            return Err(Error::MalformedPacket("invalid IPv4 header length"));
+        }
+        let total_len = get_u16(data, 2) as usize;
+        if total_len < header_len || total_len > data.len() {
+            return Err(Error::MalformedPacket("invalid IPv4 total length"));
+        }
+        Ok(Ipv4Packet { buf })
+    }
+
+    /// Consumes the view, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+
+    /// The bytes of the packet, up to its total length.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf.as_ref()[..self.total_len() as usize]
+    }
+
+    pub fn header_len(&self) -> usize {
+        (self.buf.as_ref()[0] & 0x0f) as usize * 4
+    }
+
+    pub fn dscp(&self) -> u8 {
+        self.buf.as_ref()[1] >> 2
+    }
+
+    pub fn ecn(&self) -> u8 {
+        self.buf.as_ref()[1] & 0x03
+    }
+
+    pub fn total_len(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 2)
+    }
+
+    pub fn identification(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 4)
+    }
+
+    pub fn dont_fragment(&self) -> bool {
+        self.buf.as_ref()[6] & 0x40 != 0
+    }
+
+    pub fn more_fragments(&self) -> bool {
+        self.buf.as_ref()[6] & 0x20 != 0
+    }
+
+    /// The offset of the fragment in the original payload, in bytes.
+    pub fn fragment_offset(&self) -> u16 {
+        (get_u16(self.buf.as_ref(), 6) & 0x1fff) << 3
+    }
+
+    pub fn ttl(&self) -> u8 {
+        self.buf.as_ref()[8]
+    }
+
+    pub fn protocol(&self) -> u8 {
+        self.buf.as_ref()[9]
+    }
+
+    pub fn checksum(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 10)
+    }
+
+    pub fn source(&self) -> Ipv4Addr {
+        let data = self.buf.as_ref();
+        Ipv4Addr::new(data[12], data[13], data[14], data[15])
+    }
+
+    pub fn destination(&self) -> Ipv4Addr {
+        let data = self.buf.as_ref();
+        Ipv4Addr::new(data[16], data[17], data[18], data[19])
+    }
+
+    pub fn options(&self) -> &[u8] {
+        &self.buf.as_ref()[HEADER_LEN..self.header_len()]
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.buf.as_ref()[self.header_len()..self.total_len() as usize]
+    }
+
+    /// Verify the header checksum.
+    pub fn verify_checksum(&self) -> bool {
+        let header = &self.buf.as_ref()[..self.header_len()];
+        !checksum_fold(checksum_add(0, header)) == 0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Ipv4Packet<T> {
+    pub fn set_dscp(&mut self, dscp: u8) {
+        let data = self.buf.as_mut();
+        data[1] = (dscp << 2) | (data[1] & 0x03);
+    }
+
+    pub fn set_ecn(&mut self, ecn: u8) {
+        let data = self.buf.as_mut();
+        data[1] = (data[1] & !0x03) | (ecn & 0x03);
+    }
+
+    pub fn set_identification(&mut self, identification: u16) {
+        put_u16(self.buf.as_mut(), 4, identification);
+    }
+
+    pub fn set_ttl(&mut self, ttl: u8) {
+        self.buf.as_mut()[8] = ttl;
+    }
+
+    pub fn set_source(&mut self, addr: Ipv4Addr) {
+        self.buf.as_mut()[12..16].copy_from_slice(&addr.octets());
+    }
+
+    pub fn set_destination(&mut self, addr: Ipv4Addr) {
+        self.buf.as_mut()[16..20].copy_from_slice(&addr.octets());
+    }
+
+    pub fn options_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        &mut self.buf.as_mut()[HEADER_LEN..header_len]
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let (header_len, total_len) = (self.header_len(), self.total_len() as usize);
+        &mut self.buf.as_mut()[header_len..total_len]
+    }
+
+    /// Update the header checksum.
+    pub fn fill_checksum(&mut self) {
+        let header_len = self.header_len();
+        let header = &mut self.buf.as_mut()[..header_len];
+        put_u16(header, 10, 0);
+        let checksum = !checksum_fold(checksum_add(0, header));
+        put_u16(header, 10, checksum);
+    }
+}
@@ -0,0 +1,252 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use super::{get_u16, get_u32, put_u32};
+use crate::error::{Error, Result};
+use std::net::Ipv6Addr;
+
+const HEADER_LEN: usize = 40;
+
+const HOP_BY_HOP: u8 = 0;
+const ROUTING: u8 = 43;
+const FRAGMENT: u8 = 44;
+const AUTHENTICATION: u8 = 51;
+const DESTINATION_OPTIONS: u8 = 60;
+const MOBILITY: u8 = 135;
+const HOST_IDENTITY: u8 = 139;
+const SHIM6: u8 = 140;
+
+/// The length of the extension header of type `kind` at the start of `buf`, `None` when `kind`
+/// is not an extension header or the header is truncated.
+fn extension_len(kind: u8, buf: &[u8]) -> Option<usize> {
+    let len = match kind {
+        FRAGMENT => 8,
+        AUTHENTICATION => (*buf.get(1)? as usize + 2) * 4,
+        HOP_BY_HOP | ROUTING | DESTINATION_OPTIONS | MOBILITY | HOST_IDENTITY | SHIM6 => {
+            (*buf.get(1)? as usize + 1) * 8
+        }
+        _ => return None,
+    };
+    (len <= buf.len()).then_some(len)
+}
+
+fn is_extension(kind: u8) -> bool {
+    matches!(
+        kind,
+        HOP_BY_HOP
+            | ROUTING
+            | FRAGMENT
+            | AUTHENTICATION
+            | DESTINATION_OPTIONS
+            | MOBILITY
+            | HOST_IDENTITY
+            | SHIM6
+    )
+}
+
+/// An IPv6 extension header, such as a fragment or routing header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ExtensionHeader<'a> {
+    /// The type of the header, as the next header field before it tells.
+    pub kind: u8,
+    /// The bytes of the header, starting with its next header field.
+    pub bytes: &'a [u8],
+}
+
+/// An iterator over the extension headers of an IPv6 packet.
+#[derive(Clone, Debug)]
+pub struct ExtensionHeaders<'a> {
+    buf: &'a [u8],
+    next: u8,
+}
+
+impl<'a> Iterator for ExtensionHeaders<'a> {
+    type Item = ExtensionHeader<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let len = extension_len(self.next, self.buf)?;
+        let (bytes, rest) = self.buf.split_at(len);
+        let header = ExtensionHeader {
+            kind: self.next,
+            bytes,
+        };
+        self.next = bytes[0];
+        self.buf = rest;
+        Some(header)
+    }
+}
+
+/// A view of an IPv6 packet, with its extension headers.
+#[derive(Clone, Copy, Debug)]
+pub struct Ipv6Packet<T> {
+    buf: T,
+    /// The length of the fixed header and the extension headers.
+    header_len: usize,
+    /// The protocol of the payload past the extension headers.
+    protocol: u8,
+}
+
+impl<T: AsRef<[u8]>> Ipv6Packet<T> {
+    /// View `buf` as an IPv6 packet, checking its header, extension headers and lengths.
+    /// The bytes past the payload length, such as an Ethernet padding, are ignored.
+    pub fn new(buf: T) -> Result<Self> {
+        let data = buf.as_ref();
+        if data.len() < HEADER_LEN {
+            return Err(Error::MalformedPacket("truncated IPv6 header"));
+        }
+        if data[0] >> 4 != 6 {
+            return Err(Error::MalformedPacket("not an IPv6 packet"));
+        }
+        let end = HEADER_LEN + get_u16(data, 4) as usize;
+        if end > data.len() {
+            return Err(Error::MalformedPacket("invalid IPv6 payload length"));
+        }
+        let mut headers = ExtensionHeaders {
+            buf: &data[HEADER_LEN..end],
+            next: data[6],
+        };
+        let header_len = HEADER_LEN
+            + headers
+                .by_ref()
+                .map(|header| header.bytes.len())
+                .sum::<usize>();
+        if is_extension(headers.next) {
+            return Err(Error::MalformedPacket("truncated IPv6 extension header"));
+        }
+        let protocol = headers.next;
+        Ok(Ipv6Packet {
+            buf,
+            header_len,
+            protocol,
+        })
+    }
+
+    /// Consumes the view, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+
+    /// The bytes of the packet, up to the end of its payload.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf.as_ref()[..self.end()]
+    }
+
+    fn end(&self) -> usize {
+        HEADER_LEN + self.payload_len() as usize
+    }
+
+    pub fn traffic_class(&self) -> u8 {
+        (get_u16(self.buf.as_ref(), 0) >> 4) as u8
+    }
+
+    pub fn flow_label(&self) -> u32 {
+        get_u32(self.buf.as_ref(), 0) & 0x000f_ffff
+    }
+
+    /// The length of the extension headers and the payload.
+    pub fn payload_len(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 4)
+    }
+
+    /// The type of the first extension header, or the protocol of the payload without any.
+    pub fn next_header(&self) -> u8 {
+        self.buf.as_ref()[6]
+    }
+
+    pub fn hop_limit(&self) -> u8 {
+        self.buf.as_ref()[7]
+    }
+
+    pub fn source(&self) -> Ipv6Addr {
+        let octets: [u8; 16] = self.buf.as_ref()[8..24].try_into().unwrap();
+        octets.into()
+    }
+
+    pub fn destination(&self) -> Ipv6Addr {
+        let octets: [u8; 16] = self.buf.as_ref()[24..40].try_into().unwrap();
+        octets.into()
+    }
+
+    pub fn extension_headers(&self) -> ExtensionHeaders<'_> {
+        ExtensionHeaders {
+            buf: &self.buf.as_ref()[HEADER_LEN..self.header_len],
+            next: self.next_header(),
+        }
+    }
+
+    /// The length of the fixed header and the extension headers.
+    pub fn header_len(&self) -> usize {
+        self.header_len
+    }
+
+    /// The protocol of the payload, past the extension headers.
+    pub fn protocol(&self) -> u8 {
+        self.protocol
+    }
+
+    fn fragment(&self) -> Option<u16> {
+        self.extension_headers()
+            .find(|header| header.kind == FRAGMENT)
+            .map(|header| get_u16(header.bytes, 2))
+    }
+
+    /// The offset of the fragment in the original payload, in bytes.
+    pub fn fragment_offset(&self) -> u16 {
+        self.fragment().map_or(0, |fragment| fragment & !0x07)
+    }
+
+    pub fn more_fragments(&self) -> bool {
+        self.fragment().is_some_and(|fragment| fragment & 0x01 != 0)
+    }
+
+    /// The payload, past the extension headers.
+    pub fn payload(&self) -> &[u8] {
+        &self.buf.as_ref()[self.header_len..self.end()]
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> Ipv6Packet<T> {
+    pub fn set_traffic_class(&mut self, traffic_class: u8) {
+        let word = get_u32(self.buf.as_ref(), 0);
+        let word = (word & 0xf00f_ffff) | (traffic_class as u32) << 20;
+        put_u32(self.buf.as_mut(), 0, word);
+    }
+
+    pub fn set_flow_label(&mut self, flow_label: u32) {
+        let word = get_u32(self.buf.as_ref(), 0);
+        put_u32(
+            self.buf.as_mut(),
+            0,
+            (word & 0xfff0_0000) | (flow_label & 0x000f_ffff),
+        );
+    }
+
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        self.buf.as_mut()[7] = hop_limit;
+    }
+
+    pub fn set_source(&mut self, addr: Ipv6Addr) {
+        self.buf.as_mut()[8..24].copy_from_slice(&addr.octets());
+    }
+
+    pub fn set_destination(&mut self, addr: Ipv6Addr) {
+        self.buf.as_mut()[24..40].copy_from_slice(&addr.octets());
+    }
+
+    /// The payload, past the extension headers.
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let (start, end) = (self.header_len, self.end());
+        &mut self.buf.as_mut()[start..end]
+    }
+}
@@ -0,0 +1,439 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Zero-copy views of the IP packets exchanged with a device, and of their TCP, UDP, ICMP and
+//! ICMPv6 payloads.
+//!
+//! The views borrow any `AsRef<[u8]>` buffer, and the ones over an `AsMut<[u8]>` buffer can
+//! rewrite the fields in place. Rewriting a field leaves the checksums alone, `fill_checksum`
+//! updates them once done.
+
+mod icmp;
+mod ipv4;
+mod ipv6;
+mod tcp;
+mod udp;
+
+pub use self::icmp::{
+    IcmpPacket, Icmpv6Packet, ICMPV6_ECHO_REPLY, ICMPV6_ECHO_REQUEST, ICMP_ECHO_REPLY,
+    ICMP_ECHO_REQUEST,
+};
+pub use self::ipv4::Ipv4Packet;
+pub use self::ipv6::{ExtensionHeader, ExtensionHeaders, Ipv6Packet};
+pub use self::tcp::{
+    TcpPacket, TCP_ACK, TCP_CWR, TCP_ECE, TCP_FIN, TCP_PSH, TCP_RST, TCP_SYN, TCP_URG,
+};
+pub use self::udp::UdpPacket;
+
+use crate::error::{Error, Result};
+use crate::offload::{checksum_add, checksum_fold};
+use std::net::{IpAddr, SocketAddr};
+
+pub const IPPROTO_ICMP: u8 = 1;
+pub const IPPROTO_TCP: u8 = 6;
+pub const IPPROTO_UDP: u8 = 17;
+pub const IPPROTO_ICMPV6: u8 = 58;
+
+fn get_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn get_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_be_bytes());
+}
+
+fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+/// The checksum of the transport `data` carried between `source` and `destination`,
+/// zero when `data` holds a valid checksum already.
+fn transport_checksum(source: IpAddr, destination: IpAddr, protocol: u8, data: &[u8]) -> u16 {
+    let address_sum = |addr: IpAddr| match addr {
+        IpAddr::V4(addr) => checksum_add(0, &addr.octets()),
+        IpAddr::V6(addr) => checksum_add(0, &addr.octets()),
+    };
+    let sum = address_sum(source) + address_sum(destination) + protocol as u64 + data.len() as u64;
+    !checksum_fold(checksum_add(sum, data))
+}
+
+/// A view of an IPv4 or IPv6 packet, told apart by its version.
+#[derive(Clone, Copy, Debug)]
+pub enum IpPacket<T> {
+    V4(Ipv4Packet<T>),
+    V6(Ipv6Packet<T>),
+}
+
+/// A view of the transport payload of an IP packet.
+#[derive(Debug)]
+pub enum Transport<T> {
+    Tcp(TcpPacket<T>),
+    Udp(UdpPacket<T>),
+    Icmp(IcmpPacket<T>),
+    Icmpv6(Icmpv6Packet<T>),
+    /// Another protocol, or a fragment past the first one, with its protocol number.
+    Other(u8, T),
+}
+
+/// The addresses, ports and protocol identifying the flow of a TCP or UDP packet.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct FiveTuple {
+    pub source: SocketAddr,
+    pub destination: SocketAddr,
+    pub protocol: u8,
+}
+
+fn transport<B: AsRef<[u8]>>(
+    protocol: u8,
+    fragment_offset: u16,
+    payload: B,
+) -> Result<Transport<B>> {
+    if fragment_offset != 0 {
+        return Ok(Transport::Other(protocol, payload));
+    }
+    Ok(match protocol {
+        IPPROTO_TCP => Transport::Tcp(TcpPacket::new(payload)?),
+        IPPROTO_UDP => Transport::Udp(UdpPacket::new(payload)?),
+        IPPROTO_ICMP => Transport::Icmp(IcmpPacket::new(payload)?),
+        IPPROTO_ICMPV6 => Transport::Icmpv6(Icmpv6Packet::new(payload)?),
+        _ => Transport::Other(protocol, payload),
+    })
+}
+
+impl<T: AsRef<[u8]>> IpPacket<T> {
+    /// View `buf` as an IPv4 or IPv6 packet, checking its headers and lengths.
+    pub fn new(buf: T) -> Result<Self> {
+        match buf.as_ref().first().map(|byte| byte >> 4) {
+            Some(4) => Ok(IpPacket::V4(Ipv4Packet::new(buf)?)),
+            Some(6) => Ok(IpPacket::V6(Ipv6Packet::new(buf)?)),
+            Some(_) => Err(Error::MalformedPacket("unknown IP version")),
+            None => Err(Error::MalformedPacket("empty packet")),
+        }
+    }
+
+    /// Consumes the view, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        match self {
+            IpPacket::V4(packet) => packet.into_inner(),
+            IpPacket::V6(packet) => packet.into_inner(),
+        }
+    }
+
+    pub fn source(&self) -> IpAddr {
+        match self {
+            IpPacket::V4(packet) => packet.source().into(),
+            IpPacket::V6(packet) => packet.source().into(),
+        }
+    }
+
+    pub fn destination(&self) -> IpAddr {
+        match self {
+            IpPacket::V4(packet) => packet.destination().into(),
+            IpPacket::V6(packet) => packet.destination().into(),
+        }
+    }
+
+    /// The IPv4 time to live or the IPv6 hop limit.
+    pub fn hop_limit(&self) -> u8 {
+        match self {
+            IpPacket::V4(packet) => packet.ttl(),
+            IpPacket::V6(packet) => packet.hop_limit(),
+        }
+    }
+
+    /// The protocol of the payload, past the IPv6 extension headers.
+    pub fn protocol(&self) -> u8 {
+        match self {
+            IpPacket::V4(packet) => packet.protocol(),
+            IpPacket::V6(packet) => packet.protocol(),
+        }
+    }
+
+    /// The offset of a fragment in the original payload, in bytes.
+    pub fn fragment_offset(&self) -> u16 {
+        match self {
+            IpPacket::V4(packet) => packet.fragment_offset(),
+            IpPacket::V6(packet) => packet.fragment_offset(),
+        }
+    }
+
+    /// Whether the packet is a fragment of a larger one.
+    pub fn is_fragment(&self) -> bool {
+        match self {
+            IpPacket::V4(packet) => packet.more_fragments() || packet.fragment_offset() != 0,
+            IpPacket::V6(packet) => packet.more_fragments() || packet.fragment_offset() != 0,
+        }
+    }
+
+    /// The length of the IP header, including the IPv4 options or the IPv6 extension headers.
+    pub fn header_len(&self) -> usize {
+        match self {
+            IpPacket::V4(packet) => packet.header_len(),
+            IpPacket::V6(packet) => packet.header_len(),
+        }
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        match self {
+            IpPacket::V4(packet) => packet.payload(),
+            IpPacket::V6(packet) => packet.payload(),
+        }
+    }
+
+    /// View the payload according to its protocol.
+    pub fn transport(&self) -> Result<Transport<&[u8]>> {
+        transport(self.protocol(), self.fragment_offset(), self.payload())
+    }
+
+    /// The flow of a TCP or UDP packet, `None` for other packets and past the first fragment.
+    pub fn five_tuple(&self) -> Option<FiveTuple> {
+        let (source_port, destination_port) = match self.transport().ok()? {
+            Transport::Tcp(tcp) => (tcp.source_port(), tcp.destination_port()),
+            Transport::Udp(udp) => (udp.source_port(), udp.destination_port()),
+            _ => return None,
+        };
+        Some(FiveTuple {
+            source: SocketAddr::new(self.source(), source_port),
+            destination: SocketAddr::new(self.destination(), destination_port),
+            protocol: self.protocol(),
+        })
+    }
+
+    /// Verify the IPv4 header checksum and the checksum of the transport payload.
+    ///
+    /// [Note: The checksum of a fragmented payload cannot be verified, only the IPv4 header
+    /// checksum of a fragment is. -- end note]
+    pub fn verify_checksums(&self) -> bool {
+        if let IpPacket::V4(packet) = self {
+            if !packet.verify_checksum() {
+                return false;
+            }
+        }
+        if self.is_fragment() {
+            return true;
+        }
+        let (source, destination) = (self.source(), self.destination());
+        match self.transport() {
+            Ok(Transport::Tcp(tcp)) => tcp.verify_checksum(source, destination),
+            Ok(Transport::Udp(udp)) => udp.verify_checksum(source, destination),
+            Ok(Transport::Icmp(icmp)) => icmp.verify_checksum(),
+            Ok(Transport::Icmpv6(icmp)) => icmp.verify_checksum(source, destination),
+            Ok(Transport::Other(..)) => true,
+            Err(_) => false,
+        }
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> IpPacket<T> {
+    /// Set the source address, which must be of the version of the packet.
+    pub fn set_source(&mut self, addr: IpAddr) -> Result<()> {
+        match (self, addr) {
+            (IpPacket::V4(packet), IpAddr::V4(addr)) => packet.set_source(addr),
+            (IpPacket::V6(packet), IpAddr::V6(addr)) => packet.set_source(addr),
+            _ => return Err(Error::InvalidAddress),
+        }
+        Ok(())
+    }
+
+    /// Set the destination address, which must be of the version of the packet.
+    pub fn set_destination(&mut self, addr: IpAddr) -> Result<()> {
+        match (self, addr) {
+            (IpPacket::V4(packet), IpAddr::V4(addr)) => packet.set_destination(addr),
+            (IpPacket::V6(packet), IpAddr::V6(addr)) => packet.set_destination(addr),
+            _ => return Err(Error::InvalidAddress),
+        }
+        Ok(())
+    }
+
+    /// Set the IPv4 time to live or the IPv6 hop limit.
+    pub fn set_hop_limit(&mut self, hop_limit: u8) {
+        match self {
+            IpPacket::V4(packet) => packet.set_ttl(hop_limit),
+            IpPacket::V6(packet) => packet.set_hop_limit(hop_limit),
+        }
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        match self {
+            IpPacket::V4(packet) => packet.payload_mut(),
+            IpPacket::V6(packet) => packet.payload_mut(),
+        }
+    }
+
+    /// View the payload according to its protocol, to rewrite it in place.
+    pub fn transport_mut(&mut self) -> Result<Transport<&mut [u8]>> {
+        let (protocol, fragment_offset) = (self.protocol(), self.fragment_offset());
+        transport(protocol, fragment_offset, self.payload_mut())
+    }
+
+    /// Update the IPv4 header checksum and the checksum of the transport payload, such as after
+    /// rewriting the addresses. The transport checksum of a fragment is left alone.
+    pub fn fill_checksums(&mut self) -> Result<()> {
+        if !self.is_fragment() {
+            let (source, destination) = (self.source(), self.destination());
+            match self.transport_mut()? {
+                Transport::Tcp(mut tcp) => tcp.fill_checksum(source, destination),
+                Transport::Udp(mut udp) => udp.fill_checksum(source, destination),
+                Transport::Icmp(mut icmp) => icmp.fill_checksum(),
+                Transport::Icmpv6(mut icmp) => icmp.fill_checksum(source, destination),
+                Transport::Other(..) => {}
+            }
+        }
+        if let IpPacket::V4(packet) = self {
+            packet.fill_checksum();
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_packet_views() {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    // An IPv4 ICMP echo request, answered in place.
+    let mut buf = vec![0x45, 0, 0, 32, 0, 0x42, 0x40, 0, 64, IPPROTO_ICMP, 0, 0];
+    buf.extend_from_slice(&[10, 0, 0, 9, 10, 0, 0, 1]);
+    buf.extend_from_slice(&[ICMP_ECHO_REQUEST, 0, 0, 0, 0x12, 0x34, 0, 7, 1, 2, 3, 4]);
+    let mut packet = IpPacket::new(&mut buf[..]).unwrap();
+    assert!(!packet.verify_checksums());
+    packet.fill_checksums().unwrap();
+    assert!(packet.verify_checksums());
+    assert_eq!(packet.five_tuple(), None);
+    let (source, destination) = (packet.source(), packet.destination());
+    packet.set_source(destination).unwrap();
+    packet.set_destination(source).unwrap();
+    assert!(packet.set_source(Ipv6Addr::LOCALHOST.into()).is_err());
+    let Ok(Transport::Icmp(mut icmp)) = packet.transport_mut() else {
+        panic!("not an ICMP packet");
+    };
+    assert_eq!((icmp.identifier(), icmp.sequence()), (0x1234, 7));
+    icmp.set_msg_type(ICMP_ECHO_REPLY);
+    assert!(!icmp.verify_checksum());
+    packet.fill_checksums().unwrap();
+    assert!(packet.verify_checksums());
+    let IpPacket::V4(ipv4) = packet else {
+        panic!("not an IPv4 packet");
+    };
+    assert_eq!(ipv4.source(), Ipv4Addr::new(10, 0, 0, 1));
+    assert!(ipv4.dont_fragment());
+    assert_eq!(ipv4.payload()[0], ICMP_ECHO_REPLY);
+
+    // An IPv4 UDP datagram, followed by an Ethernet padding.
+    let mut buf = vec![0x45, 0, 0, 31, 0, 0, 0, 0, 64, IPPROTO_UDP, 0, 0];
+    buf.extend_from_slice(&[10, 0, 0, 1, 10, 0, 0, 2]);
+    buf.extend_from_slice(&[0x30, 0x39, 0, 53, 0, 11, 0, 0, b'a', b'b', b'c', 0, 0, 0]);
+    let mut packet = Ipv4Packet::new(&mut buf[..]).unwrap();
+    assert_eq!(packet.payload().len(), 11);
+    let mut udp = UdpPacket::new(packet.payload_mut()).unwrap();
+    // No checksum is fine over IPv4.
+    assert!(udp.verify_checksum(
+        Ipv4Addr::new(10, 0, 0, 1).into(),
+        Ipv4Addr::new(10, 0, 0, 2).into()
+    ));
+    udp.fill_checksum(
+        Ipv4Addr::new(10, 0, 0, 1).into(),
+        Ipv4Addr::new(10, 0, 0, 2).into(),
+    );
+    packet.fill_checksum();
+    let packet = IpPacket::new(&buf[..]).unwrap();
+    assert!(packet.verify_checksums());
+    assert_eq!(
+        packet.five_tuple(),
+        Some(FiveTuple {
+            source: "10.0.0.1:12345".parse().unwrap(),
+            destination: "10.0.0.2:53".parse().unwrap(),
+            protocol: IPPROTO_UDP,
+        })
+    );
+    let Ok(Transport::Udp(udp)) = packet.transport() else {
+        panic!("not a UDP packet");
+    };
+    assert_eq!(udp.payload(), b"abc");
+
+    // An IPv6 TCP segment behind a hop-by-hop options header.
+    let source: Ipv6Addr = "fd00::1".parse().unwrap();
+    let destination: Ipv6Addr = "fd00::2".parse().unwrap();
+    let mut buf = vec![0x60, 0, 0, 0, 0, 8 + 24, 0, 64];
+    buf.extend_from_slice(&source.octets());
+    buf.extend_from_slice(&destination.octets());
+    buf.extend_from_slice(&[IPPROTO_TCP, 0, 1, 4, 0, 0, 0, 0]);
+    buf.extend_from_slice(&[
+        0x1f, 0x90, 0, 80, 0, 0, 0, 1, 0, 0, 0, 0, 0x50, TCP_SYN, 0xff, 0xff,
+    ]);
+    buf.extend_from_slice(&[0, 0, 0, 0, b'd', b'a', b't', b'a']);
+    let mut packet = Ipv6Packet::new(&mut buf[..]).unwrap();
+    assert_eq!(packet.header_len(), 48);
+    assert_eq!(packet.next_header(), 0);
+    assert_eq!(packet.protocol(), IPPROTO_TCP);
+    assert_eq!(
+        packet
+            .extension_headers()
+            .map(|header| header.kind)
+            .collect::<Vec<_>>(),
+        [0]
+    );
+    packet.set_flow_label(0xabcde);
+    packet.set_traffic_class(0x2e);
+    assert_eq!(
+        (packet.flow_label(), packet.traffic_class()),
+        (0xabcde, 0x2e)
+    );
+    let mut packet = IpPacket::V6(packet);
+    packet.fill_checksums().unwrap();
+    assert!(packet.verify_checksums());
+    let Ok(Transport::Tcp(mut tcp)) = packet.transport_mut() else {
+        panic!("not a TCP packet");
+    };
+    assert_eq!(tcp.flags(), TCP_SYN);
+    assert_eq!(tcp.payload(), b"data");
+    tcp.set_destination_port(8080);
+    assert!(!packet.verify_checksums());
+    packet.fill_checksums().unwrap();
+    assert_eq!(packet.five_tuple().unwrap().destination.port(), 8080);
+
+    // A fragment past the first one has no transport header.
+    let mut buf = vec![0x60, 0, 0, 0, 0, 16, 44, 64];
+    buf.extend_from_slice(&source.octets());
+    buf.extend_from_slice(&destination.octets());
+    buf.extend_from_slice(&[IPPROTO_UDP, 0, 0x05, 0xa8, 0, 0, 0, 1]);
+    buf.extend_from_slice(&[0; 8]);
+    let packet = IpPacket::new(&buf[..]).unwrap();
+    assert!(packet.is_fragment());
+    assert_eq!(packet.fragment_offset(), 1448);
+    assert!(matches!(
+        packet.transport(),
+        Ok(Transport::Other(IPPROTO_UDP, _))
+    ));
+    assert!(packet.verify_checksums());
+
+    // Malformed packets.
+    assert!(IpPacket::new(&[][..]).is_err());
+    assert!(IpPacket::new(&[0x45, 0, 0, 20][..]).is_err());
+    assert!(Ipv6Packet::new(&buf[..50]).is_err());
+    buf[5] = 4;
+    assert!(matches!(
+        Ipv6Packet::new(&buf[..]),
+        Err(Error::MalformedPacket(_))
+    ));
+    assert!(TcpPacket::new(&[0; 19][..]).is_err());
+}
@@ -0,0 +1,155 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use super::{get_u16, get_u32, put_u16, put_u32, transport_checksum, IPPROTO_TCP};
+use crate::error::{Error, Result};
+use std::net::IpAddr;
+
+const HEADER_LEN: usize = 20;
+
+pub const TCP_FIN: u8 = 0x01;
+pub const TCP_SYN: u8 = 0x02;
+pub const TCP_RST: u8 = 0x04;
+pub const TCP_PSH: u8 = 0x08;
+pub const TCP_ACK: u8 = 0x10;
+pub const TCP_URG: u8 = 0x20;
+pub const TCP_ECE: u8 = 0x40;
+pub const TCP_CWR: u8 = 0x80;
+
+/// A view of a TCP segment.
+#[derive(Clone, Copy, Debug)]
+pub struct TcpPacket<T> {
+    buf: T,
+}
+
+impl<T: AsRef<[u8]>> TcpPacket<T> {
+    /// View `buf`, the payload of an IP packet, as a TCP segment, checking its header length.
+    pub fn new(buf: T) -> Result<Self> {
+        let data = buf.as_ref();
+        if data.len() < HEADER_LEN {
+            return Err(Error::MalformedPacket("truncated TCP header"));
+        }
+        let header_len = (data[12] >> 4) as usize * 4;
+        if header_len < HEADER_LEN || header_len > data.len() {
+            return Err(Error::MalformedPacket("invalid TCP data offset"));
+        }
+        Ok(TcpPacket { buf })
+    }
+
+    /// Consumes the view, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        self.buf.as_ref()
+    }
+
+    pub fn source_port(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 0)
+    }
+
+    pub fn destination_port(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 2)
+    }
+
+    pub fn seq_number(&self) -> u32 {
+        get_u32(self.buf.as_ref(), 4)
+    }
+
+    pub fn ack_number(&self) -> u32 {
+        get_u32(self.buf.as_ref(), 8)
+    }
+
+    pub fn header_len(&self) -> usize {
+        (self.buf.as_ref()[12] >> 4) as usize * 4
+    }
+
+    /// The flags, a combination of `TCP_FIN`, `TCP_SYN`, `TCP_ACK` and the others.
+    pub fn flags(&self) -> u8 {
+        self.buf.as_ref()[13]
+    }
+
+    pub fn window(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 14)
+    }
+
+    pub fn checksum(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 16)
+    }
+
+    pub fn urgent_pointer(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 18)
+    }
+
+    pub fn options(&self) -> &[u8] {
+        &self.buf.as_ref()[HEADER_LEN..self.header_len()]
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.buf.as_ref()[self.header_len()..]
+    }
+
+    /// Verify the checksum, computed over the pseudo header of the IP packet carrying the segment.
+    pub fn verify_checksum(&self, source: IpAddr, destination: IpAddr) -> bool {
+        transport_checksum(source, destination, IPPROTO_TCP, self.buf.as_ref()) == 0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> TcpPacket<T> {
+    pub fn set_source_port(&mut self, port: u16) {
+        put_u16(self.buf.as_mut(), 0, port);
+    }
+
+    pub fn set_destination_port(&mut self, port: u16) {
+        put_u16(self.buf.as_mut(), 2, port);
+    }
+
+    pub fn set_seq_number(&mut self, seq_number: u32) {
+        put_u32(self.buf.as_mut(), 4, seq_number);
+    }
+
+    pub fn set_ack_number(&mut self, ack_number: u32) {
+        put_u32(self.buf.as_mut(), 8, ack_number);
+    }
+
+    pub fn set_flags(&mut self, flags: u8) {
+        self.buf.as_mut()[13] = flags;
+    }
+
+    pub fn set_window(&mut self, window: u16) {
+        put_u16(self.buf.as_mut(), 14, window);
+    }
+
+    pub fn set_urgent_pointer(&mut self, urgent_pointer: u16) {
+        put_u16(self.buf.as_mut(), 18, urgent_pointer);
+    }
+
+    pub fn options_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        &mut self.buf.as_mut()[HEADER_LEN..header_len]
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let header_len = self.header_len();
+        &mut self.buf.as_mut()[header_len..]
+    }
+
+    /// Update the checksum, computed over the pseudo header of the IP packet carrying the segment.
+    pub fn fill_checksum(&mut self, source: IpAddr, destination: IpAddr) {
+        put_u16(self.buf.as_mut(), 16, 0);
+        let checksum = transport_checksum(source, destination, IPPROTO_TCP, self.buf.as_ref());
+        put_u16(self.buf.as_mut(), 16, checksum);
+    }
+}
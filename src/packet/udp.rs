@@ -0,0 +1,107 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use super::{get_u16, put_u16, transport_checksum, IPPROTO_UDP};
+use crate::error::{Error, Result};
+use std::net::IpAddr;
+
+const HEADER_LEN: usize = 8;
+
+/// A view of a UDP datagram.
+#[derive(Clone, Copy, Debug)]
+pub struct UdpPacket<T> {
+    buf: T,
+}
+
+impl<T: AsRef<[u8]>> UdpPacket<T> {
+    /// View `buf`, the payload of an IP packet, as a UDP datagram, checking its length.
+    pub fn new(buf: T) -> Result<Self> {
+        let data = buf.as_ref();
+        if data.len() < HEADER_LEN {
+            return Err(Error::MalformedPacket("truncated UDP header"));
+        }
+        let len = get_u16(data, 4) as usize;
+        if len < HEADER_LEN || len > data.len() {
+            return Err(Error::MalformedPacket("invalid UDP length"));
+        }
+        Ok(UdpPacket { buf })
+    }
+
+    /// Consumes the view, returning the underlying buffer.
+    pub fn into_inner(self) -> T {
+        self.buf
+    }
+
+    /// The bytes of the datagram, up to its length.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf.as_ref()[..self.len() as usize]
+    }
+
+    pub fn source_port(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 0)
+    }
+
+    pub fn destination_port(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 2)
+    }
+
+    /// The length of the header and the payload.
+    #[allow(clippy::len_without_is_empty)]
+    pub fn len(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 4)
+    }
+
+    pub fn checksum(&self) -> u16 {
+        get_u16(self.buf.as_ref(), 6)
+    }
+
+    pub fn payload(&self) -> &[u8] {
+        &self.buf.as_ref()[HEADER_LEN..self.len() as usize]
+    }
+
+    /// Verify the checksum, computed over the pseudo header of the IP packet carrying the datagram.
+    /// A zero checksum stands for no checksum over IPv4.
+    pub fn verify_checksum(&self, source: IpAddr, destination: IpAddr) -> bool {
+        if self.checksum() == 0 {
+            return source.is_ipv4();
+        }
+        transport_checksum(source, destination, IPPROTO_UDP, self.as_bytes()) == 0
+    }
+}
+
+impl<T: AsRef<[u8]> + AsMut<[u8]>> UdpPacket<T> {
+    pub fn set_source_port(&mut self, port: u16) {
+        put_u16(self.buf.as_mut(), 0, port);
+    }
+
+    pub fn set_destination_port(&mut self, port: u16) {
+        put_u16(self.buf.as_mut(), 2, port);
+    }
+
+    pub fn payload_mut(&mut self) -> &mut [u8] {
+        let len = self.len() as usize;
+        &mut self.buf.as_mut()[HEADER_LEN..len]
+    }
+
+    /// Update the checksum, computed over the pseudo header of the IP packet carrying the datagram.
+    pub fn fill_checksum(&mut self, source: IpAddr, destination: IpAddr) {
+        put_u16(self.buf.as_mut(), 6, 0);
+        let mut checksum = transport_checksum(source, destination, IPPROTO_UDP, self.as_bytes());
+        // A zero checksum means no checksum at all.
+        if checksum == 0 {
+            checksum = 0xffff;
+        }
+        put_u16(self.buf.as_mut(), 6, checksum);
+    }
+}
@@ -0,0 +1,674 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Capturing the packets exchanged with a device into a pcapng file, and replaying captures.
+
+use crate::configuration::Layer;
+use std::io::{Read, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// The link type of Ethernet frames.
+pub const LINKTYPE_ETHERNET: u16 = 1;
+/// The link type of IP packets, either IPv4 or IPv6.
+pub const LINKTYPE_RAW: u16 = 101;
+
+const SECTION_HEADER_BLOCK: u32 = 0x0A0D_0D0A;
+const INTERFACE_DESCRIPTION_BLOCK: u32 = 1;
+const SIMPLE_PACKET_BLOCK: u32 = 3;
+const ENHANCED_PACKET_BLOCK: u32 = 6;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+const OPT_ENDOFOPT: u16 = 0;
+const EPB_FLAGS: u16 = 2;
+const IF_TSRESOL: u16 = 9;
+
+/// The direction of a packet, from the point of view of the interface like `tcpdump` sees it.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Direction {
+    /// A packet written to the device, which the interface receives.
+    Inbound,
+    /// A packet read from the device, which the interface sends.
+    Outbound,
+}
+
+/// A writer of pcapng files, with one interface of the link type of the `Layer` of the device.
+#[derive(Debug)]
+pub struct PcapngWriter<W: Write> {
+    out: W,
+}
+
+impl<W: Write> PcapngWriter<W> {
+    /// Write the section header and the interface description to `out`.
+    pub fn new(mut out: W, layer: Layer) -> std::io::Result<Self> {
+        let mut shb = Vec::with_capacity(28);
+        shb.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        shb.extend_from_slice(&1_u16.to_le_bytes());
+        shb.extend_from_slice(&0_u16.to_le_bytes());
+        shb.extend_from_slice(&(-1_i64).to_le_bytes());
+        write_block(&mut out, SECTION_HEADER_BLOCK, &shb)?;
+
+        let link_type = match layer {
+            Layer::L2 => LINKTYPE_ETHERNET,
+            Layer::L3 => LINKTYPE_RAW,
+        };
+        let mut idb = Vec::with_capacity(8);
+        idb.extend_from_slice(&link_type.to_le_bytes());
+        idb.extend_from_slice(&0_u16.to_le_bytes());
+        // No snapshot length limit.
+        idb.extend_from_slice(&0_u32.to_le_bytes());
+        write_block(&mut out, INTERFACE_DESCRIPTION_BLOCK, &idb)?;
+        Ok(PcapngWriter { out })
+    }
+
+    /// Write a packet, with its direction and the time it was captured at.
+    pub fn write_packet(
+        &mut self,
+        direction: Direction,
+        timestamp: SystemTime,
+        packet: &[u8],
+    ) -> std::io::Result<()> {
+        // In microseconds, the default resolution.
+        let micros = timestamp
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_micros() as u64;
+        let mut epb = Vec::with_capacity(32 + packet.len());
+        epb.extend_from_slice(&0_u32.to_le_bytes());
+        epb.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        epb.extend_from_slice(&(micros as u32).to_le_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        epb.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        epb.extend_from_slice(packet);
+        epb.resize(align(epb.len()), 0);
+        let flags: u32 = match direction {
+            Direction::Inbound => 0b01,
+            Direction::Outbound => 0b10,
+        };
+        epb.extend_from_slice(&EPB_FLAGS.to_le_bytes());
+        epb.extend_from_slice(&4_u16.to_le_bytes());
+        epb.extend_from_slice(&flags.to_le_bytes());
+        epb.extend_from_slice(&OPT_ENDOFOPT.to_le_bytes());
+        epb.extend_from_slice(&0_u16.to_le_bytes());
+        write_block(&mut self.out, ENHANCED_PACKET_BLOCK, &epb)
+    }
+
+    /// Flush the underlying writer.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        self.out.flush()
+    }
+
+    /// Consumes the `PcapngWriter`, returning the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+/// Round `len` up to the 4 bytes alignment of pcapng blocks.
+const fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn write_block<W: Write>(out: &mut W, ty: u32, body: &[u8]) -> std::io::Result<()> {
+    let len = (12 + body.len()) as u32;
+    out.write_all(&ty.to_le_bytes())?;
+    out.write_all(&len.to_le_bytes())?;
+    out.write_all(body)?;
+    out.write_all(&len.to_le_bytes())
+}
+
+type SharedWriter = Arc<Mutex<PcapngWriter<Box<dyn Write + Send>>>>;
+
+/// A wrapper of a device, or of one of its halves, capturing every packet read from or
+/// written to it into a pcapng file.
+///
+/// It captures what `Read`, `Write` and their async counterparts go through, which makes it
+/// usable with a `Device`, an `AsyncDevice`, their split halves and `Framed`.
+///
+/// [Note: Failing to write the capture is logged, the packets still go through. -- end note]
+pub struct Capture<D> {
+    inner: D,
+    writer: SharedWriter,
+}
+
+impl<D> Capture<D> {
+    /// Capture the packets of `inner`, a device of the given `Layer`, into `out`.
+    pub fn new<W: Write + Send + 'static>(inner: D, out: W, layer: Layer) -> std::io::Result<Self> {
+        let out: Box<dyn Write + Send> = Box::new(out);
+        let writer = Arc::new(Mutex::new(PcapngWriter::new(out, layer)?));
+        Ok(Capture { inner, writer })
+    }
+
+    /// Capture the packets of `inner` into the same file, such as the other half of a split device.
+    pub fn attach<E>(&self, inner: E) -> Capture<E> {
+        Capture {
+            inner,
+            writer: self.writer.clone(),
+        }
+    }
+
+    /// Returns a reference to the wrapped device.
+    pub fn get_ref(&self) -> &D {
+        &self.inner
+    }
+
+    /// Returns a mutable reference to the wrapped device.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.inner
+    }
+
+    /// Consumes the `Capture`, returning the wrapped device.
+    pub fn into_inner(self) -> D {
+        self.inner
+    }
+
+    fn capture(&self, direction: Direction, packet: &[u8]) {
+        if packet.is_empty() {
+            return;
+        }
+        let mut writer = self.writer.lock().unwrap_or_else(|err| err.into_inner());
+        if let Err(err) = writer.write_packet(direction, SystemTime::now(), packet) {
+            log::warn!("failed to capture a packet: {err}");
+        }
+    }
+
+    fn flush_capture(&self) -> std::io::Result<()> {
+        self.writer
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .flush()
+    }
+}
+
+impl<D: Read> Read for Capture<D> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.capture(Direction::Outbound, &buf[..len]);
+        Ok(len)
+    }
+}
+
+impl<D: Write> Write for Capture<D> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let len = self.inner.write(buf)?;
+        self.capture(Direction::Inbound, &buf[..len]);
+        Ok(len)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()?;
+        self.flush_capture()
+    }
+}
+
+#[cfg(feature = "async")]
+impl<D: tokio::io::AsyncRead + Unpin> tokio::io::AsyncRead for Capture<D> {
+    fn poll_read(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        let filled = buf.filled().len();
+        futures_core::ready!(std::pin::Pin::new(&mut this.inner).poll_read(cx, buf))?;
+        this.capture(Direction::Outbound, &buf.filled()[filled..]);
+        std::task::Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(feature = "async")]
+impl<D: tokio::io::AsyncWrite + Unpin> tokio::io::AsyncWrite for Capture<D> {
+    fn poll_write(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+        buf: &[u8],
+    ) -> std::task::Poll<std::io::Result<usize>> {
+        let this = self.get_mut();
+        let len = futures_core::ready!(std::pin::Pin::new(&mut this.inner).poll_write(cx, buf))?;
+        this.capture(Direction::Inbound, &buf[..len]);
+        std::task::Poll::Ready(Ok(len))
+    }
+
+    fn poll_flush(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        futures_core::ready!(std::pin::Pin::new(&mut this.inner).poll_flush(cx))?;
+        std::task::Poll::Ready(this.flush_capture())
+    }
+
+    fn poll_shutdown(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<std::io::Result<()>> {
+        let this = self.get_mut();
+        futures_core::ready!(std::pin::Pin::new(&mut this.inner).poll_shutdown(cx))?;
+        std::task::Poll::Ready(this.flush_capture())
+    }
+}
+
+/// A packet of a capture file.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Record {
+    /// The time the packet was captured at, since the Unix epoch.
+    pub timestamp: Duration,
+    /// The direction of the packet, when the capture tells it.
+    pub direction: Option<Direction>,
+    /// The link type of the packet, such as `LINKTYPE_RAW` or `LINKTYPE_ETHERNET`.
+    pub link_type: u16,
+    /// The captured bytes of the packet.
+    pub data: Vec<u8>,
+}
+
+/// The format of the capture being replayed.
+#[derive(Debug)]
+enum Format {
+    /// A pcap file, with its link type and whether the timestamps are in nanoseconds.
+    Pcap { link_type: u16, nanos: bool },
+    /// A pcapng file, with the link type and timestamp resolution of each of its interfaces.
+    Pcapng { interfaces: Vec<(u16, Resolution)> },
+}
+
+/// The resolution of the timestamps of a pcapng interface, as units per second.
+#[derive(Clone, Copy, Debug)]
+struct Resolution(u64);
+
+impl Resolution {
+    fn duration(self, units: u64) -> Duration {
+        let secs = units / self.0;
+        let nanos = (units % self.0) as u128 * 1_000_000_000 / self.0 as u128;
+        Duration::new(secs, nanos as u32)
+    }
+}
+
+/// A reader of pcap and pcapng files, feeding their packets into a device.
+#[derive(Debug)]
+pub struct Replayer<R: Read> {
+    input: R,
+    big_endian: bool,
+    format: Format,
+    speed: f64,
+    /// The timestamp of the last packet, for the pcapng packets without one.
+    last_timestamp: Duration,
+}
+
+fn invalid_data(message: &str) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::InvalidData, message.to_string())
+}
+
+impl<R: Read> Replayer<R> {
+    /// Read the header of the pcap or pcapng file `input`.
+    pub fn new(mut input: R) -> std::io::Result<Self> {
+        let mut magic = [0; 4];
+        input.read_exact(&mut magic)?;
+        let mut replayer = Replayer {
+            input,
+            big_endian: false,
+            format: Format::Pcapng {
+                interfaces: Vec::new(),
+            },
+            speed: 1.0,
+            last_timestamp: Duration::ZERO,
+        };
+        if u32::from_le_bytes(magic) == SECTION_HEADER_BLOCK {
+            let len = replayer.read_u32()?;
+            let mut order = [0; 4];
+            replayer.input.read_exact(&mut order)?;
+            replayer.big_endian = u32::from_be_bytes(order) == BYTE_ORDER_MAGIC;
+            let len = if replayer.big_endian {
+                len.swap_bytes()
+            } else {
+                len
+            };
+            replayer.discard((len as usize).saturating_sub(12))?;
+            return Ok(replayer);
+        }
+
+        let (big_endian, nanos) = match magic {
+            [0xd4, 0xc3, 0xb2, 0xa1] => (false, false),
+            [0x4d, 0x3c, 0xb2, 0xa1] => (false, true),
+            [0xa1, 0xb2, 0xc3, 0xd4] => (true, false),
+            [0xa1, 0xb2, 0x3c, 0x4d] => (true, true),
+            _ => return Err(invalid_data("not a pcap or pcapng file")),
+        };
+        replayer.big_endian = big_endian;
+        // The version, time zone, accuracy and snapshot length.
+        replayer.discard(16)?;
+        let link_type = replayer.read_u32()? as u16;
+        replayer.format = Format::Pcap { link_type, nanos };
+        Ok(replayer)
+    }
+
+    /// Set the speed of the replay relative to the capture, such as 2.0 for twice as fast,
+    /// or `f64::INFINITY` for as fast as possible. It defaults to the recorded speed.
+    ///
+    /// [Note: A speed which is not positive replays as fast as possible too. -- end note]
+    pub fn speed(&mut self, factor: f64) -> &mut Self {
+        self.speed = factor;
+        self
+    }
+
+    /// Write the packets to `device` at the pace they were captured at, divided by the speed,
+    /// and return the number of packets written.
+    ///
+    /// The packets captured as read from a device are skipped, the ones of unknown direction
+    /// are written.
+    pub fn replay<W: Write + ?Sized>(&mut self, device: &mut W) -> std::io::Result<usize> {
+        let start = Instant::now();
+        let mut first = None;
+        let mut count = 0;
+        while let Some(record) = self.next_record()? {
+            if record.direction == Some(Direction::Outbound) {
+                continue;
+            }
+            let first = *first.get_or_insert(record.timestamp);
+            let offset = record.timestamp.saturating_sub(first).as_secs_f64() / self.speed;
+            let deadline = Duration::try_from_secs_f64(offset)
+                .ok()
+                .and_then(|offset| start.checked_add(offset));
+            if let Some(deadline) = deadline {
+                thread::sleep(deadline.saturating_duration_since(Instant::now()));
+            }
+            device.write_all(&record.data)?;
+            count += 1;
+        }
+        Ok(count)
+    }
+
+    /// Read the next packet of the capture, `None` at its end.
+    pub fn next_record(&mut self) -> std::io::Result<Option<Record>> {
+        match self.format {
+            Format::Pcap { link_type, nanos } => {
+                let Some(ts_secs) = self.read_u32_or_eof()? else {
+                    return Ok(None);
+                };
+                let ts_frac = self.read_u32()?;
+                let len = self.read_u32()?;
+                // The original length.
+                self.read_u32()?;
+                let nanos = match nanos {
+                    true if ts_frac < 1_000_000_000 => ts_frac,
+                    false if ts_frac < 1_000_000 => ts_frac * 1000,
+                    _ => return Err(invalid_data("invalid pcap timestamp")),
+                };
+                Ok(Some(Record {
+                    timestamp: Duration::new(ts_secs as u64, nanos),
+                    direction: None,
+                    link_type,
+                    data: self.read_vec(len as usize)?,
+                }))
+            }
+            Format::Pcapng { .. } => self.next_pcapng_record(),
+        }
+    }
+
+    fn next_pcapng_record(&mut self) -> std::io::Result<Option<Record>> {
+        loop {
+            let Some(ty) = self.read_u32_or_eof()? else {
+                return Ok(None);
+            };
+            let len = self.read_u32()? as usize;
+            if len < 12 || !len.is_multiple_of(4) {
+                return Err(invalid_data("invalid pcapng block length"));
+            }
+            let body = self.read_vec(len - 12)?;
+            // The trailing length.
+            self.read_u32()?;
+
+            let Format::Pcapng { interfaces } = &mut self.format else {
+                unreachable!();
+            };
+            let big_endian = self.big_endian;
+            let u16_at = |offset: usize| read_u16(&body, offset, big_endian);
+            let u32_at = |offset: usize| read_u32(&body, offset, big_endian);
+            match ty {
+                SECTION_HEADER_BLOCK => {
+                    // A new section, possibly of another byte order, with its own interfaces.
+                    self.big_endian = body
+                        .get(..4)
+                        .map(|order| order.try_into().unwrap())
+                        .map(u32::from_be_bytes)
+                        == Some(BYTE_ORDER_MAGIC);
+                    interfaces.clear();
+                }
+                INTERFACE_DESCRIPTION_BLOCK => {
+                    let link_type = u16_at(0)?;
+                    let mut resolution = Resolution(1_000_000);
+                    for (code, value) in options(body.get(8..).unwrap_or_default(), big_endian) {
+                        if code == IF_TSRESOL && !value.is_empty() {
+                            let exponent = (value[0] & 0x7f) as u32;
+                            let base: u64 = if value[0] & 0x80 != 0 { 2 } else { 10 };
+                            resolution = Resolution(base.checked_pow(exponent).unwrap_or(1));
+                        }
+                    }
+                    interfaces.push((link_type, resolution));
+                }
+                ENHANCED_PACKET_BLOCK => {
+                    let interface = u32_at(0)? as usize;
+                    let &(link_type, resolution) = interfaces
+                        .get(interface)
+                        .ok_or_else(|| invalid_data("unknown pcapng interface"))?;
+                    let units = (u32_at(4)? as u64) << 32 | u32_at(8)? as u64;
+                    let len = u32_at(12)? as usize;
+                    let data = body
+                        .get(20..20 + len)
+                        .ok_or_else(|| invalid_data("truncated pcapng packet"))?
+                        .to_vec();
+                    let mut direction = None;
+                    for (code, value) in options(&body[align(20 + len)..], big_endian) {
+                        if code == EPB_FLAGS && value.len() == 4 {
+                            direction = match read_u32(value, 0, big_endian)? & 0b11 {
+                                0b01 => Some(Direction::Inbound),
+                                0b10 => Some(Direction::Outbound),
+                                _ => None,
+                            };
+                        }
+                    }
+                    self.last_timestamp = resolution.duration(units);
+                    return Ok(Some(Record {
+                        timestamp: self.last_timestamp,
+                        direction,
+                        link_type,
+                        data,
+                    }));
+                }
+                SIMPLE_PACKET_BLOCK => {
+                    let &(link_type, _) = interfaces
+                        .first()
+                        .ok_or_else(|| invalid_data("unknown pcapng interface"))?;
+                    let len = (u32_at(0)? as usize).min(body.len() - 4);
+                    return Ok(Some(Record {
+                        timestamp: self.last_timestamp,
+                        direction: None,
+                        link_type,
+                        data: body[4..4 + len].to_vec(),
+                    }));
+                }
+                // Statistics, name resolution and the other blocks.
+                _ => {}
+            }
+        }
+    }
+
+    fn read_vec(&mut self, len: usize) -> std::io::Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        (&mut self.input).take(len as u64).read_to_end(&mut buf)?;
+        if buf.len() != len {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        Ok(buf)
+    }
+
+    fn discard(&mut self, len: usize) -> std::io::Result<()> {
+        self.read_vec(len).map(|_| ())
+    }
+
+    fn read_u32(&mut self) -> std::io::Result<u32> {
+        self.read_u32_or_eof()?
+            .ok_or_else(|| std::io::ErrorKind::UnexpectedEof.into())
+    }
+
+    /// Read a `u32`, or `None` at the end of the input.
+    fn read_u32_or_eof(&mut self) -> std::io::Result<Option<u32>> {
+        let buf = self.read_vec_or_eof(4)?;
+        buf.map(|buf| read_u32(&buf, 0, self.big_endian))
+            .transpose()
+    }
+
+    fn read_vec_or_eof(&mut self, len: usize) -> std::io::Result<Option<Vec<u8>>> {
+        let mut buf = Vec::new();
+        (&mut self.input).take(len as u64).read_to_end(&mut buf)?;
+        match buf.len() {
+            0 => Ok(None),
+            n if n == len => Ok(Some(buf)),
+            _ => Err(std::io::ErrorKind::UnexpectedEof.into()),
+        }
+    }
+}
+
+impl<R: Read> Iterator for Replayer<R> {
+    type Item = std::io::Result<Record>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_record().transpose()
+    }
+}
+
+fn read_u16(buf: &[u8], offset: usize, big_endian: bool) -> std::io::Result<u16> {
+    let bytes = buf
+        .get(offset..offset + 2)
+        .ok_or_else(|| invalid_data("truncated pcapng block"))?;
+    let bytes = [bytes[0], bytes[1]];
+    Ok(if big_endian {
+        u16::from_be_bytes(bytes)
+    } else {
+        u16::from_le_bytes(bytes)
+    })
+}
+
+fn read_u32(buf: &[u8], offset: usize, big_endian: bool) -> std::io::Result<u32> {
+    let bytes = buf
+        .get(offset..offset + 4)
+        .ok_or_else(|| invalid_data("truncated pcapng block"))?;
+    let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+    Ok(if big_endian {
+        u32::from_be_bytes(bytes)
+    } else {
+        u32::from_le_bytes(bytes)
+    })
+}
+
+/// Iterate over the options of a pcapng block, until `opt_endofopt`.
+fn options(mut buf: &[u8], big_endian: bool) -> impl Iterator<Item = (u16, &[u8])> {
+    std::iter::from_fn(move || {
+        let code = read_u16(buf, 0, big_endian).ok()?;
+        let len = read_u16(buf, 2, big_endian).ok()? as usize;
+        let value = buf.get(4..4 + len)?;
+        if code == OPT_ENDOFOPT {
+            return None;
+        }
+        buf = buf.get(4 + align(len)..).unwrap_or_default();
+        Some((code, value))
+    })
+}
+
+#[test]
+fn test_capture_replay() {
+    use std::collections::VecDeque;
+
+    let packet = vec![
+        0x45, 0, 0, 20, 0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 2,
+    ];
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+    let out = Shared::default();
+    let mut writer = Capture::new(VecDeque::new(), out.clone(), Layer::L3).unwrap();
+    let mut reader = writer.attach(VecDeque::from(packet.clone()));
+    writer.write_all(&packet).unwrap();
+    assert_eq!(writer.get_ref(), &packet);
+    let mut buf = [0; 64];
+    let len = reader.read(&mut buf).unwrap();
+    assert_eq!(&buf[..len], &packet[..]);
+    let file = out.0.lock().unwrap().clone();
+
+    let records = Replayer::new(&file[..])
+        .unwrap()
+        .collect::<std::io::Result<Vec<_>>>()
+        .unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0].direction, Some(Direction::Inbound));
+    assert_eq!(records[1].direction, Some(Direction::Outbound));
+    assert!(records
+        .iter()
+        .all(|r| r.link_type == LINKTYPE_RAW && r.data == packet));
+    assert!(records[0].timestamp <= records[1].timestamp);
+
+    // The packets read from the device are not fed back into it.
+    let mut device = Vec::new();
+    let written = Replayer::new(&file[..])
+        .unwrap()
+        .speed(f64::INFINITY)
+        .replay(&mut device)
+        .unwrap();
+    assert_eq!(written, 1);
+    assert_eq!(device, packet);
+
+    // A pcap file, in big endian with nanosecond timestamps, replayed at twice the speed.
+    let mut pcap = Vec::new();
+    for field in [
+        0xa1b2_3c4d_u32,
+        0x0002_0004,
+        0,
+        0,
+        65535,
+        LINKTYPE_RAW as u32,
+    ] {
+        pcap.extend_from_slice(&field.to_be_bytes());
+    }
+    for ts in [0, 100_000_000] {
+        for field in [1, ts, packet.len() as u32, packet.len() as u32] {
+            pcap.extend_from_slice(&field.to_be_bytes());
+        }
+        pcap.extend_from_slice(&packet);
+    }
+    let mut device = Vec::new();
+    let start = Instant::now();
+    let written = Replayer::new(&pcap[..])
+        .unwrap()
+        .speed(2.0)
+        .replay(&mut device)
+        .unwrap();
+    assert_eq!(written, 2);
+    assert!(start.elapsed() >= Duration::from_millis(50));
+    assert_eq!(device.len(), 2 * packet.len());
+
+    // Negative or tiny speeds do not wait, and out of range timestamps are rejected.
+    for speed in [-1.0, f64::MIN_POSITIVE] {
+        let mut replayer = Replayer::new(&pcap[..]).unwrap();
+        assert_eq!(replayer.speed(speed).replay(&mut Vec::new()).unwrap(), 2);
+    }
+    pcap[..4].copy_from_slice(&0xa1b2_c3d4_u32.to_be_bytes());
+    pcap[28..32].copy_from_slice(&u32::MAX.to_be_bytes());
+    let err = Replayer::new(&pcap[..]).unwrap().next_record().unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+}
@@ -30,6 +30,7 @@ use crate::{
     error::{Error, Result},
     platform::freebsd::sys::*,
     platform::posix::{self, sockaddr_union, Fd, Tun},
+    route::{add_alias_command, route_command, Cidr},
     run_command::run_command,
 };
 
@@ -250,6 +251,20 @@ impl Device {
     pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
         self.tun.send(buf)
     }
+
+    /// Recv as many packets as available from tun device without blocking, after the first one
+    pub fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        self.tun.recv_many(bufs, sizes)
+    }
+
+    /// Send packets to tun device until it would block
+    pub fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        self.tun.send_many(packets)
+    }
 }
 
 impl Read for Device {
@@ -353,6 +368,27 @@ impl AbstractDevice for Device {
         Ok(())
     }
 
+    fn add_address(&mut self, value: IpAddr, prefix_len: u8) -> Result<()> {
+        add_alias_command(&self.tun_name, value, prefix_len)
+    }
+
+    fn add_route(
+        &mut self,
+        destination: Cidr,
+        gateway: Option<IpAddr>,
+        _metric: u32,
+    ) -> Result<()> {
+        route_command("add", &self.tun_name, destination, gateway)
+    }
+
+    fn delete_route(&mut self, destination: Cidr, gateway: Option<IpAddr>) -> Result<()> {
+        route_command("delete", &self.tun_name, destination, gateway)
+    }
+
+    fn routes(&self) -> Result<Vec<crate::route::Route>> {
+        posix::list_routes(&self.tun_name)
+    }
+
     fn destination(&self) -> Result<IpAddr> {
         unsafe {
             let mut req = self.request();
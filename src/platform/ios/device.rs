@@ -80,6 +80,20 @@ impl Device {
     pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
         self.tun.send(buf)
     }
+
+    /// Recv as many packets as available from tun device without blocking, after the first one
+    pub fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        self.tun.recv_many(bufs, sizes)
+    }
+
+    /// Send packets to tun device until it would block
+    pub fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        self.tun.send_many(packets)
+    }
 }
 
 impl Read for Device {
@@ -13,26 +13,42 @@
 //  0. You just DO WHAT THE FUCK YOU WANT TO.
 
 use libc::{
-    self, c_char, c_short, ifreq, AF_INET, IFF_MULTI_QUEUE, IFF_NAPI, IFF_NO_PI, IFF_RUNNING,
-    IFF_TAP, IFF_TUN, IFF_UP, IFF_VNET_HDR, IFNAMSIZ, O_RDWR, SOCK_DGRAM,
+    self, c_char, c_int, c_short, ifreq, sock_fprog, AF_INET, ARPHRD_ETHER, IFF_ATTACH_QUEUE,
+    IFF_DETACH_QUEUE, IFF_MULTI_QUEUE, IFF_NAPI, IFF_NO_PI, IFF_PERSIST, IFF_RUNNING, IFF_TAP,
+    IFF_TUN, IFF_UP, IFF_VNET_HDR, IFNAMSIZ, O_RDWR, SOCK_DGRAM, TUN_F_CSUM, TUN_F_TSO4,
+    TUN_F_TSO6, TUN_F_USO4, TUN_F_USO6,
 };
 use std::{
     ffi::{CStr, CString},
     io::{Read, Write},
     mem,
     net::IpAddr,
+    ops::{Deref, DerefMut},
     os::unix::io::{AsRawFd, IntoRawFd, RawFd},
+    path::Path,
     ptr,
+    sync::{Arc, Mutex, MutexGuard},
 };
 
 use crate::{
     configuration::{Configuration, Layer},
     device::AbstractDevice,
     error::{Error, Result},
+    platform::linux::info::{supported_flags, sysfs_stats},
+    platform::linux::netlink::{
+        max_prefix, netmask_to_prefix, prefix_to_netmask, Address, Netlink,
+    },
+    platform::linux::netns::{self, NetnsGuard},
     platform::linux::sys::*,
-    platform::posix::{self, ipaddr_to_sockaddr, sockaddr_union, Fd, Tun},
+    platform::linux::{Filter, InterfaceStats},
+    platform::posix::{self, ipaddr_to_sockaddr, sockaddr_union, Fd, IoStats, Tun},
+    route::{Cidr, Route},
+    virtio::VirtioNetHdr,
 };
 
+#[cfg(feature = "async")]
+use crate::platform::linux::events::LinkMonitor;
+
 const OVERWRITE_SIZE: usize = std::mem::size_of::<libc::__c_anonymous_ifr_ifru>();
 
 /// A TUN device using the TUN/TAP Linux driver.
@@ -40,6 +56,30 @@ pub struct Device {
     tun_name: String,
     tun: Tun,
     ctl: Fd,
+    /// The network namespace of the interface, when set by `Configuration::netns`
+    /// or `move_to_netns`.
+    netns: Option<Arc<Fd>>,
+    /// The offloads set with `set_offload`.
+    offload: u32,
+    /// The rtnetlink socket, opened on first use in the network namespace of the interface.
+    netlink: Mutex<Option<Netlink>>,
+}
+
+/// The rtnetlink socket of a device, locked for the duration of a request.
+struct NetlinkGuard<'a>(MutexGuard<'a, Option<Netlink>>);
+
+impl Deref for NetlinkGuard<'_> {
+    type Target = Netlink;
+
+    fn deref(&self) -> &Netlink {
+        self.0.as_ref().unwrap()
+    }
+}
+
+impl DerefMut for NetlinkGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Netlink {
+        self.0.as_mut().unwrap()
+    }
 }
 
 impl AsRef<dyn AbstractDevice + 'static> for Device {
@@ -57,6 +97,13 @@ impl AsMut<dyn AbstractDevice + 'static> for Device {
 impl Device {
     /// Create a new `Device` for the given `Configuration`.
     pub fn new(config: &Configuration) -> Result<Self> {
+        let netns = config.netns.as_deref().map(netns::open).transpose()?;
+        let netns = netns.map(Arc::new);
+        let _netns = netns
+            .as_ref()
+            .map(|fd| NetnsGuard::enter(fd.inner))
+            .transpose()?;
+
         if let Some(fd) = config.raw_fd {
             let close_fd_on_drop = config.close_fd_on_drop.unwrap_or(true);
             let tun_fd = Fd::new(fd, close_fd_on_drop)?;
@@ -64,17 +111,117 @@ impl Device {
             let packet_information = config.platform_config.packet_information;
             let tun_name = config.tun_name.clone().unwrap_or_else(|| "".into());
             let ctl = Fd::new(unsafe { libc::socket(AF_INET, SOCK_DGRAM, 0) }, true)?;
-            return Ok(Device {
+            let mut device = Device {
                 tun: Tun::new(tun_fd, mtu, packet_information),
                 tun_name,
                 ctl,
-            });
+                netns,
+                offload: 0,
+                netlink: Mutex::new(None),
+            };
+            device.tun.set_l2(config.layer == Some(Layer::L2));
+            if config.platform_config.vnet_hdr {
+                device.enable_vnet_hdr(config.platform_config.offload)?;
+            }
+            return Ok(device);
+        }
+
+        let queues_num = config.queues.unwrap_or(1);
+        if queues_num != 1 {
+            return Err(Error::InvalidQueuesNumber);
+        }
+
+        let mut device = Self::open(config, config.tun_name.as_deref(), false)?;
+        device.netns = netns;
+
+        if config.platform_config.ensure_root_privileges {
+            device.configure(config)?;
+        }
+
+        Ok(device)
+    }
+
+    /// Create one `Device` per queue of a multi-queue interface, the number of
+    /// queues is taken from `Configuration::queues`.
+    ///
+    /// Every returned `Device` owns its own file descriptor, the kernel spreads the
+    /// traffic of the interface over all the attached queues.
+    pub fn new_queues(config: &Configuration) -> Result<Vec<Self>> {
+        if config.raw_fd.is_some() {
+            return Err(Error::InvalidConfig);
+        }
+
+        let queues_num = config.queues.unwrap_or(1);
+        if queues_num == 0 {
+            return Err(Error::InvalidQueuesNumber);
+        }
+
+        let netns = config.netns.as_deref().map(netns::open).transpose()?;
+        let netns = netns.map(Arc::new);
+        let _netns = netns
+            .as_ref()
+            .map(|fd| NetnsGuard::enter(fd.inner))
+            .transpose()?;
+
+        let mut devices = Vec::with_capacity(queues_num);
+        devices.push(Self::open(config, config.tun_name.as_deref(), true)?);
+
+        // The remaining queues must be attached to the interface created by the first one.
+        let tun_name = devices[0].tun_name.clone();
+        for _ in 1..queues_num {
+            devices.push(Self::open(config, Some(&tun_name), true)?);
+        }
+        for device in &mut devices {
+            device.netns = netns.clone();
+        }
+
+        if config.platform_config.ensure_root_privileges {
+            devices[0].configure(config)?;
         }
 
-        let mut device = unsafe {
-            let dev_name = match config.tun_name.as_ref() {
+        Ok(devices)
+    }
+
+    /// Create a `Device` on one end of a `SOCK_SEQPACKET` socketpair, the other end being the
+    /// `Wire` standing for the kernel side of the interface, which requires no privileges.
+    ///
+    /// The packets keep the packet information and virtio header enabled by `config` on the wire.
+    ///
+    /// [Note: The interface configuration is not backed by the kernel, `configure` and
+    /// the accessors of `AbstractDevice` fail. -- end note]
+    #[cfg(feature = "testing")]
+    pub fn socketpair(config: &Configuration) -> Result<(Self, crate::Wire)> {
+        let mut fds = [0; 2];
+        let ty = libc::SOCK_SEQPACKET | libc::SOCK_CLOEXEC;
+        if unsafe { libc::socketpair(libc::AF_UNIX, ty, 0, fds.as_mut_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let tun_fd = Fd::new(fds[0], true)?;
+        let wire = crate::Wire::new(Fd::new(fds[1], true)?);
+
+        let mtu = config.mtu.unwrap_or(crate::DEFAULT_MTU);
+        let packet_information = config.platform_config.packet_information;
+        let mut device = Device {
+            tun_name: config.tun_name.clone().unwrap_or_else(|| "".into()),
+            tun: Tun::new(tun_fd, mtu, packet_information),
+            ctl: Fd::new(unsafe { libc::socket(AF_INET, SOCK_DGRAM, 0) }, true)?,
+            netns: None,
+            offload: 0,
+            netlink: Mutex::new(None),
+        };
+        device.tun.set_l2(config.layer == Some(Layer::L2));
+        if config.platform_config.vnet_hdr {
+            device.tun.set_vnet_hdr_len(VirtioNetHdr::LEN);
+        }
+        Ok((device, wire))
+    }
+
+    /// Open `/dev/net/tun` and attach it to the interface with the given name.
+    fn open(config: &Configuration, tun_name: Option<&str>, multi_queue: bool) -> Result<Self> {
+        unsafe {
+            let dev_name = match tun_name {
                 Some(tun_name) => {
-                    let tun_name = CString::new(tun_name.clone())?;
+                    let tun_name = CString::new(tun_name)?;
 
                     if tun_name.as_bytes_with_nul().len() > IFNAMSIZ {
                         return Err(Error::NameTooLong);
@@ -96,13 +243,13 @@ impl Device {
                 );
             }
 
-            let device_type: c_short = config.layer.unwrap_or(Layer::L3).into();
-
-            let queues_num = config.queues.unwrap_or(1);
-            if queues_num != 1 {
-                return Err(Error::InvalidQueuesNumber);
+            // Without this check, attaching to a missing interface would create it.
+            if config.platform_config.open_existing {
+                if_index(tun_name.ok_or(Error::InvalidConfig)?)?;
             }
 
+            let device_type: c_short = config.layer.unwrap_or(Layer::L3).into();
+
             let iff_no_pi = IFF_NO_PI as c_short;
             let iff_multi_queue = IFF_MULTI_QUEUE as c_short;
             let iff_napi = IFF_NAPI as c_short;
@@ -114,12 +261,27 @@ impl Device {
                 | if packet_information { 0 } else { iff_no_pi }
                 | if napi { iff_napi } else { 0 }
                 | if vnet_hdr { iff_vnet_hdr } else { 0 }
-                | if queues_num > 1 { iff_multi_queue } else { 0 };
+                | if multi_queue { iff_multi_queue } else { 0 };
 
             let tun_fd = {
-                let fd = libc::open(b"/dev/net/tun\0".as_ptr() as *const _, O_RDWR);
+                let fd = libc::open(c"/dev/net/tun".as_ptr(), O_RDWR);
                 let tun_fd = Fd::new(fd, true).map_err(|_| std::io::Error::last_os_error())?;
 
+                // Fail with the missing feature rather than an obscure error of TUNSETIFF,
+                // the kernels without TUNGETFEATURES are left to it.
+                if let Ok(supported) = supported_flags(tun_fd.inner) {
+                    let flags = req.ifr_ifru.ifru_flags as c_int;
+                    for (flag, name) in [
+                        (IFF_NAPI, "IFF_NAPI"),
+                        (IFF_VNET_HDR, "IFF_VNET_HDR"),
+                        (IFF_MULTI_QUEUE, "IFF_MULTI_QUEUE"),
+                    ] {
+                        if flags & flag != 0 && supported & flag == 0 {
+                            return Err(Error::UnsupportedFeature(name));
+                        }
+                    }
+                }
+
                 if let Err(err) = tunsetiff(tun_fd.inner, &mut req as *mut _ as *mut _) {
                     return Err(std::io::Error::from(err).into());
                 }
@@ -134,18 +296,41 @@ impl Device {
             let tun_name = CStr::from_ptr(req.ifr_name.as_ptr())
                 .to_string_lossy()
                 .to_string();
-            Device {
+            let mut device = Device {
                 tun_name,
                 tun: Tun::new(tun_fd, mtu, packet_information),
                 ctl,
+                netns: None,
+                offload: 0,
+                netlink: Mutex::new(None),
+            };
+            device.tun.set_l2(config.layer == Some(Layer::L2));
+            if vnet_hdr {
+                device.enable_vnet_hdr(config.platform_config.offload)?;
             }
-        };
-
-        if config.platform_config.ensure_root_privileges {
-            device.configure(config)?;
+            Ok(device)
         }
+    }
 
-        Ok(device)
+    /// Expect the default virtio header in front of every packet, which tun2
+    /// always handles in little-endian byte order, and enable the TCP offloads.
+    fn enable_vnet_hdr(&mut self, offload: bool) -> Result<()> {
+        if cfg!(target_endian = "big") {
+            unsafe {
+                if let Err(err) = tunsetvnetle(self.as_raw_fd(), &1) {
+                    return Err(std::io::Error::from(err).into());
+                }
+            }
+        }
+        self.tun.set_vnet_hdr_len(VirtioNetHdr::LEN);
+        if offload {
+            let tso = TUN_F_CSUM | TUN_F_TSO4 | TUN_F_TSO6;
+            // USO is only supported since Linux 6.2, the datagrams are then segmented in userspace.
+            if self.set_offload(tso | TUN_F_USO4 | TUN_F_USO6).is_err() {
+                self.set_offload(tso)?;
+            }
+        }
+        Ok(())
     }
 
     /// Prepare a new request.
@@ -160,10 +345,149 @@ impl Device {
         req
     }
 
-    /// Make the device persistent.
+    /// Get the index of the interface, in its network namespace.
+    fn if_index(&self) -> Result<u32> {
+        unsafe {
+            let mut req = self.request();
+            if let Err(err) = siocgifindex(self.ctl.as_raw_fd(), &mut req) {
+                return Err(std::io::Error::from(err).into());
+            }
+            Ok(req.ifr_ifru.ifru_ifindex as u32)
+        }
+    }
+
+    /// Get the rtnetlink socket of the interface, opening it in its network namespace if needed.
+    fn netlink(&self) -> Option<NetlinkGuard<'_>> {
+        let mut netlink = self.netlink.lock().unwrap();
+        if netlink.is_none() {
+            let _netns = match self.netns.as_ref().map(|fd| NetnsGuard::enter(fd.inner)) {
+                Some(Err(err)) => {
+                    log::debug!("cannot enter the network namespace of the interface: {err}");
+                    return None;
+                }
+                netns => netns,
+            };
+            *netlink = Some(Netlink::open()?);
+        }
+        Some(NetlinkGuard(netlink))
+    }
+
+    /// Get the first address of the interface, of the family of `family` if given,
+    /// otherwise preferring IPv4 like the ioctl interface does.
+    fn primary_address(&self, netlink: &mut Netlink, family: Option<IpAddr>) -> Result<Address> {
+        let addresses = netlink.addresses(self.if_index()?)?;
+        let ipv6 = family.map(|addr| addr.is_ipv6());
+        addresses
+            .iter()
+            .find(|address| address.local.is_ipv6() == ipv6.unwrap_or(false))
+            .or_else(|| addresses.first().filter(|_| ipv6.is_none()))
+            .copied()
+            .ok_or_else(no_address)
+    }
+
+    /// Replace the first address of the family of `value` with the one built by `f`,
+    /// which receives the current address if any.
+    ///
+    /// The new address is added before the current one is deleted, so that the interface is
+    /// never left without an address when the kernel refuses it.
+    fn update_address<F>(&self, netlink: &mut Netlink, value: IpAddr, f: F) -> Result<()>
+    where
+        F: FnOnce(Option<Address>) -> Result<Address>,
+    {
+        let index = self.if_index()?;
+        let current = self.primary_address(netlink, Some(value)).ok();
+        let address = f(current)?;
+        let Some(current) = current else {
+            return netlink.add_address(index, &address);
+        };
+        if current == address {
+            return Ok(());
+        }
+
+        if current.matches(&address) {
+            // Replacing it would keep the broadcast or prefix, so the current address is deleted
+            // first and restored if the new one is refused.
+            netlink.del_address(index, &current)?;
+            return netlink.add_address(index, &address).inspect_err(|_| {
+                let _ = netlink.add_address(index, &current);
+            });
+        }
+
+        netlink.add_address(index, &address)?;
+        netlink.del_address(index, &current)?;
+        // Unless promote_secondaries is set, deleting a primary IPv4 address deletes the other
+        // addresses of its subnet too, which the new one may be.
+        if !netlink.addresses(index)?.contains(&address) {
+            netlink.add_address(index, &address)?;
+        }
+        Ok(())
+    }
+
+    /// Get the `IFF_*` flags of the interface as read back from the kernel, such as
+    /// `libc::IFF_TAP`, `libc::IFF_NO_PI`, `libc::IFF_VNET_HDR` or `libc::IFF_PERSIST`.
+    pub fn flags(&self) -> Result<c_int> {
+        unsafe {
+            let mut req: ifreq = mem::zeroed();
+            if let Err(err) = tungetiff(self.as_raw_fd(), &mut req as *mut _ as *mut _) {
+                return Err(std::io::Error::from(err).into());
+            }
+            Ok(req.ifr_ifru.ifru_flags as u16 as c_int)
+        }
+    }
+
+    /// Move the interface to the network namespace of the file `path`, such as
+    /// `/var/run/netns/NAME` or `/proc/PID/ns/net`.
+    ///
+    /// The interface is brought down and loses its addresses and routes, the device keeps
+    /// exchanging its packets and configures it in the new namespace.
+    ///
+    /// [Note: The other queues of a multi-queue interface keep configuring it in the
+    /// previous namespace. -- end note]
+    pub fn move_to_netns<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let netns = netns::open(path.as_ref())?;
+        let index = self.if_index()?;
+        let mut netlink = self.netlink().ok_or(Error::NotImplemented)?;
+        netlink.set_link_netns(index, netns.inner)?;
+        drop(netlink);
+
+        let _netns = NetnsGuard::enter(netns.inner)?;
+        self.ctl = Fd::new(unsafe { libc::socket(AF_INET, SOCK_DGRAM, 0) }, true)?;
+        self.netns = Some(Arc::new(netns));
+        // The socket was opened in the previous namespace, open another one in the new one.
+        *self.netlink.get_mut().unwrap() = None;
+        Ok(())
+    }
+
+    /// Get the statistics of the interface kept by the kernel, from netlink or else sysfs.
+    pub fn stats(&self) -> Result<InterfaceStats> {
+        if let Some(mut netlink) = self.netlink() {
+            if let Some(stats) = netlink.link(self.if_index()?)?.stats {
+                return Ok(stats.into());
+            }
+        }
+        sysfs_stats(&self.tun_name)
+    }
+
+    /// Get the in-process counters of the packets read from and written to the device so far.
+    pub fn io_stats(&self) -> (IoStats, IoStats) {
+        self.tun.stats()
+    }
+
+    /// Make the device persistent, the interface outlives the process and can be attached
+    /// again with the `open_existing` platform option.
     pub fn persist(&mut self) -> Result<()> {
+        self.set_persist(true)
+    }
+
+    /// Make the device no longer persistent, the interface is deleted once all the file
+    /// descriptors attached to it are closed.
+    pub fn unpersist(&mut self) -> Result<()> {
+        self.set_persist(false)
+    }
+
+    fn set_persist(&mut self, value: bool) -> Result<()> {
         unsafe {
-            if let Err(err) = tunsetpersist(self.as_raw_fd(), &1) {
+            if let Err(err) = tunsetpersist(self.as_raw_fd(), value as _) {
                 Err(std::io::Error::from(err).into())
             } else {
                 Ok(())
@@ -174,7 +498,7 @@ impl Device {
     /// Set the owner of the device.
     pub fn user(&mut self, value: i32) -> Result<()> {
         unsafe {
-            if let Err(err) = tunsetowner(self.as_raw_fd(), &value) {
+            if let Err(err) = tunsetowner(self.as_raw_fd(), value as _) {
                 Err(std::io::Error::from(err).into())
             } else {
                 Ok(())
@@ -185,7 +509,7 @@ impl Device {
     /// Set the group of the device.
     pub fn group(&mut self, value: i32) -> Result<()> {
         unsafe {
-            if let Err(err) = tunsetgroup(self.as_raw_fd(), &value) {
+            if let Err(err) = tunsetgroup(self.as_raw_fd(), value as _) {
                 Err(std::io::Error::from(err).into())
             } else {
                 Ok(())
@@ -193,6 +517,164 @@ impl Device {
         }
     }
 
+    /// Attach this queue to its multi-queue interface again after
+    /// [`detach_queue`](Device::detach_queue), so it receives packets.
+    pub fn attach_queue(&self) -> Result<()> {
+        self.set_queue(IFF_ATTACH_QUEUE)
+    }
+
+    /// Detach this queue from its multi-queue interface, the kernel stops
+    /// delivering packets to it until it is attached again.
+    pub fn detach_queue(&self) -> Result<()> {
+        self.set_queue(IFF_DETACH_QUEUE)
+    }
+
+    fn set_queue(&self, flag: c_int) -> Result<()> {
+        unsafe {
+            let mut req: ifreq = mem::zeroed();
+            req.ifr_ifru.ifru_flags = flag as c_short;
+            if let Err(err) = tunsetqueue(self.as_raw_fd(), &req as *const _ as *const _) {
+                Err(std::io::Error::from(err).into())
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    /// Attach the classic BPF `filter` to the interface, the frames it drops are not delivered.
+    ///
+    /// [Note: The kernel only supports it for `Layer::L2` devices, use
+    /// [`attach_filter_ebpf`](Device::attach_filter_ebpf) otherwise. -- end note]
+    pub fn attach_filter(&self, filter: &Filter) -> Result<()> {
+        if !self.tun.writer.l2 {
+            return Err(Error::UnsupportedLayer);
+        }
+        let mut program = filter.compile()?;
+        let prog = sock_fprog {
+            len: program.len() as u16,
+            filter: program.as_mut_ptr(),
+        };
+        unsafe {
+            if let Err(err) = tunattachfilter(self.as_raw_fd(), &prog) {
+                return Err(std::io::Error::from(err).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Detach the classic BPF filter of the interface.
+    pub fn detach_filter(&self) -> Result<()> {
+        if !self.tun.writer.l2 {
+            return Err(Error::UnsupportedLayer);
+        }
+        unsafe {
+            let prog: sock_fprog = mem::zeroed();
+            if let Err(err) = tundetachfilter(self.as_raw_fd(), &prog) {
+                return Err(std::io::Error::from(err).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Attach the loaded eBPF program `prog_fd`, of type `BPF_PROG_TYPE_SOCKET_FILTER`, choosing
+    /// the queue of every packet sent to the interface, in place of the kernel flow hashing.
+    ///
+    /// The program returns the index of the queue, modulo the number of queues.
+    /// [Note: The program applies to the whole interface, and `prog_fd` may be closed once attached. -- end note]
+    pub fn attach_steering_ebpf(&self, prog_fd: RawFd) -> Result<()> {
+        self.set_ebpf(tunsetsteeringebpf, prog_fd)
+    }
+
+    /// Detach the steering program, the queues are chosen by the kernel again.
+    pub fn detach_steering_ebpf(&self) -> Result<()> {
+        self.set_ebpf(tunsetsteeringebpf, -1)
+    }
+
+    /// Attach the loaded eBPF program `prog_fd`, of type `BPF_PROG_TYPE_SOCKET_FILTER`, filtering
+    /// the packets sent to the interface before they are queued.
+    ///
+    /// The program returns the length of the packet to keep, `0` dropping it.
+    /// [Note: The program applies to the whole interface, and `prog_fd` may be closed once attached. -- end note]
+    pub fn attach_filter_ebpf(&self, prog_fd: RawFd) -> Result<()> {
+        self.set_ebpf(tunsetfilterebpf, prog_fd)
+    }
+
+    /// Detach the filter program.
+    pub fn detach_filter_ebpf(&self) -> Result<()> {
+        self.set_ebpf(tunsetfilterebpf, -1)
+    }
+
+    fn set_ebpf(
+        &self,
+        ioctl: unsafe fn(c_int, *mut c_int) -> nix::Result<c_int>,
+        mut prog_fd: RawFd,
+    ) -> Result<()> {
+        unsafe {
+            if let Err(err) = ioctl(self.as_raw_fd(), &mut prog_fd) {
+                return Err(std::io::Error::from(err).into());
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the length of the virtio header in front of every packet, either
+    /// [`VirtioNetHdr::LEN`] or [`VirtioNetHdr::MRG_RXBUF_LEN`].
+    ///
+    /// [Note: The device must have been created with `vnet_hdr` enabled. -- end note]
+    pub fn set_vnet_hdr_len(&mut self, value: usize) -> Result<()> {
+        if self.tun.reader.vnet_hdr_len == 0 {
+            return Err(Error::InvalidConfig);
+        }
+        if value != VirtioNetHdr::LEN && value != VirtioNetHdr::MRG_RXBUF_LEN {
+            return Err(Error::InvalidConfig);
+        }
+        unsafe {
+            if let Err(err) = tunsetvnethdrsz(self.as_raw_fd(), &(value as c_int)) {
+                return Err(std::io::Error::from(err).into());
+            }
+        }
+        self.tun.set_vnet_hdr_len(value);
+        Ok(())
+    }
+
+    /// Set the offloads the application is able to handle, a combination of the
+    /// `libc::TUN_F_*` flags, such as `TUN_F_CSUM | TUN_F_TSO4`.
+    ///
+    /// [Note: Offloads other than `0` require the virtio header to be enabled. -- end note]
+    pub fn set_offload(&mut self, flags: u32) -> Result<()> {
+        unsafe {
+            if let Err(err) = tunsetoffload(self.as_raw_fd(), flags as _) {
+                return Err(std::io::Error::from(err).into());
+            }
+        }
+        let uso = TUN_F_USO4 | TUN_F_USO6;
+        self.tun.writer.uso = flags & uso == uso;
+        self.offload = flags;
+        Ok(())
+    }
+
+    /// Get the offloads set with `set_offload`, the segmentation ones bring super-packets
+    /// of up to 64 KiB.
+    pub fn offload(&self) -> u32 {
+        self.offload
+    }
+
+    /// Subscribe to the link and address notifications of the interface, in its network namespace.
+    #[cfg(feature = "async")]
+    pub(crate) fn link_monitor(&self) -> Result<LinkMonitor> {
+        let _netns = self
+            .netns
+            .as_ref()
+            .map(|fd| NetnsGuard::enter(fd.inner))
+            .transpose()?;
+        LinkMonitor::new(self.if_index()?)
+    }
+
+    #[cfg(any(feature = "io-uring", feature = "async"))]
+    pub(crate) fn tun(&self) -> &Tun {
+        &self.tun
+    }
+
     /// Split the interface into a `Reader` and `Writer`.
     pub fn split(self) -> (posix::Reader, posix::Writer) {
         (self.tun.reader, self.tun.writer)
@@ -212,6 +694,56 @@ impl Device {
     pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
         self.tun.send(buf)
     }
+
+    /// Recv as many packets as available from tun device without blocking, after the first one
+    pub fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        self.tun.recv_many(bufs, sizes)
+    }
+
+    /// Send packets to tun device until it would block
+    pub fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        self.tun.send_many(packets)
+    }
+
+    /// Recv a packet and its virtio header from tun device
+    pub fn recv_with_hdr(&self, buf: &mut [u8]) -> std::io::Result<(VirtioNetHdr, usize)> {
+        self.tun.recv_with_hdr(buf)
+    }
+
+    /// Send a packet and its virtio header to tun device
+    pub fn send_with_hdr(&self, hdr: &VirtioNetHdr, buf: &[u8]) -> std::io::Result<usize> {
+        self.tun.send_with_hdr(hdr, buf)
+    }
+
+    /// Recv a packet from tun device and split it into segments if it is a super-packet,
+    /// see [`gso_split`](crate::gso_split)
+    pub fn recv_segments(
+        &self,
+        buf: &mut [u8],
+        segments: &mut Vec<Vec<u8>>,
+    ) -> std::io::Result<usize> {
+        self.tun.recv_segments(buf, segments)
+    }
+
+    /// Send a batch of packets to tun device, coalescing the TCP segments and, with USO,
+    /// the UDP datagrams of a flow, see [`gro_coalesce`](crate::gro_coalesce)
+    pub fn send_coalesced(&self, packets: &[&[u8]]) -> std::io::Result<usize> {
+        self.tun.send_coalesced(packets)
+    }
+
+    /// Send a super-packet to tun device, segmenting UDP ones in userspace without USO
+    pub fn send_gso(&self, hdr: &VirtioNetHdr, packet: &[u8]) -> std::io::Result<usize> {
+        self.tun.send_gso(hdr, packet)
+    }
+
+    /// Whether the device accepts UDP super-packets, that is the USO offloads are enabled.
+    pub fn uso(&self) -> bool {
+        self.tun.writer.uso
+    }
 }
 
 impl Read for Device {
@@ -244,31 +776,39 @@ impl AbstractDevice for Device {
     }
 
     fn set_tun_name(&mut self, value: &str) -> Result<()> {
-        unsafe {
-            let tun_name = CString::new(value)?;
+        let tun_name = CString::new(value)?;
 
-            if tun_name.as_bytes_with_nul().len() > IFNAMSIZ {
-                return Err(Error::NameTooLong);
-            }
+        if tun_name.as_bytes_with_nul().len() > IFNAMSIZ {
+            return Err(Error::NameTooLong);
+        }
 
-            let mut req = self.request();
-            ptr::copy_nonoverlapping(
-                tun_name.as_ptr() as *const c_char,
-                req.ifr_ifru.ifru_newname.as_mut_ptr(),
-                value.len(),
-            );
+        if let Some(mut netlink) = self.netlink() {
+            netlink.set_link_name(self.if_index()?, value)?;
+        } else {
+            unsafe {
+                let mut req = self.request();
+                ptr::copy_nonoverlapping(
+                    tun_name.as_ptr() as *const c_char,
+                    req.ifr_ifru.ifru_newname.as_mut_ptr(),
+                    value.len(),
+                );
 
-            if let Err(err) = siocsifname(self.ctl.as_raw_fd(), &req) {
-                return Err(std::io::Error::from(err).into());
+                if let Err(err) = siocsifname(self.ctl.as_raw_fd(), &req) {
+                    return Err(std::io::Error::from(err).into());
+                }
             }
+        }
 
-            self.tun_name = value.into();
+        self.tun_name = value.into();
 
-            Ok(())
-        }
+        Ok(())
     }
 
     fn enabled(&mut self, value: bool) -> Result<()> {
+        if let Some(mut netlink) = self.netlink() {
+            return netlink.set_link_up(self.if_index()?, value);
+        }
+
         unsafe {
             let mut req = self.request();
 
@@ -291,6 +831,10 @@ impl AbstractDevice for Device {
     }
 
     fn address(&self) -> Result<IpAddr> {
+        if let Some(mut netlink) = self.netlink() {
+            return Ok(self.primary_address(&mut netlink, None)?.local);
+        }
+
         unsafe {
             let mut req = self.request();
             if let Err(err) = siocgifaddr(self.ctl.as_raw_fd(), &mut req) {
@@ -302,6 +846,16 @@ impl AbstractDevice for Device {
     }
 
     fn set_address(&mut self, value: IpAddr) -> Result<()> {
+        if let Some(mut netlink) = self.netlink() {
+            return self.update_address(&mut netlink, value, |address| match address {
+                Some(address) => Ok(Address {
+                    local: value,
+                    ..address
+                }),
+                None => Ok(Address::new(value)),
+            });
+        }
+
         unsafe {
             let mut req = self.request();
             ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_addr, OVERWRITE_SIZE);
@@ -312,7 +866,69 @@ impl AbstractDevice for Device {
         }
     }
 
+    fn addresses(&self) -> Result<Vec<(IpAddr, u8)>> {
+        let mut netlink = self.netlink().ok_or(Error::NotImplemented)?;
+        let addresses = netlink.addresses(self.if_index()?)?;
+        Ok(addresses
+            .into_iter()
+            .map(|address| (address.local, address.prefix_len))
+            .collect())
+    }
+
+    fn add_address(&mut self, value: IpAddr, prefix_len: u8) -> Result<()> {
+        if prefix_len > max_prefix(value) {
+            return Err(Error::InvalidAddress);
+        }
+        let mut netlink = self.netlink().ok_or(Error::NotImplemented)?;
+        let address = Address {
+            prefix_len,
+            ..Address::new(value)
+        };
+        netlink.add_address(self.if_index()?, &address)
+    }
+
+    fn remove_address(&mut self, value: IpAddr) -> Result<()> {
+        let mut netlink = self.netlink().ok_or(Error::NotImplemented)?;
+        let index = self.if_index()?;
+        let address = netlink
+            .addresses(index)?
+            .into_iter()
+            .find(|address| address.local == value)
+            .ok_or_else(no_address)?;
+        netlink.del_address(index, &address)
+    }
+
+    fn add_route(&mut self, destination: Cidr, gateway: Option<IpAddr>, metric: u32) -> Result<()> {
+        let mut netlink = self.netlink().ok_or(Error::NotImplemented)?;
+        let route = Route {
+            destination,
+            gateway,
+            metric,
+        };
+        netlink.add_route(self.if_index()?, &route)
+    }
+
+    fn delete_route(&mut self, destination: Cidr, gateway: Option<IpAddr>) -> Result<()> {
+        let mut netlink = self.netlink().ok_or(Error::NotImplemented)?;
+        let route = Route {
+            destination,
+            gateway,
+            metric: 0,
+        };
+        netlink.del_route(self.if_index()?, &route)
+    }
+
+    fn routes(&self) -> Result<Vec<Route>> {
+        let mut netlink = self.netlink().ok_or(Error::NotImplemented)?;
+        netlink.routes(self.if_index()?)
+    }
+
     fn destination(&self) -> Result<IpAddr> {
+        if let Some(mut netlink) = self.netlink() {
+            let address = self.primary_address(&mut netlink, None)?;
+            return Ok(address.peer.unwrap_or(address.local));
+        }
+
         unsafe {
             let mut req = self.request();
             if let Err(err) = siocgifdstaddr(self.ctl.as_raw_fd(), &mut req) {
@@ -324,6 +940,15 @@ impl AbstractDevice for Device {
     }
 
     fn set_destination(&mut self, value: IpAddr) -> Result<()> {
+        if let Some(mut netlink) = self.netlink() {
+            return self.update_address(&mut netlink, value, |address| {
+                Ok(Address {
+                    peer: Some(value),
+                    ..address.ok_or_else(no_address)?
+                })
+            });
+        }
+
         unsafe {
             let mut req = self.request();
             ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_dstaddr, OVERWRITE_SIZE);
@@ -335,6 +960,12 @@ impl AbstractDevice for Device {
     }
 
     fn broadcast(&self) -> Result<IpAddr> {
+        if let Some(mut netlink) = self.netlink() {
+            let address = self.primary_address(&mut netlink, None)?;
+            let unspecified = prefix_to_netmask(address.local, 0);
+            return Ok(address.broadcast.unwrap_or(unspecified));
+        }
+
         unsafe {
             let mut req = self.request();
             if let Err(err) = siocgifbrdaddr(self.ctl.as_raw_fd(), &mut req) {
@@ -346,6 +977,15 @@ impl AbstractDevice for Device {
     }
 
     fn set_broadcast(&mut self, value: IpAddr) -> Result<()> {
+        if let Some(mut netlink) = self.netlink() {
+            return self.update_address(&mut netlink, value, |address| {
+                Ok(Address {
+                    broadcast: Some(value),
+                    ..address.ok_or_else(no_address)?
+                })
+            });
+        }
+
         unsafe {
             let mut req = self.request();
             ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_broadaddr, OVERWRITE_SIZE);
@@ -357,6 +997,11 @@ impl AbstractDevice for Device {
     }
 
     fn netmask(&self) -> Result<IpAddr> {
+        if let Some(mut netlink) = self.netlink() {
+            let address = self.primary_address(&mut netlink, None)?;
+            return Ok(prefix_to_netmask(address.local, address.prefix_len));
+        }
+
         unsafe {
             let mut req = self.request();
             if let Err(err) = siocgifnetmask(self.ctl.as_raw_fd(), &mut req) {
@@ -368,6 +1013,16 @@ impl AbstractDevice for Device {
     }
 
     fn set_netmask(&mut self, value: IpAddr) -> Result<()> {
+        if let Some(mut netlink) = self.netlink() {
+            let prefix_len = netmask_to_prefix(value)?;
+            return self.update_address(&mut netlink, value, |address| {
+                Ok(Address {
+                    prefix_len,
+                    ..address.ok_or_else(no_address)?
+                })
+            });
+        }
+
         unsafe {
             let mut req = self.request();
             ipaddr_to_sockaddr(value, 0, &mut req.ifr_ifru.ifru_netmask, OVERWRITE_SIZE);
@@ -379,6 +1034,11 @@ impl AbstractDevice for Device {
     }
 
     fn mtu(&self) -> Result<u16> {
+        if let Some(mut netlink) = self.netlink() {
+            let link = netlink.link(self.if_index()?)?;
+            return link.mtu.try_into().map_err(|_| Error::TryFromIntError);
+        }
+
         unsafe {
             let mut req = self.request();
 
@@ -394,23 +1054,119 @@ impl AbstractDevice for Device {
     }
 
     fn set_mtu(&mut self, value: u16) -> Result<()> {
+        if let Some(mut netlink) = self.netlink() {
+            netlink.set_link_mtu(self.if_index()?, value as u32)?;
+        } else {
+            unsafe {
+                let mut req = self.request();
+                req.ifr_ifru.ifru_mtu = value as i32;
+
+                if let Err(err) = siocsifmtu(self.ctl.as_raw_fd(), &req) {
+                    return Err(std::io::Error::from(err).into());
+                }
+            }
+        }
+        self.tun.set_mtu(value);
+        Ok(())
+    }
+
+    fn mac_address(&self) -> Result<[u8; 6]> {
         unsafe {
             let mut req = self.request();
-            req.ifr_ifru.ifru_mtu = value as i32;
 
-            if let Err(err) = siocsifmtu(self.ctl.as_raw_fd(), &req) {
+            if let Err(err) = siocgifhwaddr(self.ctl.as_raw_fd(), &mut req) {
                 return Err(std::io::Error::from(err).into());
             }
-            self.tun.set_mtu(value);
-            Ok(())
+
+            let hwaddr = req.ifr_ifru.ifru_hwaddr;
+            if hwaddr.sa_family != ARPHRD_ETHER {
+                return Err(Error::UnsupportedLayer);
+            }
+            Ok(std::array::from_fn(|i| hwaddr.sa_data[i] as u8))
         }
     }
 
+    fn set_mac_address(&mut self, value: [u8; 6]) -> Result<()> {
+        unsafe {
+            let mut req = self.request();
+            req.ifr_ifru.ifru_hwaddr.sa_family = ARPHRD_ETHER;
+            for (dst, src) in req.ifr_ifru.ifru_hwaddr.sa_data.iter_mut().zip(value) {
+                *dst = src as c_char;
+            }
+
+            if let Err(err) = siocsifhwaddr(self.ctl.as_raw_fd(), &req) {
+                return Err(std::io::Error::from(err).into());
+            }
+        }
+        Ok(())
+    }
+
     fn packet_information(&self) -> bool {
         self.tun.packet_information()
     }
 }
 
+/// Get the index of the interface `name`.
+fn if_index(name: &str) -> Result<u32> {
+    let name = CString::new(name)?;
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(std::io::Error::last_os_error().into()),
+        index => Ok(index),
+    }
+}
+
+/// Get the `IFF_*` flags of the TUN/TAP interface `name`, from netlink or else sysfs.
+fn tun_flags(name: &str) -> Result<c_short> {
+    let index = if_index(name)?;
+    if let Some(mut netlink) = Netlink::open() {
+        if let Some(tun) = netlink.link(index)?.tun {
+            return Ok(tun.flags);
+        }
+    }
+    let flags = std::fs::read_to_string(format!("/sys/class/net/{name}/tun_flags"))?;
+    Ok(u16::from_str_radix(flags.trim().trim_start_matches("0x"), 16)? as c_short)
+}
+
+/// Delete the persistent interface `name`.
+///
+/// This fails with `EBUSY` while a file descriptor is still attached to a single-queue interface,
+/// whereas a multi-queue interface is deleted once the file descriptors of its queues are closed.
+///
+/// [Note: Like attaching to the interface, this requires being its owner or `CAP_NET_ADMIN`. -- end note]
+pub fn delete_persistent(name: &str) -> Result<()> {
+    let flags = tun_flags(name)?;
+    let tun_name = CString::new(name)?;
+    if tun_name.as_bytes_with_nul().len() > IFNAMSIZ {
+        return Err(Error::NameTooLong);
+    }
+
+    unsafe {
+        let mut req: ifreq = mem::zeroed();
+        ptr::copy_nonoverlapping(
+            tun_name.as_ptr() as *const c_char,
+            req.ifr_name.as_mut_ptr(),
+            tun_name.as_bytes_with_nul().len(),
+        );
+        // Attaching with the current flags keeps the other file descriptors working.
+        req.ifr_ifru.ifru_flags = flags & !(IFF_PERSIST as c_short);
+
+        let fd = libc::open(c"/dev/net/tun".as_ptr(), O_RDWR);
+        let tun_fd = Fd::new(fd, true).map_err(|_| std::io::Error::last_os_error())?;
+        if let Err(err) = tunsetiff(tun_fd.inner, &mut req as *mut _ as *mut _) {
+            return Err(std::io::Error::from(err).into());
+        }
+        if let Err(err) = tunsetpersist(tun_fd.inner, 0) {
+            return Err(std::io::Error::from(err).into());
+        }
+    }
+    Ok(())
+}
+
+/// The error reported by the kernel when the interface has no address to modify.
+fn no_address() -> Error {
+    std::io::Error::from_raw_os_error(libc::EADDRNOTAVAIL).into()
+}
+
 impl AsRawFd for Device {
     fn as_raw_fd(&self) -> RawFd {
         self.tun.as_raw_fd()
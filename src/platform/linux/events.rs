@@ -0,0 +1,135 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Notifications of the changes of an interface.
+
+use crate::error::{Error, Result};
+use crate::platform::linux::netlink::{Address, Link, Netlink};
+use libc::{
+    IFF_UP, RTMGRP_IPV4_IFADDR, RTMGRP_IPV6_IFADDR, RTMGRP_LINK, RTM_DELADDR, RTM_DELLINK,
+    RTM_NEWADDR, RTM_NEWLINK,
+};
+use std::collections::VecDeque;
+use std::net::IpAddr;
+use std::os::unix::io::{AsRawFd, RawFd};
+
+/// A change of an interface, as notified by the kernel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum LinkEvent {
+    /// The interface was brought up.
+    LinkUp,
+    /// The interface was brought down.
+    LinkDown,
+    /// The MTU of the interface changed to the given value.
+    MtuChanged(u32),
+    /// An address with the given prefix length was added to the interface.
+    AddressAdded(IpAddr, u8),
+    /// An address with the given prefix length was removed from the interface.
+    AddressRemoved(IpAddr, u8),
+    /// The interface was deleted, or moved to another network namespace.
+    Deleted,
+}
+
+/// A subscription to the link and address notifications of one interface.
+pub(crate) struct LinkMonitor {
+    netlink: Netlink,
+    index: u32,
+    up: bool,
+    mtu: u32,
+    deleted: bool,
+}
+
+impl LinkMonitor {
+    /// Subscribe to the notifications of the interface `index`, in the network
+    /// namespace of the calling thread.
+    pub fn new(index: u32) -> Result<Self> {
+        let groups = RTMGRP_LINK | RTMGRP_IPV4_IFADDR | RTMGRP_IPV6_IFADDR;
+        let netlink = Netlink::subscribe(groups as u32)?;
+        // Queried once subscribed, so that no change is missed in between.
+        let link = Netlink::open().ok_or(Error::NotImplemented)?.link(index)?;
+        Ok(LinkMonitor {
+            netlink,
+            index,
+            up: link.flags & IFF_UP as u32 != 0,
+            mtu: link.mtu,
+            deleted: false,
+        })
+    }
+
+    /// Whether the interface is gone, after which there are no more events.
+    pub fn is_deleted(&self) -> bool {
+        self.deleted
+    }
+
+    /// Receive the pending notifications, appending the events of the interface to `events`.
+    pub fn recv(&mut self, events: &mut VecDeque<LinkEvent>) -> std::io::Result<()> {
+        for notification in self.netlink.notifications()? {
+            if self.deleted {
+                break;
+            }
+            match notification.ty {
+                RTM_NEWLINK => {
+                    let Some(link) = Link::parse(&notification) else {
+                        continue;
+                    };
+                    if link.index != self.index {
+                        continue;
+                    }
+                    // Every change of the link is notified with all its properties.
+                    let up = link.flags & IFF_UP as u32 != 0;
+                    if up != self.up {
+                        self.up = up;
+                        events.push_back(if up {
+                            LinkEvent::LinkUp
+                        } else {
+                            LinkEvent::LinkDown
+                        });
+                    }
+                    if link.mtu != self.mtu {
+                        self.mtu = link.mtu;
+                        events.push_back(LinkEvent::MtuChanged(link.mtu));
+                    }
+                }
+                RTM_DELLINK
+                    if Link::parse(&notification).is_some_and(|link| link.index == self.index) =>
+                {
+                    self.deleted = true;
+                    events.push_back(LinkEvent::Deleted);
+                }
+                ty @ (RTM_NEWADDR | RTM_DELADDR) => {
+                    let Some((index, address)) = Address::parse(&notification) else {
+                        continue;
+                    };
+                    if index != self.index {
+                        continue;
+                    }
+                    let (local, prefix_len) = (address.local, address.prefix_len);
+                    events.push_back(if ty == RTM_NEWADDR {
+                        LinkEvent::AddressAdded(local, prefix_len)
+                    } else {
+                        LinkEvent::AddressRemoved(local, prefix_len)
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+impl AsRawFd for LinkMonitor {
+    fn as_raw_fd(&self) -> RawFd {
+        self.netlink.as_raw_fd()
+    }
+}
@@ -0,0 +1,555 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Classic BPF filters of the frames delivered by TAP devices.
+
+use crate::error::{Error, Result};
+use crate::route::Cidr;
+use libc::{
+    sock_filter, BPF_ABS, BPF_ALU, BPF_AND, BPF_B, BPF_H, BPF_IND, BPF_JEQ, BPF_JMP, BPF_JSET,
+    BPF_K, BPF_LD, BPF_LDX, BPF_MSH, BPF_RET, BPF_W,
+};
+use std::net::IpAddr;
+
+const ETHER_HEADER_LEN: u32 = 14;
+const ETHERTYPE_OFFSET: u32 = 12;
+const IPV6_HEADER_LEN: u32 = 40;
+
+const IPPROTO_TCP: u8 = 6;
+const IPPROTO_UDP: u8 = 17;
+const IPPROTO_SCTP: u8 = 132;
+const IPPROTO_ICMPV6: u8 = 58;
+
+/// The IPv4 fragment offset bits, only the first fragment has the ports.
+const IPV4_FRAGMENT_OFFSET: u32 = 0x1fff;
+
+/// The length to keep of the accepted frames, all of it.
+const ACCEPT: u32 = u32::MAX;
+const DROP: u32 = 0;
+
+/// The largest program the kernel accepts.
+const BPF_MAXINSNS: usize = 4096;
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum EtherType {
+    Ipv4 = 0x0800,
+    Arp = 0x0806,
+    Ipv6 = 0x86dd,
+}
+
+/// A rule of a [`Filter`], matching the frames satisfying all of its conditions.
+///
+/// The IP version is inferred from the addresses when not set, a rule on the protocol or the
+/// ports only matching both IPv4 and IPv6 packets, and a rule without any IP condition matching
+/// the frames of every Ethernet type.
+#[derive(Clone, Debug, Default)]
+pub struct FilterRule {
+    destination_mac: Option<[u8; 6]>,
+    multicast: bool,
+    ethertype: Option<EtherType>,
+    protocol: Option<u8>,
+    source: Option<Cidr>,
+    destination: Option<Cidr>,
+    source_port: Option<u16>,
+    destination_port: Option<u16>,
+    icmpv6_type: Option<u8>,
+}
+
+impl FilterRule {
+    /// Create a rule matching every frame.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match the frames sent to the MAC address `value`.
+    pub fn destination_mac(&mut self, value: [u8; 6]) -> &mut Self {
+        self.destination_mac = Some(value);
+        self
+    }
+
+    /// Match the frames sent to a multicast MAC address, the broadcasts included.
+    pub fn multicast(&mut self) -> &mut Self {
+        self.multicast = true;
+        self
+    }
+
+    /// Match the IPv4 packets.
+    pub fn ipv4(&mut self) -> &mut Self {
+        self.ethertype = Some(EtherType::Ipv4);
+        self
+    }
+
+    /// Match the IPv6 packets.
+    pub fn ipv6(&mut self) -> &mut Self {
+        self.ethertype = Some(EtherType::Ipv6);
+        self
+    }
+
+    /// Match the ARP packets, which cannot have any IP condition.
+    pub fn arp(&mut self) -> &mut Self {
+        self.ethertype = Some(EtherType::Arp);
+        self
+    }
+
+    /// Match the IP packets of the transport protocol `value`, such as `libc::IPPROTO_ICMP`.
+    ///
+    /// [Note: The IPv6 extension headers are not skipped, the protocol is the next header. -- end note]
+    pub fn protocol(&mut self, value: u8) -> &mut Self {
+        self.protocol = Some(value);
+        self
+    }
+
+    /// Match the TCP packets.
+    pub fn tcp(&mut self) -> &mut Self {
+        self.protocol(IPPROTO_TCP)
+    }
+
+    /// Match the UDP packets.
+    pub fn udp(&mut self) -> &mut Self {
+        self.protocol(IPPROTO_UDP)
+    }
+
+    /// Match the packets sent from the network `value`.
+    pub fn source(&mut self, value: Cidr) -> &mut Self {
+        self.source = Some(value);
+        self
+    }
+
+    /// Match the packets sent to the network `value`.
+    pub fn destination(&mut self, value: Cidr) -> &mut Self {
+        self.destination = Some(value);
+        self
+    }
+
+    /// Match the ICMPv6 messages of type `value`, such as 133 for the router solicitations.
+    ///
+    /// [Note: The IPv6 extension headers are not skipped, the message follows the fixed header. -- end note]
+    pub fn icmpv6_type(&mut self, value: u8) -> &mut Self {
+        self.protocol = Some(IPPROTO_ICMPV6);
+        self.icmpv6_type = Some(value);
+        self
+    }
+
+    /// Match the packets sent from the port `value`, of TCP, UDP or SCTP unless the protocol is set.
+    ///
+    /// [Note: The IPv6 extension headers are not skipped, the ports follow the fixed header. -- end note]
+    pub fn source_port(&mut self, value: u16) -> &mut Self {
+        self.source_port = Some(value);
+        self
+    }
+
+    /// Match the packets sent to the port `value`, of TCP, UDP or SCTP unless the protocol is set.
+    ///
+    /// [Note: The IPv6 extension headers are not skipped, the ports follow the fixed header. -- end note]
+    pub fn destination_port(&mut self, value: u16) -> &mut Self {
+        self.destination_port = Some(value);
+        self
+    }
+
+    /// Whether the rule has conditions on the IP packets.
+    fn has_ip_conditions(&self) -> bool {
+        self.protocol.is_some()
+            || self.source.is_some()
+            || self.destination.is_some()
+            || self.source_port.is_some()
+            || self.destination_port.is_some()
+    }
+
+    /// The Ethernet types of the frames matched by the rule, `None` for all of them.
+    fn ethertypes(&self) -> Result<Vec<Option<EtherType>>> {
+        let mut families = [self.source, self.destination]
+            .into_iter()
+            .flatten()
+            .map(|cidr| match cidr.addr() {
+                IpAddr::V4(_) => EtherType::Ipv4,
+                IpAddr::V6(_) => EtherType::Ipv6,
+            })
+            .chain(self.icmpv6_type.map(|_| EtherType::Ipv6));
+        let family = families.next();
+        if families.any(|other| Some(other) != family) {
+            return Err(Error::InvalidConfig);
+        }
+        match (self.ethertype, family) {
+            (Some(ethertype), Some(family)) if ethertype != family => Err(Error::InvalidConfig),
+            (Some(ethertype), _) => Ok(vec![Some(ethertype)]),
+            (None, Some(family)) => Ok(vec![Some(family)]),
+            (None, None) if self.has_ip_conditions() => {
+                Ok(vec![Some(EtherType::Ipv4), Some(EtherType::Ipv6)])
+            }
+            (None, None) => Ok(vec![None]),
+        }
+    }
+
+    /// Emit the rule, returning `ret` for the frames it matches.
+    fn compile(&self, ret: u32, program: &mut Program) -> Result<()> {
+        for ethertype in self.ethertypes()? {
+            program.block(ret, |program| self.compile_block(ethertype, program))?;
+        }
+        Ok(())
+    }
+
+    /// Emit the checks of the rule on the frames of `ethertype`, or of every type.
+    fn compile_block(&self, ethertype: Option<EtherType>, program: &mut Program) -> Result<()> {
+        if let Some(mac) = self.destination_mac {
+            program.load(BPF_W, 0);
+            program.require(u32::from_be_bytes([mac[0], mac[1], mac[2], mac[3]]));
+            program.load(BPF_H, 4);
+            program.require(u16::from_be_bytes([mac[4], mac[5]]) as u32);
+        }
+        if self.multicast {
+            program.load(BPF_B, 0);
+            program.require_set(0x01);
+        }
+        let Some(ethertype) = ethertype else {
+            return Ok(());
+        };
+
+        program.load(BPF_H, ETHERTYPE_OFFSET);
+        program.require(ethertype as u32);
+
+        let ip = ETHER_HEADER_LEN;
+        let (protocol, source, destination) = match ethertype {
+            EtherType::Arp if self.has_ip_conditions() => return Err(Error::InvalidConfig),
+            EtherType::Arp => return Ok(()),
+            EtherType::Ipv4 => (ip + 9, ip + 12, ip + 16),
+            EtherType::Ipv6 => (ip + 6, ip + 8, ip + 24),
+        };
+
+        let ports = self.source_port.is_some() || self.destination_port.is_some();
+        if self.icmpv6_type.is_some() && (ports || self.protocol != Some(IPPROTO_ICMPV6)) {
+            return Err(Error::InvalidConfig);
+        }
+        if let Some(value) = self.protocol {
+            program.load(BPF_B, protocol);
+            program.require(value as u32);
+        } else if ports {
+            program.load(BPF_B, protocol);
+            program.jump(BPF_JEQ, IPPROTO_TCP as u32, 2, 0);
+            program.jump(BPF_JEQ, IPPROTO_UDP as u32, 1, 0);
+            program.require(IPPROTO_SCTP as u32);
+        }
+
+        for (cidr, offset) in [(self.source, source), (self.destination, destination)] {
+            if let Some(cidr) = cidr {
+                program.require_network(cidr, offset);
+            }
+        }
+
+        if ports {
+            let transport = if ethertype == EtherType::Ipv4 {
+                program.load(BPF_H, ip + 6);
+                program.reject_set(IPV4_FRAGMENT_OFFSET);
+                program.stmt(BPF_LDX | BPF_B | BPF_MSH, ip);
+                BPF_IND
+            } else {
+                BPF_ABS
+            };
+            let ports = [(self.source_port, 0), (self.destination_port, 2)];
+            for (port, offset) in ports {
+                if let Some(port) = port {
+                    let offset = if transport == BPF_IND {
+                        ip + offset
+                    } else {
+                        ip + IPV6_HEADER_LEN + offset
+                    };
+                    program.stmt(BPF_LD | BPF_H | transport, offset);
+                    program.require(port as u32);
+                }
+            }
+        }
+
+        if let Some(value) = self.icmpv6_type {
+            program.load(BPF_B, ip + IPV6_HEADER_LEN);
+            program.require(value as u32);
+        }
+        Ok(())
+    }
+}
+
+/// A classic BPF program accepting or dropping the frames as decided by the first of its rules
+/// matching them, and dropping the frames matched by none unless told otherwise.
+///
+/// ```no_run
+/// use tun2::{Filter, FilterRule};
+///
+/// // Only IPv4 TCP to port 80, and ARP.
+/// let mut filter = Filter::new();
+/// filter
+///     .rule(FilterRule::new().ipv4().tcp().destination_port(80))
+///     .rule(FilterRule::new().arp());
+///
+/// // Everything but the IPv6 router solicitations and the multicasts.
+/// let mut filter = Filter::new();
+/// filter
+///     .drop_rule(FilterRule::new().icmpv6_type(133))
+///     .drop_rule(FilterRule::new().multicast())
+///     .accept_others();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Filter {
+    rules: Vec<(FilterRule, u32)>,
+    others: u32,
+}
+
+impl Default for Filter {
+    fn default() -> Self {
+        Self {
+            rules: Vec::new(),
+            others: DROP,
+        }
+    }
+}
+
+impl Filter {
+    /// Create a filter dropping every frame.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Accept the frames matched by `rule`, unless an earlier rule dropped them.
+    pub fn rule(&mut self, rule: &FilterRule) -> &mut Self {
+        self.rules.push((rule.clone(), ACCEPT));
+        self
+    }
+
+    /// Drop the frames matched by `rule`, unless an earlier rule accepted them.
+    pub fn drop_rule(&mut self, rule: &FilterRule) -> &mut Self {
+        self.rules.push((rule.clone(), DROP));
+        self
+    }
+
+    /// Accept the frames matched by none of the rules, instead of dropping them.
+    pub fn accept_others(&mut self) -> &mut Self {
+        self.others = ACCEPT;
+        self
+    }
+
+    /// Compile the filter into classic BPF instructions.
+    pub fn compile(&self) -> Result<Vec<sock_filter>> {
+        let mut program = Program::default();
+        for (rule, ret) in &self.rules {
+            rule.compile(*ret, &mut program)?;
+        }
+        program.stmt(BPF_RET | BPF_K, self.others);
+        if program.insns.len() > BPF_MAXINSNS {
+            return Err(Error::InvalidConfig);
+        }
+        Ok(program.insns)
+    }
+}
+
+/// A program being built, made of blocks deciding the fate of the frame when none of their checks fails.
+#[derive(Default)]
+struct Program {
+    insns: Vec<sock_filter>,
+    /// The jumps of the current block to patch to its end, and whether taken when true.
+    failures: Vec<(usize, bool)>,
+}
+
+impl Program {
+    fn stmt(&mut self, code: u32, k: u32) {
+        self.insns.push(sock_filter {
+            code: code as u16,
+            jt: 0,
+            jf: 0,
+            k,
+        });
+    }
+
+    fn jump(&mut self, op: u32, k: u32, jt: u8, jf: u8) {
+        self.insns.push(sock_filter {
+            code: (BPF_JMP | op | BPF_K) as u16,
+            jt,
+            jf,
+            k,
+        });
+    }
+
+    /// Load the word of `size` at `offset` into the accumulator.
+    fn load(&mut self, size: u32, offset: u32) {
+        self.stmt(BPF_LD | size | BPF_ABS, offset);
+    }
+
+    /// Fail the block unless the accumulator is `k`.
+    fn require(&mut self, k: u32) {
+        self.failures.push((self.insns.len(), false));
+        self.jump(BPF_JEQ, k, 0, 0);
+    }
+
+    /// Fail the block unless a bit of `k` is set in the accumulator.
+    fn require_set(&mut self, k: u32) {
+        self.failures.push((self.insns.len(), false));
+        self.jump(BPF_JSET, k, 0, 0);
+    }
+
+    /// Fail the block if any bit of `k` is set in the accumulator.
+    fn reject_set(&mut self, k: u32) {
+        self.failures.push((self.insns.len(), true));
+        self.jump(BPF_JSET, k, 0, 0);
+    }
+
+    /// Fail the block unless the address at `offset` is in `cidr`.
+    fn require_network(&mut self, cidr: Cidr, offset: u32) {
+        let prefix_len = cidr.prefix_len() as u32;
+        let words: Vec<u32> = match cidr.addr() {
+            IpAddr::V4(addr) => vec![addr.into()],
+            IpAddr::V6(addr) => addr
+                .octets()
+                .chunks_exact(4)
+                .map(|word| u32::from_be_bytes(word.try_into().unwrap()))
+                .collect(),
+        };
+        for (i, word) in words.into_iter().enumerate() {
+            let bits = prefix_len.saturating_sub(i as u32 * 32).min(32);
+            if bits == 0 {
+                break;
+            }
+            self.load(BPF_W, offset + i as u32 * 4);
+            if bits < 32 {
+                self.stmt(BPF_ALU | BPF_AND | BPF_K, u32::MAX << (32 - bits));
+            }
+            self.require(word);
+        }
+    }
+
+    /// Emit a block, returning `ret` if it does not fail, and going on with the next one otherwise.
+    fn block(&mut self, ret: u32, f: impl FnOnce(&mut Self) -> Result<()>) -> Result<()> {
+        self.failures.clear();
+        f(self)?;
+        self.stmt(BPF_RET | BPF_K, ret);
+        let end = self.insns.len();
+        for (index, taken) in self.failures.drain(..) {
+            let offset = u8::try_from(end - index - 1).map_err(|_| Error::InvalidConfig)?;
+            if taken {
+                self.insns[index].jt = offset;
+            } else {
+                self.insns[index].jf = offset;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_filter_compile() {
+    let mut filter = Filter::new();
+    assert_eq!(1, filter.compile().unwrap().len());
+
+    filter.rule(FilterRule::new().ipv4().tcp().destination_port(80));
+    let program = filter.compile().unwrap();
+    // ethertype, protocol, fragment, header length and port checks, accept, and drop.
+    assert_eq!(11, program.len());
+    assert_eq!(8, program[1].jf);
+    assert_eq!(4, program[5].jt);
+
+    let v6: Cidr = "fd00::/64".parse().unwrap();
+    let mut filter = Filter::new();
+    filter.rule(FilterRule::new().ipv4().destination(v6));
+    assert!(filter.compile().is_err());
+    let mut filter = Filter::new();
+    filter.rule(FilterRule::new().arp().udp());
+    assert!(filter.compile().is_err());
+    let mut filter = Filter::new();
+    filter.rule(FilterRule::new().udp());
+    // The ethertype and protocol checks and the accept, for both IPv4 and IPv6, and the drop.
+    assert_eq!(11, filter.compile().unwrap().len());
+}
+
+/// Run `program` on `frame` as the kernel does, returning the length to keep.
+#[cfg(test)]
+fn run(program: &[sock_filter], frame: &[u8]) -> u32 {
+    let (mut a, mut x, mut pc) = (0u32, 0u32, 0);
+    loop {
+        let insn = program[pc];
+        let (code, k) = (insn.code as u32, insn.k);
+        pc += 1;
+        match code & 0x07 {
+            BPF_LD | BPF_LDX => {
+                let size = match code & 0x18 {
+                    BPF_W => 4,
+                    BPF_H => 2,
+                    _ => 1,
+                };
+                let offset = if code & 0xe0 == BPF_IND { x + k } else { k } as usize;
+                let Some(bytes) = frame.get(offset..offset + size) else {
+                    return 0;
+                };
+                match code {
+                    c if c == BPF_LDX | BPF_B | BPF_MSH => x = (bytes[0] as u32 & 0x0f) * 4,
+                    _ => a = bytes.iter().fold(0, |word, &byte| word << 8 | byte as u32),
+                }
+            }
+            BPF_ALU => a &= k,
+            BPF_JMP => {
+                let taken = match code & 0xf0 {
+                    BPF_JSET => a & k != 0,
+                    _ => a == k,
+                };
+                pc += if taken { insn.jt } else { insn.jf } as usize;
+            }
+            _ => return k,
+        }
+    }
+}
+
+#[test]
+fn test_filter_run() {
+    // An Ethernet frame to `mac`, of IPv4 UDP to port 80 or of an IPv6 ICMP message of type 133.
+    let frame = |mac: [u8; 6], ipv6: bool| {
+        let mut frame = mac.to_vec();
+        frame.extend_from_slice(&[2, 0, 0, 0, 0, 1]);
+        if ipv6 {
+            frame.extend_from_slice(&[0x86, 0xdd, 0x60, 0, 0, 0, 0, 8, IPPROTO_ICMPV6, 255]);
+            frame.resize(frame.len() + 32, 0);
+            frame.extend_from_slice(&[133, 0, 0, 0, 0, 0, 0, 0]);
+        } else {
+            frame.extend_from_slice(&[0x08, 0x00, 0x45, 0, 0, 28, 0, 0, 0, 0, 64, IPPROTO_UDP]);
+            frame.resize(frame.len() + 10, 0);
+            frame.extend_from_slice(&[0x30, 0x39, 0, 80, 0, 8, 0, 0]);
+        }
+        frame
+    };
+    let unicast = [2, 0, 0, 0, 0, 2];
+    let broadcast = [0xff; 6];
+
+    let mut filter = Filter::new();
+    filter.rule(FilterRule::new().udp().destination_port(80));
+    let program = filter.compile().unwrap();
+    assert_eq!(ACCEPT, run(&program, &frame(unicast, false)));
+    assert_eq!(DROP, run(&program, &frame(unicast, true)));
+
+    let mut filter = Filter::new();
+    filter
+        .drop_rule(FilterRule::new().icmpv6_type(133))
+        .drop_rule(FilterRule::new().multicast())
+        .accept_others();
+    let program = filter.compile().unwrap();
+    assert_eq!(ACCEPT, run(&program, &frame(unicast, false)));
+    assert_eq!(DROP, run(&program, &frame(unicast, true)));
+    assert_eq!(DROP, run(&program, &frame(broadcast, false)));
+
+    // The earlier rule wins.
+    let mut filter = Filter::new();
+    filter
+        .rule(FilterRule::new().destination_mac(broadcast).udp())
+        .drop_rule(FilterRule::new().multicast())
+        .accept_others();
+    let program = filter.compile().unwrap();
+    assert_eq!(ACCEPT, run(&program, &frame(broadcast, false)));
+    assert_eq!(DROP, run(&program, &frame(broadcast, true)));
+
+    let mut filter = Filter::new();
+    filter.rule(FilterRule::new().icmpv6_type(133).tcp());
+    assert!(filter.compile().is_err());
+}
@@ -0,0 +1,246 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Inspection of the TUN/TAP driver and of its existing interfaces.
+
+use crate::configuration::Layer;
+use crate::error::Result;
+use crate::platform::linux::netlink::Netlink;
+use crate::platform::linux::sys::tungetfeatures;
+use crate::platform::posix::Fd;
+use libc::{
+    c_int, c_uint, IFF_MULTI_QUEUE, IFF_NO_PI, IFF_PERSIST, IFF_TAP, IFF_VNET_HDR, O_RDWR,
+    TUN_F_CSUM, TUN_F_TSO4, TUN_F_TSO6, TUN_F_TSO_ECN, TUN_F_USO4, TUN_F_USO6,
+};
+use std::os::unix::io::RawFd;
+use std::path::Path;
+
+/// The features of the TUN/TAP driver of the running kernel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Features {
+    /// The supported `IFF_*` flags, such as `libc::IFF_NAPI` or `libc::IFF_VNET_HDR`.
+    pub flags: c_int,
+    /// The supported offloads, a combination of the `libc::TUN_F_*` flags.
+    pub offloads: c_uint,
+}
+
+impl Features {
+    /// Whether all the `IFF_*` flags of `flags` are supported.
+    pub fn supports(&self, flags: c_int) -> bool {
+        self.flags & flags == flags
+    }
+}
+
+/// Query the features of the TUN/TAP driver, which requires no privileges.
+///
+/// The flags are reported by the kernel, the offloads are the ones of its version.
+pub fn features() -> Result<Features> {
+    let fd = unsafe { libc::open(c"/dev/net/tun".as_ptr(), O_RDWR) };
+    let fd = Fd::new(fd, true).map_err(|_| std::io::Error::last_os_error())?;
+    let flags = supported_flags(fd.inner)?;
+
+    let mut offloads = 0;
+    if flags & IFF_VNET_HDR != 0 {
+        offloads |= TUN_F_CSUM | TUN_F_TSO4 | TUN_F_TSO6 | TUN_F_TSO_ECN;
+        if kernel_version() >= (6, 2) {
+            offloads |= TUN_F_USO4 | TUN_F_USO6;
+        }
+    }
+    Ok(Features { flags, offloads })
+}
+
+/// Query the `IFF_*` flags supported by the driver, on a descriptor of `/dev/net/tun`.
+pub(crate) fn supported_flags(fd: RawFd) -> std::io::Result<c_int> {
+    let mut flags: c_uint = 0;
+    unsafe {
+        if let Err(err) = tungetfeatures(fd, &mut flags) {
+            return Err(err.into());
+        }
+    }
+    Ok(flags as c_int)
+}
+
+/// The major and minor version of the running kernel.
+fn kernel_version() -> (u32, u32) {
+    let mut uts: libc::utsname = unsafe { std::mem::zeroed() };
+    if unsafe { libc::uname(&mut uts) } != 0 {
+        return (0, 0);
+    }
+    let release = unsafe { std::ffi::CStr::from_ptr(uts.release.as_ptr()) }.to_string_lossy();
+    let mut numbers = release
+        .split(|c: char| !c.is_ascii_digit())
+        .map(|number| number.parse().unwrap_or(0));
+    (numbers.next().unwrap_or(0), numbers.next().unwrap_or(0))
+}
+
+/// The statistics of an interface kept by the kernel, from the point of view of the interface:
+/// the packets written to the device are received, the ones read from it are sent.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InterfaceStats {
+    pub rx_packets: u64,
+    pub tx_packets: u64,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_errors: u64,
+    pub tx_errors: u64,
+    /// The packets dropped on their way in, such as the invalid ones written to the device.
+    pub rx_dropped: u64,
+    /// The packets dropped on their way out, such as the ones not read from the device in time.
+    pub tx_dropped: u64,
+}
+
+impl From<[u64; 8]> for InterfaceStats {
+    fn from(counters: [u64; 8]) -> Self {
+        let [rx_packets, tx_packets, rx_bytes, tx_bytes, rx_errors, tx_errors, rx_dropped, tx_dropped] =
+            counters;
+        InterfaceStats {
+            rx_packets,
+            tx_packets,
+            rx_bytes,
+            tx_bytes,
+            rx_errors,
+            tx_errors,
+            rx_dropped,
+            tx_dropped,
+        }
+    }
+}
+
+/// Read the statistics of the interface `name` from the sysfs of the mount namespace.
+pub(crate) fn sysfs_stats(name: &str) -> Result<InterfaceStats> {
+    let path = Path::new("/sys/class/net").join(name).join("statistics");
+    let mut counters = [0; 8];
+    let names = [
+        "rx_packets",
+        "tx_packets",
+        "rx_bytes",
+        "tx_bytes",
+        "rx_errors",
+        "tx_errors",
+        "rx_dropped",
+        "tx_dropped",
+    ];
+    for (counter, name) in counters.iter_mut().zip(names) {
+        *counter = std::fs::read_to_string(path.join(name))?.trim().parse()?;
+    }
+    Ok(counters.into())
+}
+
+/// The properties of an existing TUN/TAP interface.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceInfo {
+    /// The interface name.
+    pub name: String,
+    /// The interface index.
+    pub index: u32,
+    /// `Layer::L3` for a TUN interface, `Layer::L2` for a TAP one.
+    pub layer: Layer,
+    /// Whether the interface has several queues.
+    pub multi_queue: bool,
+    /// Whether the packets carry a virtio header.
+    pub vnet_hdr: bool,
+    /// Whether the packets carry packet information, that is `IFF_NO_PI` is not set.
+    pub packet_information: bool,
+    /// The user allowed to attach to the interface, if restricted.
+    pub owner: Option<u32>,
+    /// The group allowed to attach to the interface, if restricted.
+    pub group: Option<u32>,
+    /// Whether the interface outlives the file descriptors attached to it.
+    pub persistent: bool,
+    /// The MTU.
+    pub mtu: u32,
+}
+
+impl DeviceInfo {
+    fn new(name: String, index: u32, flags: c_int, mtu: u32) -> Self {
+        DeviceInfo {
+            name,
+            index,
+            layer: if flags & IFF_TAP != 0 {
+                Layer::L2
+            } else {
+                Layer::L3
+            },
+            multi_queue: flags & IFF_MULTI_QUEUE != 0,
+            vnet_hdr: flags & IFF_VNET_HDR != 0,
+            packet_information: flags & IFF_NO_PI == 0,
+            owner: None,
+            group: None,
+            persistent: flags & IFF_PERSIST != 0,
+            mtu,
+        }
+    }
+}
+
+/// List the TUN/TAP interfaces of the network namespace, from netlink or else sysfs.
+///
+/// [Note: The sysfs of the mount namespace is only used when netlink is unavailable,
+/// or does not report the TUN/TAP properties, before Linux 4.15. -- end note]
+pub fn list_devices() -> Result<Vec<DeviceInfo>> {
+    let Some(mut netlink) = Netlink::open() else {
+        return list_sysfs_devices();
+    };
+    let mut devices = Vec::new();
+    for link in netlink.links()? {
+        if link.kind.as_deref() != Some("tun") {
+            continue;
+        }
+        let device = match link.tun {
+            Some(tun) => DeviceInfo {
+                owner: tun.owner,
+                group: tun.group,
+                ..DeviceInfo::new(link.name, link.index, tun.flags as c_int, link.mtu)
+            },
+            None => sysfs_device(&Path::new("/sys/class/net").join(&link.name))?,
+        };
+        devices.push(device);
+    }
+    Ok(devices)
+}
+
+fn list_sysfs_devices() -> Result<Vec<DeviceInfo>> {
+    let mut devices = Vec::new();
+    for entry in std::fs::read_dir("/sys/class/net")? {
+        let path = entry?.path();
+        if path.join("tun_flags").exists() {
+            devices.push(sysfs_device(&path)?);
+        }
+    }
+    Ok(devices)
+}
+
+/// Read the properties of the TUN/TAP interface of the sysfs directory `path`.
+fn sysfs_device(path: &Path) -> Result<DeviceInfo> {
+    let read = |file: &str| -> Result<String> {
+        Ok(std::fs::read_to_string(path.join(file))?.trim().to_string())
+    };
+    // An unrestricted owner or group reads as -1.
+    let id = |file: &str| -> Result<Option<u32>> {
+        let id: i64 = read(file)?.parse()?;
+        Ok(id.try_into().ok())
+    };
+
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let flags = c_int::from_str_radix(read("tun_flags")?.trim_start_matches("0x"), 16)?;
+    Ok(DeviceInfo {
+        owner: id("owner")?,
+        group: id("group")?,
+        ..DeviceInfo::new(
+            name.into_owned(),
+            read("ifindex")?.parse()?,
+            flags,
+            read("mtu")?.parse()?,
+        )
+    })
+}
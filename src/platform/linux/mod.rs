@@ -16,8 +16,26 @@
 
 mod sys;
 
+mod netlink;
+
+mod netns;
+
+#[cfg(feature = "async")]
+pub(crate) mod events;
+#[cfg(feature = "async")]
+pub use self::events::LinkEvent;
+
+#[cfg(feature = "io-uring")]
+pub(crate) mod uring;
+
+mod filter;
+pub use self::filter::{Filter, FilterRule};
+
+mod info;
+pub use self::info::{features, list_devices, DeviceInfo, Features, InterfaceStats};
+
 mod device;
-pub use self::device::Device;
+pub use self::device::{delete_persistent, Device};
 
 use crate::configuration::Configuration;
 use crate::error::Result;
@@ -35,6 +53,12 @@ pub struct PlatformConfig {
 
     /// Enable IFF_VNET_HDR
     pub(crate) vnet_hdr: bool,
+
+    /// Enable the TCP segmentation offload
+    pub(crate) offload: bool,
+
+    /// Only attach to an existing interface
+    pub(crate) open_existing: bool,
 }
 
 /// `packet_information` is default to be `false` and `ensure_root_privileges` is default to be `true`.
@@ -45,6 +69,8 @@ impl Default for PlatformConfig {
             ensure_root_privileges: true,
             napi: false,
             vnet_hdr: false,
+            offload: false,
+            open_existing: false,
         }
     }
 }
@@ -81,9 +107,36 @@ impl PlatformConfig {
         self.vnet_hdr = value;
         self
     }
+
+    /// Enable / Disable the checksum and TCP segmentation offloads, which implies IFF_VNET_HDR.
+    ///
+    /// The device then exchanges TCP super-packets of up to 64KiB, use `recv_segments` and
+    /// `send_coalesced` to split and build them.
+    pub fn offload(&mut self, value: bool) -> &mut Self {
+        self.offload = value;
+        if value {
+            self.vnet_hdr = true;
+        }
+        self
+    }
+
+    /// Only attach to the existing interface named by `tun_name`, such as a persistent one
+    /// created beforehand, failing with `ENODEV` when it does not exist instead of creating it.
+    ///
+    /// [Note: An unprivileged owner of the interface can attach to it, but not configure it,
+    /// which requires disabling `ensure_root_privileges`. -- end note]
+    pub fn open_existing(&mut self, value: bool) -> &mut Self {
+        self.open_existing = value;
+        self
+    }
 }
 
 /// Create a TUN device with the given name.
 pub fn create(configuration: &Configuration) -> Result<Device> {
     Device::new(configuration)
 }
+
+/// Create a multi-queue TUN device, returning one `Device` per queue.
+pub fn create_queues(configuration: &Configuration) -> Result<Vec<Device>> {
+    Device::new_queues(configuration)
+}
@@ -0,0 +1,742 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! A minimal rtnetlink client used to configure the interface.
+
+use libc::{
+    c_int, c_short, c_void, ifaddrmsg, ifinfomsg, nlmsghdr, rtattr, socklen_t, AF_INET, AF_INET6,
+    AF_NETLINK, AF_UNSPEC, IFA_ADDRESS, IFA_BROADCAST, IFA_LOCAL, IFF_MULTI_QUEUE, IFF_NO_PI,
+    IFF_PERSIST, IFF_UP, IFF_VNET_HDR, IFLA_IFNAME, IFLA_MTU, NETLINK_CAP_ACK, NETLINK_EXT_ACK,
+    NETLINK_ROUTE, NLA_TYPE_MASK, NLMSG_DONE, NLMSG_ERROR, NLM_F_ACK, NLM_F_ACK_TLVS, NLM_F_CAPPED,
+    NLM_F_CREATE, NLM_F_DUMP, NLM_F_EXCL, NLM_F_REPLACE, NLM_F_REQUEST, RTA_DST, RTA_GATEWAY,
+    RTA_OIF, RTA_PRIORITY, RTA_TABLE, RTM_DELADDR, RTM_DELROUTE, RTM_GETADDR, RTM_GETLINK,
+    RTM_GETROUTE, RTM_NEWADDR, RTM_NEWLINK, RTM_NEWROUTE, RTN_UNICAST, RTPROT_STATIC,
+    RT_SCOPE_LINK, RT_SCOPE_NOWHERE, RT_SCOPE_UNIVERSE, RT_TABLE_LOCAL, RT_TABLE_MAIN,
+    SOCK_CLOEXEC, SOCK_RAW, SOL_NETLINK,
+};
+use std::{
+    ffi::CStr,
+    mem::{self, size_of},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
+    os::unix::io::{AsRawFd, RawFd},
+    ptr, slice,
+};
+
+use crate::{
+    error::{Error, Result},
+    platform::linux::sys::*,
+    platform::posix::Fd,
+    route::{Cidr, Route},
+};
+
+const RECV_BUF_LEN: usize = 64 * 1024;
+
+const HDR_LEN: usize = size_of::<nlmsghdr>();
+
+/// Round `len` up to the 4 bytes alignment of netlink messages and attributes.
+const fn align(len: usize) -> usize {
+    (len + 3) & !3
+}
+
+fn bytes_of<T: Copy>(value: &T) -> &[u8] {
+    unsafe { slice::from_raw_parts(value as *const T as *const u8, size_of::<T>()) }
+}
+
+/// Read a `T` from the beginning of `buf`, if it is large enough.
+pub(crate) fn read_struct<T: Copy>(buf: &[u8]) -> Option<T> {
+    if buf.len() < size_of::<T>() {
+        return None;
+    }
+    Some(unsafe { ptr::read_unaligned(buf.as_ptr() as *const T) })
+}
+
+/// The link message header of the interface `index`, or of all the interfaces for 0.
+fn link_header(index: u32) -> ifinfomsg {
+    let mut header: ifinfomsg = unsafe { mem::zeroed() };
+    header.ifi_index = index as i32;
+    header
+}
+
+/// Decode a string attribute, up to its nul terminator.
+fn read_str(data: &[u8]) -> String {
+    let data = data.split(|b| *b == 0).next().unwrap_or_default();
+    String::from_utf8_lossy(data).into_owned()
+}
+
+/// Decode an address attribute of either family.
+pub(crate) fn read_ip(data: &[u8]) -> Option<IpAddr> {
+    match data.len() {
+        4 => Some(IpAddr::from(<[u8; 4]>::try_from(data).ok()?)),
+        16 => Some(IpAddr::from(<[u8; 16]>::try_from(data).ok()?)),
+        _ => None,
+    }
+}
+
+/// Convert a netmask into a prefix length, the mask must be contiguous.
+pub(crate) fn netmask_to_prefix(netmask: IpAddr) -> Result<u8> {
+    let bits = match netmask {
+        IpAddr::V4(mask) => u128::from(u32::from(mask)) << 96,
+        IpAddr::V6(mask) => u128::from(mask),
+    };
+    let prefix = bits.leading_ones();
+    if bits.checked_shl(prefix).unwrap_or(0) != 0 {
+        return Err(Error::InvalidAddress);
+    }
+    Ok(prefix as u8)
+}
+
+/// Convert a prefix length into the netmask of the family of `addr`.
+pub(crate) fn prefix_to_netmask(addr: IpAddr, prefix: u8) -> IpAddr {
+    match addr {
+        IpAddr::V4(_) => {
+            let mask = u32::MAX
+                .checked_shl(32 - prefix.min(32) as u32)
+                .unwrap_or(0);
+            IpAddr::V4(Ipv4Addr::from(mask))
+        }
+        IpAddr::V6(_) => {
+            let mask = u128::MAX
+                .checked_shl(128 - prefix.min(128) as u32)
+                .unwrap_or(0);
+            IpAddr::V6(Ipv6Addr::from(mask))
+        }
+    }
+}
+
+/// The largest prefix length of the family of `addr`.
+pub(crate) fn max_prefix(addr: IpAddr) -> u8 {
+    if addr.is_ipv6() {
+        128
+    } else {
+        32
+    }
+}
+
+fn family(addr: IpAddr) -> u8 {
+    if addr.is_ipv6() {
+        AF_INET6 as u8
+    } else {
+        AF_INET as u8
+    }
+}
+
+/// A netlink request being built.
+pub(crate) struct Message {
+    buf: Vec<u8>,
+}
+
+impl Message {
+    /// Start a request of the given type, followed by the family specific `header`.
+    pub fn new<T: Copy>(ty: u16, flags: c_int, header: &T) -> Self {
+        let hdr = nlmsghdr {
+            nlmsg_len: 0,
+            nlmsg_type: ty,
+            nlmsg_flags: (flags | NLM_F_REQUEST) as u16,
+            nlmsg_seq: 0,
+            nlmsg_pid: 0,
+        };
+        let mut msg = Message {
+            buf: Vec::with_capacity(256),
+        };
+        msg.put(bytes_of(&hdr));
+        msg.put(bytes_of(header));
+        msg
+    }
+
+    fn put(&mut self, data: &[u8]) {
+        self.buf.extend_from_slice(data);
+        self.buf.resize(align(self.buf.len()), 0);
+    }
+
+    /// Append an attribute.
+    pub fn attr(&mut self, ty: u16, data: &[u8]) -> &mut Self {
+        let rta = rtattr {
+            rta_len: (size_of::<rtattr>() + data.len()) as u16,
+            rta_type: ty,
+        };
+        self.put(bytes_of(&rta));
+        self.put(data);
+        self
+    }
+
+    /// Append a `u32` attribute in host byte order.
+    pub fn attr_u32(&mut self, ty: u16, value: u32) -> &mut Self {
+        self.attr(ty, &value.to_ne_bytes())
+    }
+
+    /// Append a nul-terminated string attribute.
+    pub fn attr_str(&mut self, ty: u16, value: &str) -> &mut Self {
+        let mut data = Vec::with_capacity(value.len() + 1);
+        data.extend_from_slice(value.as_bytes());
+        data.push(0);
+        self.attr(ty, &data)
+    }
+
+    /// Append an address attribute of either family.
+    pub fn attr_ip(&mut self, ty: u16, value: IpAddr) -> &mut Self {
+        match value {
+            IpAddr::V4(ip) => self.attr(ty, &ip.octets()),
+            IpAddr::V6(ip) => self.attr(ty, &ip.octets()),
+        }
+    }
+
+    fn finish(&mut self, seq: u32) -> &[u8] {
+        let len = self.buf.len() as u32;
+        let mut hdr: nlmsghdr = read_struct(&self.buf).unwrap();
+        hdr.nlmsg_len = len;
+        hdr.nlmsg_seq = seq;
+        self.buf[..HDR_LEN].copy_from_slice(bytes_of(&hdr));
+        &self.buf
+    }
+}
+
+/// A message received from the kernel, without its netlink header.
+pub(crate) struct Reply {
+    pub ty: u16,
+    pub data: Vec<u8>,
+}
+
+impl Reply {
+    /// Decode the family specific header of the message.
+    pub fn header<T: Copy>(&self) -> Option<T> {
+        read_struct(&self.data)
+    }
+
+    /// Iterate over the attributes following the family specific header `T`.
+    pub fn attrs<T>(&self) -> Attrs<'_> {
+        Attrs(self.data.get(align(size_of::<T>())..).unwrap_or_default())
+    }
+}
+
+/// Iterator over a sequence of netlink attributes.
+pub(crate) struct Attrs<'a>(pub &'a [u8]);
+
+impl<'a> Iterator for Attrs<'a> {
+    type Item = (u16, &'a [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let rta: rtattr = read_struct(self.0)?;
+        let len = rta.rta_len as usize;
+        if len < size_of::<rtattr>() || len > self.0.len() {
+            return None;
+        }
+        let data = &self.0[size_of::<rtattr>()..len];
+        self.0 = self.0.get(align(len)..).unwrap_or_default();
+        Some((rta.rta_type & NLA_TYPE_MASK as u16, data))
+    }
+}
+
+/// The link properties of an interface.
+#[derive(Clone, Debug)]
+pub(crate) struct Link {
+    pub index: u32,
+    pub name: String,
+    pub mtu: u32,
+    /// The `IFF_*` flags of the interface, such as `IFF_UP`.
+    #[cfg_attr(not(feature = "async"), allow(dead_code))]
+    pub flags: u32,
+    /// The kind of virtual interface, such as `tun` for TUN/TAP interfaces.
+    pub kind: Option<String>,
+    /// Only reported for TUN/TAP interfaces, by kernels 4.15 and later.
+    pub tun: Option<TunInfo>,
+    /// The leading counters of `struct rtnl_link_stats64`: the received and sent
+    /// packets, bytes, errors and drops, in this order.
+    pub stats: Option<[u64; 8]>,
+}
+
+impl Link {
+    pub fn parse(reply: &Reply) -> Option<Self> {
+        let header = reply.header::<ifinfomsg>()?;
+        let mut link = Link {
+            index: header.ifi_index as u32,
+            name: String::new(),
+            mtu: 0,
+            flags: header.ifi_flags,
+            kind: None,
+            tun: None,
+            stats: None,
+        };
+        for (ty, data) in reply.attrs::<ifinfomsg>() {
+            match ty {
+                IFLA_IFNAME => link.name = read_str(data),
+                IFLA_MTU => link.mtu = read_struct(data).unwrap_or_default(),
+                IFLA_STATS64 => link.stats = read_struct(data),
+                IFLA_LINKINFO => {
+                    for (ty, data) in Attrs(data) {
+                        match ty {
+                            IFLA_INFO_KIND => link.kind = Some(read_str(data)),
+                            IFLA_INFO_DATA => link.tun = Some(TunInfo::parse(data)),
+                            _ => {}
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+        if link.kind.as_deref() != Some("tun") {
+            link.tun = None;
+        }
+        Some(link)
+    }
+}
+
+/// The TUN/TAP properties of an interface.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct TunInfo {
+    /// The `IFF_*` flags the interface was set up with, as reported in its sysfs `tun_flags`.
+    pub flags: c_short,
+    pub owner: Option<u32>,
+    pub group: Option<u32>,
+}
+
+impl TunInfo {
+    /// Decode the `IFLA_INFO_DATA` attributes of a TUN/TAP link.
+    fn parse(data: &[u8]) -> Self {
+        let mut info = TunInfo::default();
+        for (ty, data) in Attrs(data) {
+            let value: u8 = read_struct(data).unwrap_or_default();
+            let flag = |flag: c_int| if value != 0 { flag as c_short } else { 0 };
+            match ty {
+                IFLA_TUN_OWNER => info.owner = read_struct(data),
+                IFLA_TUN_GROUP => info.group = read_struct(data),
+                IFLA_TUN_TYPE => info.flags |= value as c_short,
+                IFLA_TUN_PI => info.flags |= if value != 0 { 0 } else { IFF_NO_PI as c_short },
+                IFLA_TUN_VNET_HDR => info.flags |= flag(IFF_VNET_HDR),
+                IFLA_TUN_PERSIST => info.flags |= flag(IFF_PERSIST),
+                IFLA_TUN_MULTI_QUEUE => info.flags |= flag(IFF_MULTI_QUEUE),
+                _ => {}
+            }
+        }
+        info
+    }
+}
+
+/// An address assigned to an interface.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct Address {
+    pub local: IpAddr,
+    pub prefix_len: u8,
+    pub peer: Option<IpAddr>,
+    pub broadcast: Option<IpAddr>,
+}
+
+impl Address {
+    pub fn new(local: IpAddr) -> Self {
+        Address {
+            local,
+            prefix_len: max_prefix(local),
+            peer: None,
+            broadcast: None,
+        }
+    }
+
+    /// Whether the kernel identifies `other` as the same address, only refreshing the lifetimes
+    /// of the existing one when replacing it.
+    pub fn matches(&self, other: &Address) -> bool {
+        self.local == other.local
+            && (self.local.is_ipv6()
+                || (self.prefix_len == other.prefix_len && self.peer == other.peer))
+    }
+
+    /// Decode an address message, along with the index of its interface.
+    pub fn parse(reply: &Reply) -> Option<(u32, Self)> {
+        let header = reply.header::<ifaddrmsg>()?;
+        let (mut local, mut address, mut broadcast) = (None, None, None);
+        for (ty, data) in reply.attrs::<ifaddrmsg>() {
+            match ty {
+                IFA_LOCAL => local = read_ip(data),
+                IFA_ADDRESS => address = read_ip(data),
+                IFA_BROADCAST => broadcast = read_ip(data),
+                _ => {}
+            }
+        }
+        // Without a peer, the kernel only reports IFA_ADDRESS for IPv6.
+        let local_addr = local.or(address)?;
+        let address = Address {
+            local: local_addr,
+            prefix_len: header.ifa_prefixlen,
+            peer: address.filter(|addr| *addr != local_addr),
+            broadcast,
+        };
+        Some((header.ifa_index, address))
+    }
+
+    fn to_message(self, ty: u16, flags: c_int, index: u32) -> Message {
+        let header = ifaddrmsg {
+            ifa_family: family(self.local),
+            ifa_prefixlen: self.prefix_len,
+            ifa_flags: 0,
+            ifa_scope: 0,
+            ifa_index: index,
+        };
+        let mut msg = Message::new(ty, flags, &header);
+        msg.attr_ip(IFA_LOCAL, self.local);
+        msg.attr_ip(IFA_ADDRESS, self.peer.unwrap_or(self.local));
+        if let Some(broadcast) = self.broadcast {
+            msg.attr_ip(IFA_BROADCAST, broadcast);
+        }
+        msg
+    }
+}
+
+/// A rtnetlink socket.
+pub(crate) struct Netlink {
+    fd: Fd,
+    seq: u32,
+}
+
+impl AsRawFd for Netlink {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.inner
+    }
+}
+
+impl Netlink {
+    /// Open a rtnetlink socket, or return `None` when netlink is unavailable
+    /// so that the caller can fall back to ioctl.
+    pub fn open() -> Option<Self> {
+        match Self::new() {
+            Ok(netlink) => Some(netlink),
+            Err(err) => {
+                log::debug!("netlink unavailable, falling back to ioctl: {err}");
+                None
+            }
+        }
+    }
+
+    fn new() -> std::io::Result<Self> {
+        let fd = unsafe { libc::socket(AF_NETLINK, SOCK_RAW | SOCK_CLOEXEC, NETLINK_ROUTE) };
+        let fd = Fd::new(fd, true).map_err(|_| std::io::Error::last_os_error())?;
+
+        // Both options are best effort, older kernels just report the bare errno.
+        for opt in [NETLINK_EXT_ACK, NETLINK_CAP_ACK] {
+            let on: c_int = 1;
+            unsafe {
+                libc::setsockopt(
+                    fd.inner,
+                    SOL_NETLINK,
+                    opt,
+                    &on as *const _ as *const c_void,
+                    size_of::<c_int>() as socklen_t,
+                )
+            };
+        }
+
+        Ok(Netlink { fd, seq: 0 })
+    }
+
+    /// Open a non-blocking rtnetlink socket receiving the notifications of the
+    /// multicast `groups`, a combination of the `RTMGRP_*` flags.
+    #[cfg(feature = "async")]
+    pub fn subscribe(groups: u32) -> std::io::Result<Self> {
+        let ty = SOCK_RAW | SOCK_CLOEXEC | libc::SOCK_NONBLOCK;
+        let fd = unsafe { libc::socket(AF_NETLINK, ty, NETLINK_ROUTE) };
+        let fd = Fd::new(fd, true).map_err(|_| std::io::Error::last_os_error())?;
+        let mut addr: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        addr.nl_family = AF_NETLINK as u16;
+        addr.nl_groups = groups;
+        let len = size_of::<libc::sockaddr_nl>() as socklen_t;
+        if unsafe { libc::bind(fd.inner, &addr as *const _ as *const libc::sockaddr, len) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Netlink { fd, seq: 0 })
+    }
+
+    /// Receive the notifications of one datagram, failing with `WouldBlock` when there is none.
+    #[cfg(feature = "async")]
+    pub fn notifications(&mut self) -> std::io::Result<Vec<Reply>> {
+        let mut buf = vec![0_u8; RECV_BUF_LEN];
+        let len = self.fd.read(&mut buf)?;
+        let mut data = &buf[..len];
+        let mut notifications = Vec::new();
+        while let Some(hdr) = read_struct::<nlmsghdr>(data) {
+            let msg_len = hdr.nlmsg_len as usize;
+            if msg_len < HDR_LEN || msg_len > data.len() {
+                return Err(std::io::Error::from(std::io::ErrorKind::InvalidData));
+            }
+            notifications.push(Reply {
+                ty: hdr.nlmsg_type,
+                data: data[HDR_LEN..msg_len].to_vec(),
+            });
+            data = data.get(align(msg_len)..).unwrap_or_default();
+        }
+        Ok(notifications)
+    }
+
+    /// Send `msg` and collect the replies until the kernel acknowledges it.
+    pub fn request(&mut self, mut msg: Message) -> Result<Vec<Reply>> {
+        self.seq = self.seq.wrapping_add(1);
+        let seq = self.seq;
+        let buf = msg.finish(seq);
+        if self.fd.write(buf)? != buf.len() {
+            return Err(std::io::Error::from(std::io::ErrorKind::WriteZero).into());
+        }
+
+        let mut replies = Vec::new();
+        let mut buf = vec![0_u8; RECV_BUF_LEN];
+        loop {
+            let len = self.fd.read(&mut buf)?;
+            let mut data = &buf[..len];
+            while let Some(hdr) = read_struct::<nlmsghdr>(data) {
+                let msg_len = hdr.nlmsg_len as usize;
+                if msg_len < HDR_LEN || msg_len > data.len() {
+                    return Err(std::io::Error::from(std::io::ErrorKind::InvalidData).into());
+                }
+                let payload = &data[HDR_LEN..msg_len];
+                data = data.get(align(msg_len)..).unwrap_or_default();
+
+                if hdr.nlmsg_seq != seq {
+                    continue;
+                }
+                match hdr.nlmsg_type as c_int {
+                    NLMSG_DONE => return Ok(replies),
+                    NLMSG_ERROR => return parse_ack(hdr.nlmsg_flags, payload).map(|_| replies),
+                    _ => replies.push(Reply {
+                        ty: hdr.nlmsg_type,
+                        data: payload.to_vec(),
+                    }),
+                }
+            }
+        }
+    }
+
+    /// Get the link properties of the interface.
+    pub fn link(&mut self, index: u32) -> Result<Link> {
+        let header = link_header(index);
+        let replies = self.request(Message::new(RTM_GETLINK, NLM_F_ACK, &header))?;
+        replies
+            .iter()
+            .find(|reply| reply.ty == RTM_NEWLINK)
+            .and_then(Link::parse)
+            .ok_or(Error::InvalidConfig)
+    }
+
+    /// Get the link properties of all the interfaces.
+    pub fn links(&mut self) -> Result<Vec<Link>> {
+        let header = link_header(0);
+        let replies = self.request(Message::new(RTM_GETLINK, NLM_F_DUMP, &header))?;
+        Ok(replies
+            .iter()
+            .filter(|reply| reply.ty == RTM_NEWLINK)
+            .filter_map(Link::parse)
+            .collect())
+    }
+
+    fn set_link(&mut self, header: ifinfomsg, f: impl FnOnce(&mut Message)) -> Result<()> {
+        let mut msg = Message::new(RTM_NEWLINK, NLM_F_ACK, &header);
+        f(&mut msg);
+        self.request(msg).map(|_| ())
+    }
+
+    /// Rename the interface.
+    pub fn set_link_name(&mut self, index: u32, name: &str) -> Result<()> {
+        let header = link_header(index);
+        self.set_link(header, |msg| {
+            msg.attr_str(IFLA_IFNAME, name);
+        })
+    }
+
+    /// Set the MTU of the interface.
+    pub fn set_link_mtu(&mut self, index: u32, mtu: u32) -> Result<()> {
+        let header = link_header(index);
+        self.set_link(header, |msg| {
+            msg.attr_u32(IFLA_MTU, mtu);
+        })
+    }
+
+    /// Move the interface to the network namespace `netns`.
+    pub fn set_link_netns(&mut self, index: u32, netns: RawFd) -> Result<()> {
+        let header = link_header(index);
+        self.set_link(header, |msg| {
+            msg.attr_u32(IFLA_NET_NS_FD, netns as u32);
+        })
+    }
+
+    /// Bring the interface up or down.
+    pub fn set_link_up(&mut self, index: u32, up: bool) -> Result<()> {
+        let mut header = link_header(index);
+        header.ifi_flags = if up { IFF_UP as u32 } else { 0 };
+        header.ifi_change = IFF_UP as u32;
+        self.set_link(header, |_| {})
+    }
+
+    /// List the addresses of the interface, in the order the kernel reports them.
+    pub fn addresses(&mut self, index: u32) -> Result<Vec<Address>> {
+        let header = ifaddrmsg {
+            ifa_family: AF_UNSPEC as u8,
+            ifa_prefixlen: 0,
+            ifa_flags: 0,
+            ifa_scope: 0,
+            ifa_index: 0,
+        };
+        let replies = self.request(Message::new(RTM_GETADDR, NLM_F_DUMP, &header))?;
+
+        Ok(replies
+            .iter()
+            .filter(|reply| reply.ty == RTM_NEWADDR)
+            .filter_map(Address::parse)
+            .filter(|(ifa_index, _)| *ifa_index == index)
+            .map(|(_, address)| address)
+            .collect())
+    }
+
+    /// Add an address to the interface, or replace the existing one with the same local address.
+    pub fn add_address(&mut self, index: u32, address: &Address) -> Result<()> {
+        let flags = NLM_F_ACK | NLM_F_CREATE | NLM_F_REPLACE;
+        let msg = address.to_message(RTM_NEWADDR, flags, index);
+        self.request(msg).map(|_| ())
+    }
+
+    /// Remove an address from the interface.
+    pub fn del_address(&mut self, index: u32, address: &Address) -> Result<()> {
+        let msg = address.to_message(RTM_DELADDR, NLM_F_ACK, index);
+        self.request(msg).map(|_| ())
+    }
+
+    /// List the unicast routes through the interface, in all the tables but the local one.
+    pub fn routes(&mut self, index: u32) -> Result<Vec<Route>> {
+        let header = rtmsg {
+            rtm_family: AF_UNSPEC as u8,
+            ..Default::default()
+        };
+        let replies = self.request(Message::new(RTM_GETROUTE, NLM_F_DUMP, &header))?;
+
+        let mut routes = Vec::new();
+        for reply in replies.iter().filter(|reply| reply.ty == RTM_NEWROUTE) {
+            let Some(header) = reply.header::<rtmsg>() else {
+                continue;
+            };
+            if header.rtm_type != RTN_UNICAST {
+                continue;
+            }
+            let (mut dst, mut gateway, mut oif, mut metric) = (None, None, None, 0);
+            let mut table = header.rtm_table as u32;
+            for (ty, data) in reply.attrs::<rtmsg>() {
+                match ty {
+                    RTA_DST => dst = read_ip(data),
+                    RTA_GATEWAY => gateway = read_ip(data),
+                    RTA_OIF => oif = read_struct::<u32>(data),
+                    RTA_PRIORITY => metric = read_struct(data).unwrap_or_default(),
+                    RTA_TABLE => table = read_struct(data).unwrap_or(table),
+                    _ => {}
+                }
+            }
+            if oif != Some(index) || table == RT_TABLE_LOCAL as u32 {
+                continue;
+            }
+            let dst = dst.unwrap_or(match header.rtm_family as c_int {
+                AF_INET6 => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+                _ => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            });
+            routes.push(Route {
+                destination: Cidr::new(dst, header.rtm_dst_len)?,
+                gateway,
+                metric,
+            });
+        }
+        Ok(routes)
+    }
+
+    fn route_message(ty: u16, flags: c_int, index: u32, route: &Route) -> Message {
+        let dst = route.destination;
+        let header = rtmsg {
+            rtm_family: family(dst.addr()),
+            rtm_dst_len: dst.prefix_len(),
+            rtm_table: RT_TABLE_MAIN,
+            rtm_protocol: RTPROT_STATIC,
+            rtm_scope: match (ty, route.gateway) {
+                (RTM_DELROUTE, _) => RT_SCOPE_NOWHERE,
+                (_, Some(_)) => RT_SCOPE_UNIVERSE,
+                (_, None) => RT_SCOPE_LINK,
+            },
+            rtm_type: RTN_UNICAST,
+            ..Default::default()
+        };
+        let mut msg = Message::new(ty, flags, &header);
+        if dst.prefix_len() > 0 {
+            msg.attr_ip(RTA_DST, dst.addr());
+        }
+        msg.attr_u32(RTA_OIF, index);
+        if let Some(gateway) = route.gateway {
+            msg.attr_ip(RTA_GATEWAY, gateway);
+        }
+        if route.metric != 0 {
+            msg.attr_u32(RTA_PRIORITY, route.metric);
+        }
+        msg
+    }
+
+    /// Add a route through the interface to the main table.
+    pub fn add_route(&mut self, index: u32, route: &Route) -> Result<()> {
+        let flags = NLM_F_ACK | NLM_F_CREATE | NLM_F_EXCL;
+        let msg = Self::route_message(RTM_NEWROUTE, flags, index, route);
+        self.request(msg).map(|_| ())
+    }
+
+    /// Delete a route through the interface from the main table, a zero metric matches any.
+    pub fn del_route(&mut self, index: u32, route: &Route) -> Result<()> {
+        let msg = Self::route_message(RTM_DELROUTE, NLM_F_ACK, index, route);
+        self.request(msg).map(|_| ())
+    }
+}
+
+/// Decode a `NLMSG_ERROR` message, which is an acknowledgement when its error code is zero.
+fn parse_ack(flags: u16, payload: &[u8]) -> Result<()> {
+    let code: i32 = read_struct(payload).ok_or(Error::InvalidConfig)?;
+    if code == 0 {
+        return Ok(());
+    }
+    let err = std::io::Error::from_raw_os_error(-code);
+
+    if flags as c_int & NLM_F_ACK_TLVS != 0 {
+        // The request is echoed after the error code, unless it was capped to its header.
+        let request_len = if flags as c_int & NLM_F_CAPPED != 0 {
+            HDR_LEN
+        } else {
+            read_struct::<nlmsghdr>(&payload[size_of::<i32>()..])
+                .map(|hdr| align(hdr.nlmsg_len as usize))
+                .unwrap_or(HDR_LEN)
+        };
+        let tlvs = payload
+            .get(size_of::<i32>() + request_len..)
+            .unwrap_or_default();
+        for (ty, data) in Attrs(tlvs) {
+            if ty == NLMSGERR_ATTR_MSG {
+                let message = CStr::from_bytes_until_nul(data)
+                    .map(|msg| msg.to_string_lossy().into_owned())
+                    .unwrap_or_else(|_| String::from_utf8_lossy(data).into_owned());
+                return Err(Error::Netlink {
+                    source: err,
+                    message,
+                });
+            }
+        }
+    }
+
+    Err(err.into())
+}
+
+#[test]
+fn test_prefix_conversion() {
+    let mask: IpAddr = "255.255.240.0".parse().unwrap();
+    assert_eq!(20, netmask_to_prefix(mask).unwrap());
+    assert_eq!(mask, prefix_to_netmask(mask, 20));
+
+    let mask: IpAddr = "ffff:ffff:ffff:ffff::".parse().unwrap();
+    assert_eq!(64, netmask_to_prefix(mask).unwrap());
+    assert_eq!(mask, prefix_to_netmask(mask, 64));
+
+    assert_eq!(0, netmask_to_prefix("0.0.0.0".parse().unwrap()).unwrap());
+    assert!(netmask_to_prefix("255.0.255.0".parse().unwrap()).is_err());
+}
@@ -0,0 +1,61 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Switching the calling thread to another network namespace.
+
+use crate::error::Result;
+use crate::platform::posix::Fd;
+use libc::{CLONE_NEWNET, O_CLOEXEC, O_RDONLY};
+use std::ffi::CString;
+use std::os::unix::{ffi::OsStrExt, io::RawFd};
+use std::path::Path;
+
+/// Open the network namespace file `path`, such as `/var/run/netns/NAME` or `/proc/PID/ns/net`.
+pub(crate) fn open(path: &Path) -> Result<Fd> {
+    let path = CString::new(path.as_os_str().as_bytes())?;
+    let fd = unsafe { libc::open(path.as_ptr(), O_RDONLY | O_CLOEXEC) };
+    Ok(Fd::new(fd, true).map_err(|_| std::io::Error::last_os_error())?)
+}
+
+/// Switch the calling thread to the network namespace `fd`.
+fn set(fd: RawFd) -> std::io::Result<()> {
+    if unsafe { libc::setns(fd, CLONE_NEWNET) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Keeps the calling thread in another network namespace, until dropped.
+pub(crate) struct NetnsGuard {
+    previous: Fd,
+}
+
+impl NetnsGuard {
+    /// Switch the calling thread to the network namespace `fd`.
+    ///
+    /// [Note: This requires `CAP_SYS_ADMIN` in both namespaces. -- end note]
+    pub fn enter(fd: RawFd) -> Result<Self> {
+        let previous = open(Path::new("/proc/thread-self/ns/net"))?;
+        set(fd)?;
+        Ok(NetnsGuard { previous })
+    }
+}
+
+impl Drop for NetnsGuard {
+    fn drop(&mut self) {
+        if let Err(err) = set(self.previous.inner) {
+            log::error!("failed to return to the previous network namespace: {err}");
+        }
+    }
+}
@@ -14,8 +14,8 @@
 
 //! Bindings to internal Linux stuff.
 
-use libc::{c_int, ifreq};
-use nix::{ioctl_read_bad, ioctl_write_ptr, ioctl_write_ptr_bad};
+use libc::{c_int, c_uint, ifreq, sock_fprog};
+use nix::{ioctl_read, ioctl_read_bad, ioctl_write_int, ioctl_write_ptr, ioctl_write_ptr_bad};
 
 ioctl_read_bad!(siocgifflags, 0x8913, ifreq);
 ioctl_write_ptr_bad!(siocsifflags, 0x8914, ifreq);
@@ -30,8 +30,154 @@ ioctl_write_ptr_bad!(siocsifnetmask, 0x891c, ifreq);
 ioctl_read_bad!(siocgifmtu, 0x8921, ifreq);
 ioctl_write_ptr_bad!(siocsifmtu, 0x8922, ifreq);
 ioctl_write_ptr_bad!(siocsifname, 0x8923, ifreq);
+ioctl_write_ptr_bad!(siocsifhwaddr, 0x8924, ifreq);
+ioctl_read_bad!(siocgifhwaddr, 0x8927, ifreq);
+ioctl_read_bad!(siocgifindex, 0x8933, ifreq);
 
 ioctl_write_ptr!(tunsetiff, b'T', 202, c_int);
-ioctl_write_ptr!(tunsetpersist, b'T', 203, c_int);
-ioctl_write_ptr!(tunsetowner, b'T', 204, c_int);
-ioctl_write_ptr!(tunsetgroup, b'T', 206, c_int);
+ioctl_write_int!(tunsetpersist, b'T', 203);
+ioctl_write_int!(tunsetowner, b'T', 204);
+ioctl_write_int!(tunsetgroup, b'T', 206);
+ioctl_read!(tungetfeatures, b'T', 207, c_uint);
+ioctl_write_int!(tunsetoffload, b'T', 208);
+ioctl_read!(tungetiff, b'T', 210, c_uint);
+ioctl_write_ptr!(tunattachfilter, b'T', 213, sock_fprog);
+ioctl_write_ptr!(tundetachfilter, b'T', 214, sock_fprog);
+ioctl_write_ptr!(tunsetvnethdrsz, b'T', 216, c_int);
+ioctl_write_ptr!(tunsetqueue, b'T', 217, c_int);
+ioctl_write_ptr!(tunsetvnetle, b'T', 220, c_int);
+ioctl_read!(tunsetsteeringebpf, b'T', 224, c_int);
+ioctl_read!(tunsetfilterebpf, b'T', 225, c_int);
+
+pub const NLMSGERR_ATTR_MSG: u16 = 1;
+
+pub const IFLA_LINKINFO: u16 = 18;
+pub const IFLA_STATS64: u16 = 23;
+pub const IFLA_NET_NS_FD: u16 = 28;
+pub const IFLA_INFO_KIND: u16 = 1;
+pub const IFLA_INFO_DATA: u16 = 2;
+
+pub const IFLA_TUN_OWNER: u16 = 1;
+pub const IFLA_TUN_GROUP: u16 = 2;
+pub const IFLA_TUN_TYPE: u16 = 3;
+pub const IFLA_TUN_PI: u16 = 4;
+pub const IFLA_TUN_VNET_HDR: u16 = 5;
+pub const IFLA_TUN_PERSIST: u16 = 6;
+pub const IFLA_TUN_MULTI_QUEUE: u16 = 7;
+
+/// The route message header, which `libc` does not provide unlike the other netlink headers.
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct rtmsg {
+    pub rtm_family: u8,
+    pub rtm_dst_len: u8,
+    pub rtm_src_len: u8,
+    pub rtm_tos: u8,
+    pub rtm_table: u8,
+    pub rtm_protocol: u8,
+    pub rtm_scope: u8,
+    pub rtm_type: u8,
+    pub rtm_flags: u32,
+}
+
+#[cfg(feature = "io-uring")]
+pub const IORING_OFF_SQ_RING: libc::off_t = 0;
+#[cfg(feature = "io-uring")]
+pub const IORING_OFF_CQ_RING: libc::off_t = 0x8000000;
+#[cfg(feature = "io-uring")]
+pub const IORING_OFF_SQES: libc::off_t = 0x10000000;
+#[cfg(feature = "io-uring")]
+pub const IORING_FEAT_SINGLE_MMAP: u32 = 1;
+#[cfg(feature = "io-uring")]
+pub const IORING_ENTER_GETEVENTS: u32 = 1;
+#[cfg(feature = "io-uring")]
+pub const IORING_REGISTER_BUFFERS: u32 = 0;
+#[cfg(feature = "io-uring")]
+pub const IORING_REGISTER_EVENTFD: u32 = 4;
+#[cfg(feature = "io-uring")]
+pub const IORING_OP_READ_FIXED: u8 = 4;
+#[cfg(feature = "io-uring")]
+pub const IORING_OP_WRITE_FIXED: u8 = 5;
+#[cfg(feature = "io-uring")]
+pub const IORING_OP_ASYNC_CANCEL: u8 = 14;
+
+#[cfg(feature = "io-uring")]
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct io_sqring_offsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub flags: u32,
+    pub dropped: u32,
+    pub array: u32,
+    pub resv1: u32,
+    pub user_addr: u64,
+}
+
+#[cfg(feature = "io-uring")]
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct io_cqring_offsets {
+    pub head: u32,
+    pub tail: u32,
+    pub ring_mask: u32,
+    pub ring_entries: u32,
+    pub overflow: u32,
+    pub cqes: u32,
+    pub flags: u32,
+    pub resv1: u32,
+    pub user_addr: u64,
+}
+
+#[cfg(feature = "io-uring")]
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct io_uring_params {
+    pub sq_entries: u32,
+    pub cq_entries: u32,
+    pub flags: u32,
+    pub sq_thread_cpu: u32,
+    pub sq_thread_idle: u32,
+    pub features: u32,
+    pub wq_fd: u32,
+    pub resv: [u32; 3],
+    pub sq_off: io_sqring_offsets,
+    pub cq_off: io_cqring_offsets,
+}
+
+#[cfg(feature = "io-uring")]
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct io_uring_sqe {
+    pub opcode: u8,
+    pub flags: u8,
+    pub ioprio: u16,
+    pub fd: i32,
+    pub off: u64,
+    pub addr: u64,
+    pub len: u32,
+    pub op_flags: u32,
+    pub user_data: u64,
+    pub buf_index: u16,
+    pub personality: u16,
+    pub splice_fd_in: i32,
+    pub addr3: u64,
+    pub pad: u64,
+}
+
+#[cfg(feature = "io-uring")]
+#[allow(non_camel_case_types)]
+#[repr(C)]
+#[derive(Copy, Clone, Default)]
+pub struct io_uring_cqe {
+    pub user_data: u64,
+    pub res: i32,
+    pub flags: u32,
+}
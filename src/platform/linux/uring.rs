@@ -0,0 +1,259 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! A minimal io_uring instance, submitting reads and writes on pre-registered buffers.
+
+use crate::platform::linux::sys::*;
+use crate::platform::posix::Fd;
+use libc::{c_void, iovec};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+pub(crate) use crate::platform::linux::sys::{IORING_OP_READ_FIXED, IORING_OP_WRITE_FIXED};
+
+/// A shared memory region of the ring.
+struct Mmap {
+    ptr: *mut c_void,
+    len: usize,
+}
+
+impl Mmap {
+    fn new(fd: RawFd, len: usize, offset: libc::off_t) -> std::io::Result<Self> {
+        let ptr = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED | libc::MAP_POPULATE,
+                fd,
+                offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(Mmap { ptr, len })
+    }
+
+    /// Get a pointer `offset` bytes into the region.
+    fn at<T>(&self, offset: u32) -> *mut T {
+        unsafe { self.ptr.add(offset as usize) as *mut T }
+    }
+}
+
+impl Drop for Mmap {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr, self.len);
+        }
+    }
+}
+
+/// An io_uring submission and completion queue pair.
+pub(crate) struct Ring {
+    sqes: Mmap,
+    /// Only mapped on its own by kernels older than 5.4, kept alive for the pointers into it.
+    _cq_ring: Option<Mmap>,
+    sq_ring: Mmap,
+    fd: Fd,
+
+    sq_head: *const AtomicU32,
+    sq_tail: *const AtomicU32,
+    sq_mask: u32,
+    sq_entries: u32,
+    cq_head: *const AtomicU32,
+    cq_tail: *const AtomicU32,
+    cq_mask: u32,
+    cqes: *const io_uring_cqe,
+
+    /// Entries pushed but not submitted yet.
+    pending: u32,
+}
+
+// SAFETY: the raw pointers only point into the memory mapped by the ring, which it owns.
+unsafe impl Send for Ring {}
+
+impl Ring {
+    /// Create a ring with at least `entries` submission entries.
+    pub fn new(entries: u32) -> std::io::Result<Self> {
+        let mut params = io_uring_params::default();
+        let fd = unsafe { libc::syscall(libc::SYS_io_uring_setup, entries, &mut params) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        let fd = Fd::new(fd as RawFd, true).map_err(std::io::Error::from)?;
+
+        let sq_len = params.sq_off.array as usize + params.sq_entries as usize * 4;
+        let cq_len = params.cq_off.cqes as usize
+            + params.cq_entries as usize * std::mem::size_of::<io_uring_cqe>();
+        let single_mmap = params.features & IORING_FEAT_SINGLE_MMAP != 0;
+        let sq_ring = if single_mmap {
+            Mmap::new(fd.as_raw_fd(), sq_len.max(cq_len), IORING_OFF_SQ_RING)?
+        } else {
+            Mmap::new(fd.as_raw_fd(), sq_len, IORING_OFF_SQ_RING)?
+        };
+        let cq_ring = if single_mmap {
+            None
+        } else {
+            Some(Mmap::new(fd.as_raw_fd(), cq_len, IORING_OFF_CQ_RING)?)
+        };
+        let sqes_len = params.sq_entries as usize * std::mem::size_of::<io_uring_sqe>();
+        let sqes = Mmap::new(fd.as_raw_fd(), sqes_len, IORING_OFF_SQES)?;
+
+        let sq_off = params.sq_off;
+        let cq_off = params.cq_off;
+        let cq = cq_ring.as_ref().unwrap_or(&sq_ring);
+        let ring = Ring {
+            sq_head: sq_ring.at(sq_off.head),
+            sq_tail: sq_ring.at(sq_off.tail),
+            sq_mask: unsafe { *sq_ring.at::<u32>(sq_off.ring_mask) },
+            sq_entries: params.sq_entries,
+            cq_head: cq.at(cq_off.head),
+            cq_tail: cq.at(cq_off.tail),
+            cq_mask: unsafe { *cq.at::<u32>(cq_off.ring_mask) },
+            cqes: cq.at(cq_off.cqes),
+            pending: 0,
+            sqes,
+            _cq_ring: cq_ring,
+            sq_ring,
+            fd,
+        };
+
+        // Submission entries are always used in order.
+        let array = ring.sq_ring.at::<u32>(sq_off.array);
+        for i in 0..params.sq_entries {
+            unsafe { *array.add(i as usize) = i };
+        }
+        Ok(ring)
+    }
+
+    fn register(&self, opcode: u32, arg: *const c_void, count: u32) -> std::io::Result<()> {
+        let fd = self.fd.as_raw_fd();
+        let res = unsafe { libc::syscall(libc::SYS_io_uring_register, fd, opcode, arg, count) };
+        if res < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    /// Register the buffers used by the fixed reads and writes, by index.
+    pub fn register_buffers(&self, buffers: &[iovec]) -> std::io::Result<()> {
+        let arg = buffers.as_ptr() as *const c_void;
+        self.register(IORING_REGISTER_BUFFERS, arg, buffers.len() as u32)
+    }
+
+    /// Register an eventfd signaled on every completion.
+    pub fn register_eventfd(&self, fd: RawFd) -> std::io::Result<()> {
+        self.register(
+            IORING_REGISTER_EVENTFD,
+            &fd as *const RawFd as *const c_void,
+            1,
+        )
+    }
+
+    /// Queue an entry, returning `false` if the submission queue is full.
+    pub fn push(&mut self, sqe: io_uring_sqe) -> bool {
+        unsafe {
+            let head = (*self.sq_head).load(Ordering::Acquire);
+            let tail = (*self.sq_tail).load(Ordering::Relaxed);
+            if tail.wrapping_sub(head) == self.sq_entries {
+                return false;
+            }
+            let entry = self
+                .sqes
+                .at::<io_uring_sqe>(0)
+                .add((tail & self.sq_mask) as usize);
+            ptr::write(entry, sqe);
+            (*self.sq_tail).store(tail.wrapping_add(1), Ordering::Release);
+        }
+        self.pending += 1;
+        true
+    }
+
+    /// Submit the queued entries, waiting for `min_complete` completions.
+    pub fn submit(&mut self, min_complete: u32) -> std::io::Result<()> {
+        let flags = if min_complete > 0 {
+            IORING_ENTER_GETEVENTS
+        } else {
+            0
+        };
+        loop {
+            let res = unsafe {
+                libc::syscall(
+                    libc::SYS_io_uring_enter,
+                    self.fd.as_raw_fd(),
+                    self.pending,
+                    min_complete,
+                    flags,
+                    ptr::null::<c_void>(),
+                    0,
+                )
+            };
+            if res >= 0 {
+                self.pending -= res as u32;
+                return Ok(());
+            }
+            let err = std::io::Error::last_os_error();
+            if err.kind() != std::io::ErrorKind::Interrupted {
+                return Err(err);
+            }
+        }
+    }
+
+    /// Call `f` with the user data and result of every completion.
+    pub fn reap(&mut self, mut f: impl FnMut(u64, i32)) {
+        unsafe {
+            let mut head = (*self.cq_head).load(Ordering::Relaxed);
+            let tail = (*self.cq_tail).load(Ordering::Acquire);
+            while head != tail {
+                let cqe = *self.cqes.add((head & self.cq_mask) as usize);
+                f(cqe.user_data, cqe.res);
+                head = head.wrapping_add(1);
+            }
+            (*self.cq_head).store(head, Ordering::Release);
+        }
+    }
+}
+
+/// Build a read or write of `len` bytes of the registered buffer `buf_index` at `addr`.
+pub(crate) fn rw_fixed(
+    opcode: u8,
+    fd: RawFd,
+    addr: *const u8,
+    len: usize,
+    buf_index: u16,
+    user_data: u64,
+) -> io_uring_sqe {
+    io_uring_sqe {
+        opcode,
+        fd,
+        addr: addr as u64,
+        len: len as u32,
+        buf_index,
+        user_data,
+        ..Default::default()
+    }
+}
+
+/// Build a cancellation of the request whose user data is `target`.
+pub(crate) fn cancel(target: u64, user_data: u64) -> io_uring_sqe {
+    io_uring_sqe {
+        opcode: IORING_OP_ASYNC_CANCEL,
+        fd: -1,
+        addr: target,
+        user_data,
+        ..Default::default()
+    }
+}
@@ -21,6 +21,7 @@ use crate::{
         macos::sys::*,
         posix::{self, ipaddr_to_sockaddr, sockaddr_union, Fd},
     },
+    route::{add_alias_command, route_command, Cidr},
     run_command::run_command,
 };
 
@@ -241,6 +242,11 @@ impl Device {
         self.tun.set_nonblock()
     }
 
+    #[cfg(feature = "async")]
+    pub(crate) fn tun(&self) -> &posix::Tun {
+        &self.tun
+    }
+
     fn set_route(&mut self, route: Route) -> Result<()> {
         if let Some(v) = &self.route {
             let prefix_len = ipnet::ip_mask_to_prefix(IpAddr::V4(v.netmask))
@@ -285,6 +291,20 @@ impl Device {
     pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
         self.tun.send(buf)
     }
+
+    /// Recv as many packets as available from tun device without blocking, after the first one
+    pub fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        self.tun.recv_many(bufs, sizes)
+    }
+
+    /// Send packets to tun device until it would block
+    pub fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        self.tun.send_many(packets)
+    }
 }
 
 impl Read for Device {
@@ -367,6 +387,26 @@ impl AbstractDevice for Device {
         }
     }
 
+    fn add_address(&mut self, value: IpAddr, prefix_len: u8) -> Result<()> {
+        let tun_name = self.tun_name.as_ref().ok_or(Error::InvalidConfig)?;
+        add_alias_command(tun_name, value, prefix_len)
+    }
+
+    fn add_route(&mut self, destination: Cidr, gateway: Option<IpAddr>, metric: u32) -> Result<()> {
+        let tun_name = self.tun_name.as_ref().ok_or(Error::InvalidConfig)?;
+        route_command("add", tun_name, destination, gateway)
+    }
+
+    fn delete_route(&mut self, destination: Cidr, gateway: Option<IpAddr>) -> Result<()> {
+        let tun_name = self.tun_name.as_ref().ok_or(Error::InvalidConfig)?;
+        route_command("delete", tun_name, destination, gateway)
+    }
+
+    fn routes(&self) -> Result<Vec<crate::route::Route>> {
+        let tun_name = self.tun_name.as_ref().ok_or(Error::InvalidConfig)?;
+        posix::list_routes(tun_name)
+    }
+
     fn destination(&self) -> Result<IpAddr> {
         let ctl = self.ctl.as_ref().ok_or(Error::InvalidConfig)?;
         unsafe {
@@ -18,9 +18,14 @@
 pub(crate) mod posix;
 
 #[cfg(target_os = "linux")]
-pub(crate) mod linux;
+pub mod linux;
+#[cfg(all(target_os = "linux", feature = "async"))]
+pub use self::linux::LinkEvent;
 #[cfg(target_os = "linux")]
-pub use self::linux::{create, Device, PlatformConfig};
+pub use self::linux::{
+    create, create_queues, delete_persistent, list_devices, Device, DeviceInfo, Filter, FilterRule,
+    InterfaceStats, PlatformConfig,
+};
 
 #[cfg(target_os = "freebsd")]
 pub(crate) mod freebsd;
@@ -43,7 +48,7 @@ pub(crate) mod android;
 pub use self::android::{create, Device, PlatformConfig};
 
 #[cfg(unix)]
-pub use crate::platform::posix::Tun;
+pub use crate::platform::posix::{IoStats, Tun};
 
 #[cfg(target_os = "windows")]
 pub(crate) mod windows;
@@ -80,4 +85,742 @@ mod test {
 
         assert_eq!(crate::DEFAULT_MTU, dev.mtu().unwrap());
     }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn create_queues() {
+        let mut config = Configuration::default();
+        config.tun_name("utun7").queues(2).up();
+        let devs = super::create_queues(&config).unwrap();
+
+        assert_eq!(2, devs.len());
+        assert_eq!("utun7", devs[1].tun_name().unwrap());
+
+        devs[1].detach_queue().unwrap();
+        devs[1].attach_queue().unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn dual_stack_addresses() {
+        let ipv6: std::net::IpAddr = "fd00::1".parse().unwrap();
+        let mut dev = super::create(
+            Configuration::default()
+                .tun_name("utun8")
+                .address("192.168.51.1")
+                .netmask("255.255.255.0")
+                .add_address(ipv6, 64)
+                .add_address("192.168.52.1", 24)
+                .up(),
+        )
+        .unwrap();
+
+        let addresses = dev.addresses().unwrap();
+        assert!(addresses.contains(&("192.168.51.1".parse().unwrap(), 24)));
+        assert!(addresses.contains(&("192.168.52.1".parse().unwrap(), 24)));
+        assert!(addresses.contains(&(ipv6, 64)));
+
+        dev.remove_address(ipv6).unwrap();
+        assert!(!dev.addresses().unwrap().contains(&(ipv6, 64)));
+
+        // The new address is added to the subnet of the current one before it is deleted.
+        dev.set_address("192.168.51.2".parse().unwrap()).unwrap();
+        let addresses = dev.addresses().unwrap();
+        assert!(addresses.contains(&("192.168.51.2".parse().unwrap(), 24)));
+        assert!(!addresses.contains(&("192.168.51.1".parse().unwrap(), 24)));
+        assert!(addresses.contains(&("192.168.52.1".parse().unwrap(), 24)));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn routes() {
+        use crate::route::Cidr;
+
+        // Namespaces are per thread, this keeps the routes of the host untouched.
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let mut dev = super::create(
+            Configuration::default()
+                .tun_name("utun9")
+                .address("10.10.0.1")
+                .netmask("255.255.255.0")
+                .up(),
+        )
+        .unwrap();
+
+        let destination: Cidr = "10.20.0.0/16".parse().unwrap();
+        let gateway = Some("10.10.0.2".parse().unwrap());
+        dev.add_route(destination, gateway, 10).unwrap();
+
+        let routes = dev.routes().unwrap();
+        let route = routes
+            .iter()
+            .find(|route| route.destination == destination)
+            .unwrap();
+        assert_eq!(gateway, route.gateway);
+        assert_eq!(10, route.metric);
+
+        dev.delete_route(destination, gateway).unwrap();
+        let routes = dev.routes().unwrap();
+        assert!(!routes.iter().any(|route| route.destination == destination));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn vnet_hdr() {
+        use crate::VirtioNetHdr;
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let mut dev = super::create(
+            Configuration::default()
+                .tun_name("utun10")
+                .address("10.11.0.1")
+                .netmask("255.255.255.0")
+                .platform_config(|config| {
+                    config.vnet_hdr(true);
+                })
+                .up(),
+        )
+        .unwrap();
+        dev.set_offload(libc::TUN_F_CSUM).unwrap();
+
+        let socket = std::net::UdpSocket::bind("10.11.0.1:0").unwrap();
+        socket.send_to(b"vnet", "10.11.0.2:9").unwrap();
+
+        let mut buf = [0; 1500];
+        let (hdr, len) = loop {
+            let (hdr, len) = dev.recv_with_hdr(&mut buf).unwrap();
+            if buf[0] >> 4 == 4 && buf[9] == libc::IPPROTO_UDP as u8 {
+                break (hdr, len);
+            }
+        };
+        assert_eq!(b"vnet", &buf[28..len]);
+        if hdr.flags & VirtioNetHdr::F_NEEDS_CSUM != 0 {
+            assert_eq!(20, hdr.csum_start);
+            assert_eq!(6, hdr.csum_offset);
+        }
+
+        let sent = dev
+            .send_with_hdr(&VirtioNetHdr::default(), &buf[..len])
+            .unwrap();
+        assert_eq!(len, sent);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn udp_segmentation_offload() {
+        use crate::VirtioNetHdr;
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let mut dev = super::create(
+            Configuration::default()
+                .tun_name("utun11")
+                .address("10.12.0.1")
+                .netmask("255.255.255.0")
+                .platform_config(|config| {
+                    config.offload(true);
+                })
+                .up(),
+        )
+        .unwrap();
+        let socket = std::net::UdpSocket::bind("10.12.0.1:5000").unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+
+        let datagram = udp_datagram([10, 12, 0, 2], [10, 12, 0, 1], 5000, 1000);
+        let datagrams = [&datagram[..]; 3];
+        let mut buf = [0; 1500];
+        for uso in [dev.uso(), false] {
+            if !uso {
+                // Without USO the batch is written one datagram at a time.
+                dev.set_offload(libc::TUN_F_CSUM).unwrap();
+            }
+            assert_eq!(3, dev.send_coalesced(&datagrams).unwrap());
+            for _ in 0..3 {
+                assert_eq!(1000, socket.recv(&mut buf).unwrap());
+            }
+        }
+
+        // A super-packet built by the application is segmented when USO is off.
+        let coalesced = crate::gro_coalesce(&datagrams, true);
+        let (hdr, packet) = &coalesced[0];
+        assert_eq!(VirtioNetHdr::GSO_UDP_L4, hdr.gso_type);
+        assert_eq!(3, dev.send_gso(hdr, packet).unwrap());
+        for _ in 0..3 {
+            assert_eq!(1000, socket.recv(&mut buf).unwrap());
+        }
+    }
+
+    /// Build an IPv4 UDP datagram from port 12345 with `len` bytes of payload.
+    #[cfg(target_os = "linux")]
+    fn udp_datagram(src: [u8; 4], dst: [u8; 4], port: u16, len: usize) -> Vec<u8> {
+        use crate::offload::{checksum_add, checksum_fold, pseudo_header_sum};
+
+        let mut datagram = vec![7_u8; 28 + len];
+        datagram[..12].copy_from_slice(&[0x45, 0, 0, 0, 0, 0, 0x40, 0, 64, 17, 0, 0]);
+        datagram[2..4].copy_from_slice(&(28 + len as u16).to_be_bytes());
+        datagram[12..16].copy_from_slice(&src);
+        datagram[16..20].copy_from_slice(&dst);
+        let ip_checksum = !checksum_fold(checksum_add(0, &datagram[..20]));
+        datagram[10..12].copy_from_slice(&ip_checksum.to_be_bytes());
+
+        datagram[20..22].copy_from_slice(&12345_u16.to_be_bytes());
+        datagram[22..24].copy_from_slice(&port.to_be_bytes());
+        datagram[24..26].copy_from_slice(&(8 + len as u16).to_be_bytes());
+        datagram[26..28].fill(0);
+        let sum = pseudo_header_sum(&datagram, 17, 8 + len);
+        let udp_checksum = !checksum_fold(checksum_add(sum, &datagram[20..]));
+        datagram[26..28].copy_from_slice(&udp_checksum.to_be_bytes());
+        datagram
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn batched_io() {
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let dev = super::create(
+            Configuration::default()
+                .tun_name("utun12")
+                .address("10.13.0.1")
+                .netmask("255.255.255.0")
+                .up(),
+        )
+        .unwrap();
+        let socket = std::net::UdpSocket::bind("10.13.0.1:5000").unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+
+        for _ in 0..4 {
+            socket.send_to(b"batch", "10.13.0.2:9").unwrap();
+        }
+        // Batches are only received in non-blocking mode.
+        dev.set_nonblock().unwrap();
+        let mut bufs = vec![vec![0_u8; 1500]; 8];
+        let mut sizes = vec![0; 8];
+        let mut datagrams = 0;
+        while datagrams < 4 {
+            let count = match dev.recv_many(&mut bufs, &mut sizes) {
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                    continue;
+                }
+                count => count.unwrap(),
+            };
+            datagrams += bufs[..count]
+                .iter()
+                .zip(&sizes)
+                .filter(|(buf, size)| buf[0] >> 4 == 4 && buf[9] == 17 && **size == 33)
+                .count();
+        }
+        assert_eq!(4, datagrams);
+
+        let datagram = udp_datagram([10, 13, 0, 2], [10, 13, 0, 1], 5000, 100);
+        assert_eq!(3, dev.send_many(&[&datagram; 3]).unwrap());
+        let mut buf = [0; 1500];
+        for _ in 0..3 {
+            assert_eq!(100, socket.recv(&mut buf).unwrap());
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tap_device() {
+        use crate::Layer;
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let mac = [0x02, 0, 0, 0, 0, 0x14];
+        let mut dev = super::create(
+            Configuration::default()
+                .tun_name("utun14")
+                .layer(Layer::L2)
+                .mac_address(mac)
+                .address("10.15.0.1")
+                .netmask("255.255.255.0")
+                .platform_config(|config| {
+                    #[allow(deprecated)]
+                    config.packet_information(true);
+                })
+                .up(),
+        )
+        .unwrap();
+        assert_eq!(mac, dev.mac_address().unwrap());
+        let other = [0x02, 0, 0, 0, 0, 0x15];
+        dev.set_mac_address(other).unwrap();
+        assert_eq!(other, dev.mac_address().unwrap());
+
+        // Resolving the peer makes the kernel send an ARP request from the interface.
+        let socket = std::net::UdpSocket::bind("10.15.0.1:5000").unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+        socket.send_to(b"tap", "10.15.0.2:9").unwrap();
+        let mut buf = [0; 1514];
+        loop {
+            let len = dev.recv(&mut buf).unwrap();
+            if len >= 14 && buf[12..14] == [0x08, 0x06] {
+                assert_eq!(other, buf[6..12]);
+                break;
+            }
+        }
+
+        let mut frame = other.to_vec();
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x16, 0x08, 0x00]);
+        frame.extend(udp_datagram([10, 15, 0, 2], [10, 15, 0, 1], 5000, 100));
+        assert_eq!(frame.len(), dev.send(&frame).unwrap());
+        assert_eq!(100, socket.recv(&mut buf).unwrap());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tap_adapter() {
+        use crate::{Layer, TapAdapter};
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let dev = super::create(
+            Configuration::default()
+                .tun_name("utun15")
+                .layer(Layer::L2)
+                .address("10.16.0.1")
+                .netmask("255.255.255.0")
+                .up(),
+        )
+        .unwrap();
+        let mut tap = TapAdapter::new(dev).unwrap();
+        tap.add_gateway("10.16.0.254".parse().unwrap());
+
+        // The datagram only leaves once the gateway is resolved by the adapter.
+        let socket = std::net::UdpSocket::bind("10.16.0.1:5000").unwrap();
+        socket
+            .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+            .unwrap();
+        socket.send_to(b"adapter", "10.16.0.254:9").unwrap();
+        let mut buf = [0; 1500];
+        loop {
+            let len = tap.recv(&mut buf).unwrap();
+            if buf[0] >> 4 == 4 && buf[9] == libc::IPPROTO_UDP as u8 {
+                assert_eq!(b"adapter", &buf[28..len]);
+                break;
+            }
+        }
+
+        let datagram = udp_datagram([10, 16, 0, 254], [10, 16, 0, 1], 5000, 100);
+        assert_eq!(datagram.len(), tap.send(&datagram).unwrap());
+        assert_eq!(100, socket.recv(&mut buf).unwrap());
+    }
+
+    /// Load an eBPF socket filter returning `value`.
+    #[cfg(target_os = "linux")]
+    fn load_socket_filter(value: i32) -> std::os::unix::io::RawFd {
+        // mov r0, value; exit
+        let mut insns = [0_u8; 16];
+        insns[0] = 0xb7;
+        insns[4..8].copy_from_slice(&value.to_ne_bytes());
+        insns[8] = 0x95;
+        let license = c"GPL";
+
+        // The attributes of BPF_PROG_LOAD: type, instructions count, instructions and license.
+        let mut attr = [0_u8; 128];
+        attr[..4].copy_from_slice(&1_u32.to_ne_bytes());
+        attr[4..8].copy_from_slice(&2_u32.to_ne_bytes());
+        attr[8..16].copy_from_slice(&(insns.as_ptr() as u64).to_ne_bytes());
+        attr[16..24].copy_from_slice(&(license.as_ptr() as u64).to_ne_bytes());
+        let fd = unsafe { libc::syscall(libc::SYS_bpf, 5, attr.as_ptr(), attr.len()) };
+        assert!(fd >= 0, "{}", std::io::Error::last_os_error());
+        fd as _
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn ebpf_programs() {
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let dev = super::create(
+            Configuration::default()
+                .tun_name("utun16")
+                .address("10.17.0.1")
+                .netmask("255.255.255.0")
+                .up(),
+        )
+        .unwrap();
+        // Only eBPF programs are accepted.
+        let fd = std::os::unix::io::AsRawFd::as_raw_fd(&dev);
+        assert!(dev.attach_filter_ebpf(fd).is_err());
+
+        let steering = load_socket_filter(0);
+        dev.attach_steering_ebpf(steering).unwrap();
+        unsafe { libc::close(steering) };
+        let filter = load_socket_filter(0);
+        dev.attach_filter_ebpf(filter).unwrap();
+        unsafe { libc::close(filter) };
+
+        // The first datagram is dropped by the filter, the second one is received.
+        let socket = std::net::UdpSocket::bind("10.17.0.1:0").unwrap();
+        socket.send_to(b"first", "10.17.0.2:9").unwrap();
+        dev.detach_filter_ebpf().unwrap();
+        socket.send_to(b"second", "10.17.0.2:9").unwrap();
+        let mut buf = [0; 1500];
+        loop {
+            let len = dev.recv(&mut buf).unwrap();
+            if buf[0] >> 4 == 4 && buf[9] == libc::IPPROTO_UDP as u8 {
+                assert_eq!(b"second", &buf[28..len]);
+                break;
+            }
+        }
+        dev.detach_steering_ebpf().unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn classic_filter() {
+        use crate::{Filter, FilterRule, Layer};
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let dev = super::create(
+            Configuration::default()
+                .tun_name("utun17")
+                .layer(Layer::L2)
+                .address("10.18.0.1")
+                .netmask("255.255.255.0")
+                .up(),
+        )
+        .unwrap();
+        let mut filter = Filter::new();
+        filter.rule(FilterRule::new().ipv4().udp().destination_port(7777));
+        dev.attach_filter(&filter).unwrap();
+
+        // The frames queued before the filter was attached are skipped.
+        let mut buf = [0; 1514];
+        let mut recv_datagram = || loop {
+            let len = dev.recv(&mut buf).unwrap();
+            if buf[12..14] == [0x08, 0x00] && buf[23] == libc::IPPROTO_UDP as u8 {
+                return buf[42..len].to_vec();
+            }
+        };
+
+        // Broadcasts need no neighbor resolution, only the second one passes the filter.
+        let socket = std::net::UdpSocket::bind("10.18.0.1:0").unwrap();
+        socket.set_broadcast(true).unwrap();
+        socket.send_to(b"dropped", "10.18.0.255:9").unwrap();
+        socket.send_to(b"kept", "10.18.0.255:7777").unwrap();
+        assert_eq!(b"kept", &recv_datagram()[..]);
+
+        dev.detach_filter().unwrap();
+        socket.send_to(b"passed", "10.18.0.255:9").unwrap();
+        assert_eq!(b"passed", &recv_datagram()[..]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn persistent_device() {
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+        let exists = |name: &str| {
+            let name = std::ffi::CString::new(name).unwrap();
+            unsafe { libc::if_nametoindex(name.as_ptr()) != 0 }
+        };
+
+        let mut dev = super::create(Configuration::default().tun_name("utun18")).unwrap();
+        dev.persist().unwrap();
+        dev.user(0).unwrap();
+        drop(dev);
+        assert!(exists("utun18"));
+
+        let existing = |name: &str| {
+            super::create(
+                Configuration::default()
+                    .tun_name(name)
+                    .platform_config(|config| {
+                        config.open_existing(true);
+                    }),
+            )
+        };
+        let mut dev = existing("utun18").unwrap();
+        dev.unpersist().unwrap();
+        dev.persist().unwrap();
+        match super::delete_persistent("utun18") {
+            Err(crate::Error::Io(err)) => assert_eq!(err.raw_os_error(), Some(libc::EBUSY)),
+            res => panic!("{res:?}"),
+        }
+        drop(dev);
+        assert!(exists("utun18"));
+        assert!(existing("utun19").is_err());
+        assert!(!exists("utun19"));
+
+        super::delete_persistent("utun18").unwrap();
+        assert!(!exists("utun18"));
+        assert!(super::delete_persistent("utun18").is_err());
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn list_devices() {
+        use crate::Layer;
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let mut tap = super::create_queues(
+            Configuration::default()
+                .tun_name("utun20")
+                .layer(Layer::L2)
+                .queues(2)
+                .mtu(1400)
+                .platform_config(|config| {
+                    config.vnet_hdr(true);
+                }),
+        )
+        .unwrap();
+        tap[0].persist().unwrap();
+        tap[0].user(1000).unwrap();
+        let _tun = super::create(Configuration::default().tun_name("utun21")).unwrap();
+
+        let mut devices = super::list_devices().unwrap();
+        devices.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(2, devices.len());
+        let (tap, tun) = (&devices[0], &devices[1]);
+        assert_eq!("utun20", tap.name);
+        assert_eq!(Layer::L2, tap.layer);
+        assert!(tap.multi_queue && tap.vnet_hdr && tap.persistent);
+        assert!(!tap.packet_information);
+        assert_eq!(Some(1000), tap.owner);
+        assert_eq!(None, tap.group);
+        assert_eq!(1400, tap.mtu);
+        assert_eq!("utun21", tun.name);
+        assert_eq!(Layer::L3, tun.layer);
+        assert!(!tun.multi_queue && !tun.vnet_hdr && !tun.persistent);
+        assert_eq!(None, tun.owner);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn kernel_features() {
+        use libc::{IFF_MULTI_QUEUE, IFF_NO_PI, IFF_TAP, IFF_TUN, IFF_VNET_HDR};
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let features = crate::linux::features().unwrap();
+        assert!(features.supports(IFF_TUN | IFF_TAP | IFF_NO_PI | IFF_VNET_HDR | IFF_MULTI_QUEUE));
+        assert_ne!(0, features.offloads & libc::TUN_F_CSUM);
+
+        let dev = super::create(Configuration::default().tun_name("utun22")).unwrap();
+        let flags = dev.flags().unwrap();
+        assert_eq!(IFF_TUN | IFF_NO_PI, flags & (IFF_TUN | IFF_TAP | IFF_NO_PI));
+        assert_eq!(0, flags & IFF_VNET_HDR);
+
+        let dev = super::create(Configuration::default().tun_name("utun23").platform_config(
+            |config| {
+                config.vnet_hdr(true);
+            },
+        ))
+        .unwrap();
+        assert_ne!(0, dev.flags().unwrap() & IFF_VNET_HDR);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn network_namespaces() {
+        use std::sync::mpsc::channel;
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+        let current = || std::fs::read_link("/proc/thread-self/ns/net").unwrap();
+        let exists = |name: &str| {
+            let name = std::ffi::CString::new(name).unwrap();
+            unsafe { libc::if_nametoindex(name.as_ptr()) != 0 }
+        };
+        let own = current();
+        let own_path = format!("/proc/self/task/{}/ns/net", unsafe { libc::gettid() });
+
+        // A thread staying in another namespace, until the end of the test.
+        let (tid_tx, tid_rx) = channel();
+        let (done_tx, done_rx) = channel::<()>();
+        let other = std::thread::spawn(move || {
+            assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+            tid_tx.send(unsafe { libc::gettid() }).unwrap();
+            done_rx.recv().ok();
+        });
+        let other_path = format!("/proc/self/task/{}/ns/net", tid_rx.recv().unwrap());
+
+        let mut dev = super::create(
+            Configuration::default()
+                .tun_name("utun24")
+                .address((10, 24, 0, 1))
+                .netmask((255, 255, 255, 0))
+                .mtu(1400)
+                .netns(&other_path)
+                .up(),
+        )
+        .unwrap();
+        assert_eq!(own, current());
+        assert!(!exists("utun24"));
+        assert_eq!(1400, dev.mtu().unwrap());
+        assert_eq!(Ipv4Addr::new(10, 24, 0, 1), dev.address().unwrap());
+
+        dev.move_to_netns(&own_path).unwrap();
+        assert_eq!(own, current());
+        assert!(exists("utun24"));
+        assert!(dev.address().is_err());
+        dev.set_mtu(1300).unwrap();
+        assert_eq!(1300, dev.mtu().unwrap());
+
+        drop(done_tx);
+        other.join().unwrap();
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn interface_stats() {
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let dev = super::create(
+            Configuration::default()
+                .tun_name("utun26")
+                .address((10, 26, 0, 1))
+                .netmask((255, 255, 255, 0))
+                .up(),
+        )
+        .unwrap();
+        dev.set_nonblock().unwrap();
+        let socket = std::net::UdpSocket::bind("10.26.0.1:5000").unwrap();
+
+        socket.send_to(b"stats", "10.26.0.2:9").unwrap();
+        let mut buf = [0; 1500];
+        let mut read = 0;
+        while let Ok(len) = dev.recv(&mut buf) {
+            read += len as u64;
+        }
+        let datagram = udp_datagram([10, 26, 0, 2], [10, 26, 0, 1], 5000, 100);
+        dev.send(&datagram).unwrap();
+        // Not an IP packet, dropped by the kernel.
+        assert!(dev.send(&[0; 20]).is_err());
+
+        let (rx, tx) = dev.io_stats();
+        assert!(rx.packets >= 1);
+        assert_eq!(read, rx.bytes);
+        assert_eq!(1, rx.would_block);
+        assert_eq!(1, tx.packets);
+        assert_eq!(datagram.len() as u64, tx.bytes);
+        assert_eq!(1, tx.errors);
+        assert_eq!(0, tx.short_writes);
+
+        // The kernel may have sent more packets since, such as IPv6 multicast reports.
+        let stats = dev.stats().unwrap();
+        assert!(stats.tx_packets >= rx.packets);
+        assert!(stats.tx_bytes >= rx.bytes);
+        assert_eq!(1, stats.rx_packets);
+        assert_eq!(datagram.len() as u64, stats.rx_bytes);
+        assert_eq!(1, stats.rx_dropped);
+    }
+
+    #[cfg(all(target_os = "linux", feature = "async"))]
+    #[test]
+    fn link_events() {
+        use crate::LinkEvent;
+        use futures::StreamExt;
+
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let mut dev =
+                crate::create_as_async(Configuration::default().tun_name("utun25")).unwrap();
+            let events = dev.events().unwrap();
+
+            let address = Ipv4Addr::new(10, 25, 0, 1).into();
+            dev.set_mtu(1400).unwrap();
+            dev.add_address(address, 24).unwrap();
+            dev.remove_address(address).unwrap();
+            dev.enabled(true).unwrap();
+            dev.enabled(false).unwrap();
+            drop(dev);
+
+            // The IPv6 link-local address comes and goes with the link.
+            let events: Vec<_> = events
+                .map(Result::unwrap)
+                .filter(|event| {
+                    let link_local = matches!(
+                        event,
+                        LinkEvent::AddressAdded(addr, _) | LinkEvent::AddressRemoved(addr, _)
+                            if addr.is_ipv6()
+                    );
+                    std::future::ready(!link_local)
+                })
+                .collect()
+                .await;
+            assert_eq!(
+                vec![
+                    LinkEvent::MtuChanged(1400),
+                    LinkEvent::AddressAdded(address, 24),
+                    LinkEvent::AddressRemoved(address, 24),
+                    LinkEvent::LinkUp,
+                    LinkEvent::LinkDown,
+                    LinkEvent::Deleted,
+                ],
+                events
+            );
+        });
+    }
+
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    #[test]
+    fn uring_device() {
+        assert_eq!(0, unsafe { libc::unshare(libc::CLONE_NEWNET) });
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap();
+        runtime.block_on(async {
+            let dev = crate::create_as_uring(
+                Configuration::default()
+                    .tun_name("utun13")
+                    .address("10.14.0.1")
+                    .netmask("255.255.255.0")
+                    .up(),
+            )
+            .unwrap();
+            let socket = std::net::UdpSocket::bind("10.14.0.1:5000").unwrap();
+            socket
+                .set_read_timeout(Some(std::time::Duration::from_secs(1)))
+                .unwrap();
+            let is_datagram = |buf: &[u8]| buf[0] >> 4 == 4 && buf[9] == 17;
+
+            socket.send_to(b"uring", "10.14.0.2:9").unwrap();
+            let mut buf = [0; 1500];
+            loop {
+                let len = dev.recv(&mut buf).await.unwrap();
+                if is_datagram(&buf) {
+                    assert_eq!(33, len);
+                    break;
+                }
+            }
+
+            for _ in 0..4 {
+                socket.send_to(b"uring", "10.14.0.2:9").unwrap();
+            }
+            let mut bufs = vec![vec![0_u8; 1500]; 8];
+            let mut sizes = vec![0; 8];
+            let mut datagrams = 0;
+            while datagrams < 4 {
+                let count = dev.recv_many(&mut bufs, &mut sizes).await.unwrap();
+                datagrams += bufs[..count].iter().filter(|buf| is_datagram(buf)).count();
+            }
+            assert_eq!(4, datagrams);
+
+            let datagram = udp_datagram([10, 14, 0, 2], [10, 14, 0, 1], 5000, 100);
+            assert_eq!(datagram.len(), dev.send(&datagram).await.unwrap());
+            assert_eq!(3, dev.send_many(&[&datagram; 3]).await.unwrap());
+            for _ in 0..4 {
+                assert_eq!(100, socket.recv(&mut buf).unwrap());
+            }
+        });
+    }
 }
@@ -15,11 +15,14 @@
 use crate::error::{Error, Result};
 use libc::{self, fcntl, F_GETFL, F_SETFL, O_NONBLOCK};
 use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 /// POSIX file descriptor support for `io` traits.
 pub(crate) struct Fd {
     pub(crate) inner: RawFd,
     close_fd_on_drop: bool,
+    /// Whether non-blocking mode is enabled, saving a syscall per query.
+    nonblock: AtomicBool,
 }
 
 impl Fd {
@@ -27,20 +30,48 @@ impl Fd {
         if value < 0 {
             return Err(Error::InvalidDescriptor);
         }
+        let flags = unsafe { fcntl(value, F_GETFL) };
         Ok(Fd {
             inner: value,
             close_fd_on_drop,
+            nonblock: AtomicBool::new(flags != -1 && flags & O_NONBLOCK != 0),
         })
     }
 
     /// Enable non-blocking mode
     pub fn set_nonblock(&self) -> std::io::Result<()> {
         match unsafe { fcntl(self.inner, F_SETFL, fcntl(self.inner, F_GETFL) | O_NONBLOCK) } {
-            0 => Ok(()),
+            0 => {
+                self.nonblock.store(true, Ordering::Relaxed);
+                Ok(())
+            }
             _ => Err(std::io::Error::last_os_error()),
         }
     }
 
+    /// Disable non-blocking mode
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    pub fn clear_nonblock(&self) -> std::io::Result<()> {
+        match unsafe {
+            fcntl(
+                self.inner,
+                F_SETFL,
+                fcntl(self.inner, F_GETFL) & !O_NONBLOCK,
+            )
+        } {
+            0 => {
+                self.nonblock.store(false, Ordering::Relaxed);
+                Ok(())
+            }
+            _ => Err(std::io::Error::last_os_error()),
+        }
+    }
+
+    /// Whether non-blocking mode is enabled
+    pub fn is_nonblock(&self) -> bool {
+        self.nonblock.load(Ordering::Relaxed)
+    }
+
     pub fn read(&self, buf: &mut [u8]) -> std::io::Result<usize> {
         let fd = self.as_raw_fd();
         let amount = unsafe { libc::read(fd, buf.as_mut_ptr() as *mut _, buf.len()) };
@@ -58,6 +89,26 @@ impl Fd {
         }
         Ok(amount as usize)
     }
+
+    pub fn read_vectored(&self, bufs: &mut [std::io::IoSliceMut<'_>]) -> std::io::Result<usize> {
+        let fd = self.as_raw_fd();
+        let iov = bufs.as_mut_ptr() as *mut libc::iovec;
+        let amount = unsafe { libc::readv(fd, iov, bufs.len() as libc::c_int) };
+        if amount < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(amount as usize)
+    }
+
+    pub fn write_vectored(&self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        let fd = self.as_raw_fd();
+        let iov = bufs.as_ptr() as *const libc::iovec;
+        let amount = unsafe { libc::writev(fd, iov, bufs.len() as libc::c_int) };
+        if amount < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(amount as usize)
+    }
 }
 
 impl AsRawFd for Fd {
@@ -24,5 +24,11 @@ pub(crate) use sockaddr::ipaddr_to_sockaddr;
 mod fd;
 pub(crate) use self::fd::Fd;
 
+#[cfg(any(target_os = "macos", target_os = "freebsd", test))]
+mod routes;
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub(crate) use self::routes::list_routes;
+
 mod split;
-pub use self::split::{Reader, Tun, Writer};
+pub(crate) use self::split::is_ipv6;
+pub use self::split::{IoStats, Reader, Tun, Writer};
@@ -0,0 +1,257 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! The routes of the BSD routing table, as dumped by the `NET_RT_DUMP` sysctl.
+
+use crate::route::{Cidr, Route};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// The length of the `struct rt_msghdr` heading every message, the libc crate lacks it on FreeBSD.
+#[cfg(target_os = "macos")]
+const RT_MSGHDR_LEN: usize = std::mem::size_of::<libc::rt_msghdr>();
+#[cfg(not(target_os = "macos"))]
+const RT_MSGHDR_LEN: usize = 152;
+
+/// The alignment of the socket addresses following the header.
+#[cfg(target_os = "macos")]
+const SA_ALIGN: usize = 4;
+#[cfg(not(target_os = "macos"))]
+const SA_ALIGN: usize = 8;
+
+/// The BSD route flags, which differ from the Linux ones.
+const RTF_UP: i32 = 0x1;
+const RTF_GATEWAY: i32 = 0x2;
+const RTF_LLINFO: i32 = 0x400;
+const RTF_MULTICAST: i32 = 0x80_0000;
+
+/// The indices of the socket addresses in `rtm_addrs`.
+const RTAX_DST: usize = 0;
+const RTAX_GATEWAY: usize = 1;
+const RTAX_NETMASK: usize = 2;
+const RTAX_MAX: usize = 8;
+
+/// List the routes through the interface `tun_name`, in all the address families.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub(crate) fn list_routes(tun_name: &str) -> crate::error::Result<Vec<Route>> {
+    use crate::error::Error;
+    use std::ptr;
+
+    let name = std::ffi::CString::new(tun_name).map_err(|_| Error::InvalidName)?;
+    let index = unsafe { libc::if_nametoindex(name.as_ptr()) };
+    if index == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    let mut mib = [
+        libc::CTL_NET,
+        libc::PF_ROUTE,
+        0,
+        libc::AF_UNSPEC,
+        libc::NET_RT_DUMP,
+        0,
+    ];
+    let buf = loop {
+        let mut len = 0;
+        let mib_len = mib.len() as libc::c_uint;
+        let null = ptr::null_mut();
+        if unsafe { libc::sysctl(mib.as_mut_ptr(), mib_len, null, &mut len, null, 0) } < 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut buf = vec![0_u8; len];
+        let oldp = buf.as_mut_ptr() as *mut libc::c_void;
+        if unsafe { libc::sysctl(mib.as_mut_ptr(), mib_len, oldp, &mut len, null, 0) } == 0 {
+            buf.truncate(len);
+            break buf;
+        }
+        // The table may have grown in between.
+        let err = std::io::Error::last_os_error();
+        if err.raw_os_error() != Some(libc::ENOMEM) {
+            return Err(err.into());
+        }
+    };
+    Ok(parse_routes(&buf, index))
+}
+
+/// Decode the unicast routes through the interface of index `index` from a routing table dump.
+fn parse_routes(mut buf: &[u8], index: u32) -> Vec<Route> {
+    let mut routes = vec![];
+    while buf.len() >= RT_MSGHDR_LEN {
+        let len = u16::from_ne_bytes([buf[0], buf[1]]) as usize;
+        if len < RT_MSGHDR_LEN || len > buf.len() {
+            break;
+        }
+        routes.extend(parse_route(&buf[..len], index));
+        buf = &buf[len..];
+    }
+    routes
+}
+
+fn parse_route(msg: &[u8], index: u32) -> Option<Route> {
+    let get_i32 = |offset: usize| i32::from_ne_bytes(msg[offset..offset + 4].try_into().unwrap());
+    let (flags, addrs) = (get_i32(8), get_i32(12));
+    if u16::from_ne_bytes([msg[4], msg[5]]) as u32 != index
+        || flags & RTF_UP == 0
+        || flags & (RTF_LLINFO | RTF_MULTICAST) != 0
+    {
+        return None;
+    }
+
+    let mut sockaddrs: [Option<&[u8]>; RTAX_MAX] = [None; RTAX_MAX];
+    let mut rest = &msg[RT_MSGHDR_LEN..];
+    for (i, sockaddr) in sockaddrs.iter_mut().enumerate() {
+        if addrs & (1 << i) == 0 {
+            continue;
+        }
+        let len = *rest.first()? as usize;
+        *sockaddr = Some(rest.get(..len)?);
+        rest = rest
+            .get(len.max(1).next_multiple_of(SA_ALIGN)..)
+            .unwrap_or_default();
+    }
+
+    let destination = sockaddr_ip(sockaddrs[RTAX_DST]?)?;
+    if destination.is_multicast() {
+        return None;
+    }
+    let gateway = match flags & RTF_GATEWAY {
+        0 => None,
+        _ => Some(sockaddr_ip(sockaddrs[RTAX_GATEWAY]?)?),
+    };
+    // A route without a netmask is a host route, the netmask may be truncated after its last
+    // non-zero byte and lack the address family.
+    let prefix_len = match (sockaddrs[RTAX_NETMASK], destination) {
+        (None, IpAddr::V4(_)) => 32,
+        (None, IpAddr::V6(_)) => 128,
+        (Some(netmask), IpAddr::V4(_)) => {
+            let mut mask = [0_u8; 4];
+            let bytes = netmask.get(4..).unwrap_or_default();
+            let len = bytes.len().min(4);
+            mask[..len].copy_from_slice(&bytes[..len]);
+            u32::from_be_bytes(mask).leading_ones()
+        }
+        (Some(netmask), IpAddr::V6(_)) => {
+            let mut mask = [0_u8; 16];
+            let bytes = netmask.get(8..).unwrap_or_default();
+            let len = bytes.len().min(16);
+            mask[..len].copy_from_slice(&bytes[..len]);
+            u128::from_be_bytes(mask).leading_ones()
+        }
+    };
+    Some(Route {
+        destination: Cidr::new(destination, prefix_len as u8).ok()?,
+        gateway,
+        metric: 0,
+    })
+}
+
+/// Get the address of an IPv4 or IPv6 socket address.
+fn sockaddr_ip(sockaddr: &[u8]) -> Option<IpAddr> {
+    match *sockaddr.get(1)? as i32 {
+        libc::AF_INET => {
+            let octets: [u8; 4] = sockaddr.get(4..8)?.try_into().ok()?;
+            Some(Ipv4Addr::from(octets).into())
+        }
+        libc::AF_INET6 => {
+            let octets: [u8; 16] = sockaddr.get(8..24)?.try_into().ok()?;
+            let mut addr = Ipv6Addr::from(octets);
+            // The KAME stack embeds the scope of the link-local addresses in their second group.
+            let mut segments = addr.segments();
+            if segments[0] & 0xffc0 == 0xfe80 {
+                segments[1] = 0;
+                addr = segments.into();
+            }
+            Some(addr.into())
+        }
+        _ => None,
+    }
+}
+
+#[test]
+fn test_parse_routes() {
+    fn message(index: u16, flags: i32, sockaddrs: &[(usize, Vec<u8>)]) -> Vec<u8> {
+        let mut msg = vec![0_u8; RT_MSGHDR_LEN];
+        msg[4..6].copy_from_slice(&index.to_ne_bytes());
+        msg[8..12].copy_from_slice(&flags.to_ne_bytes());
+        let addrs = sockaddrs.iter().fold(0_i32, |addrs, (i, _)| addrs | 1 << i);
+        msg[12..16].copy_from_slice(&addrs.to_ne_bytes());
+        for (_, sockaddr) in sockaddrs {
+            let len = msg.len() + sockaddr.len().max(1).next_multiple_of(SA_ALIGN);
+            msg.extend_from_slice(sockaddr);
+            msg.resize(len, 0);
+        }
+        let len = msg.len() as u16;
+        msg[..2].copy_from_slice(&len.to_ne_bytes());
+        msg
+    }
+    fn sockaddr_in(addr: [u8; 4]) -> Vec<u8> {
+        let mut sockaddr = vec![16, libc::AF_INET as u8, 0, 0];
+        sockaddr.extend_from_slice(&addr);
+        sockaddr.resize(16, 0);
+        sockaddr
+    }
+
+    let ipv6: Ipv6Addr = "fe80:7::".parse().unwrap();
+    let mut sockaddr_in6 = vec![28, libc::AF_INET6 as u8, 0, 0, 0, 0, 0, 0];
+    sockaddr_in6.extend_from_slice(&ipv6.octets());
+    sockaddr_in6.resize(28, 0);
+    let dump = [
+        // 10.1.0.0/16 via 10.0.0.1, its netmask truncated.
+        message(
+            7,
+            RTF_UP | RTF_GATEWAY,
+            &[
+                (RTAX_DST, sockaddr_in([10, 1, 0, 0])),
+                (RTAX_GATEWAY, sockaddr_in([10, 0, 0, 1])),
+                (RTAX_NETMASK, vec![6, 0, 0, 0, 255, 255]),
+            ],
+        ),
+        // The host route of another interface.
+        message(3, RTF_UP, &[(RTAX_DST, sockaddr_in([10, 0, 0, 2]))]),
+        // The default route, its netmask empty.
+        message(
+            7,
+            RTF_UP,
+            &[(RTAX_DST, sockaddr_in([0; 4])), (RTAX_NETMASK, vec![])],
+        ),
+        // fe80::/64 with the scope embedded.
+        message(
+            7,
+            RTF_UP,
+            &[
+                (RTAX_DST, sockaddr_in6),
+                (
+                    RTAX_NETMASK,
+                    vec![
+                        16, 0, 0, 0, 0, 0, 0, 0, 255, 255, 255, 255, 255, 255, 255, 255,
+                    ],
+                ),
+            ],
+        ),
+    ]
+    .concat();
+
+    let route = |destination: &str, gateway: Option<&str>| Route {
+        destination: destination.parse().unwrap(),
+        gateway: gateway.map(|gateway| gateway.parse().unwrap()),
+        metric: 0,
+    };
+    assert_eq!(
+        vec![
+            route("10.1.0.0/16", Some("10.0.0.1")),
+            route("0.0.0.0/0", None),
+            route("fe80::/64", None),
+        ],
+        parse_routes(&dump, 7)
+    );
+}
@@ -13,11 +13,13 @@
 //  0. You just DO WHAT THE FUCK YOU WANT TO.
 
 use crate::platform::posix::Fd;
+use crate::virtio::VirtioNetHdr;
 use crate::PACKET_INFORMATION_LENGTH as PIL;
 use bytes::BufMut;
-use std::io::{Read, Write};
+use std::io::{IoSlice, IoSliceMut, Read, Write};
 use std::os::unix::io::{AsRawFd, IntoRawFd, RawFd};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
 
 /// Infer the protocol based on the first nibble in the packet buffer.
 pub(crate) fn is_ipv6(buf: &[u8]) -> std::io::Result<bool> {
@@ -63,22 +65,215 @@ pub(crate) fn generate_packet_information(
     None
 }
 
+/// Build the packet information of an Ethernet frame, from its EtherType.
+pub(crate) fn frame_packet_information(buf: &[u8]) -> std::io::Result<[u8; PIL]> {
+    use std::io::{Error, ErrorKind::InvalidData};
+    if buf.len() < ETHERNET_HEADER_LENGTH {
+        return Err(Error::new(InvalidData, "Truncated Ethernet frame"));
+    }
+    Ok([0, 0, buf[12], buf[13]])
+}
+
+/// The length of an untagged Ethernet header.
+const ETHERNET_HEADER_LENGTH: usize = 14;
+
+/// The largest header in front of the packets: packet information and virtio header.
+const MAX_PREFIX_LEN: usize = PIL + VirtioNetHdr::MRG_RXBUF_LEN;
+
+fn vnet_hdr_disabled() -> std::io::Error {
+    std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "virtio net header is not enabled",
+    )
+}
+
+/// Counters of the packets going through a `Reader` or a `Writer`, kept in process
+/// to be compared with the statistics of the kernel.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct IoStats {
+    /// The packets read or written.
+    pub packets: u64,
+    /// The bytes of the packets read or written, without packet information or virtio header.
+    pub bytes: u64,
+    /// The reads or writes which would have blocked.
+    pub would_block: u64,
+    /// The writes which only wrote part of the packet.
+    pub short_writes: u64,
+    /// The reads or writes which failed, other than the ones which would have blocked.
+    pub errors: u64,
+}
+
+/// The live counters behind `IoStats`.
+#[derive(Debug, Default)]
+pub(crate) struct Counters {
+    packets: AtomicU64,
+    bytes: AtomicU64,
+    would_block: AtomicU64,
+    short_writes: AtomicU64,
+    errors: AtomicU64,
+}
+
+impl Counters {
+    /// Account for the `result` of reading a packet preceded by `offset` bytes of headers.
+    fn read(&self, result: std::io::Result<usize>, offset: usize) -> std::io::Result<usize> {
+        self.record(result, 0, offset)
+    }
+
+    /// Account for the `result` of writing `len` bytes, a packet preceded by `offset` bytes of headers.
+    fn write(
+        &self,
+        result: std::io::Result<usize>,
+        len: usize,
+        offset: usize,
+    ) -> std::io::Result<usize> {
+        self.record(result, len, offset)
+    }
+
+    fn record(
+        &self,
+        result: std::io::Result<usize>,
+        len: usize,
+        offset: usize,
+    ) -> std::io::Result<usize> {
+        let count = |counter: &AtomicU64, value| counter.fetch_add(value, Ordering::Relaxed);
+        match &result {
+            Ok(amount) => {
+                count(&self.packets, 1);
+                count(&self.bytes, amount.saturating_sub(offset) as u64);
+                if *amount < len {
+                    count(&self.short_writes, 1);
+                }
+            }
+            Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => {
+                count(&self.would_block, 1);
+            }
+            Err(_) => {
+                count(&self.errors, 1);
+            }
+        }
+        result
+    }
+
+    fn snapshot(&self) -> IoStats {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        IoStats {
+            packets: load(&self.packets),
+            bytes: load(&self.bytes),
+            would_block: load(&self.would_block),
+            short_writes: load(&self.short_writes),
+            errors: load(&self.errors),
+        }
+    }
+}
+
 /// Read-only end for a file descriptor.
 pub struct Reader {
     pub(crate) fd: Arc<Fd>,
     pub(crate) offset: usize,
     pub(crate) buf: Vec<u8>,
     pub(crate) mtu: u16,
+    pub(crate) vnet_hdr_len: usize,
+    pub(crate) counters: Counters,
+    /// The error which ended the last batch received, reported by the next call.
+    pub(crate) error: Mutex<Option<std::io::Error>>,
 }
 
 impl Reader {
+    /// Get the counters of the packets read so far.
+    pub fn stats(&self) -> IoStats {
+        self.counters.snapshot()
+    }
+
     pub(crate) fn set_mtu(&mut self, value: u16) {
         self.mtu = value;
         self.buf.resize(value as usize + self.offset, 0);
     }
 
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_vnet_hdr_len(&mut self, value: usize) {
+        self.offset = self.offset - self.vnet_hdr_len + value;
+        self.vnet_hdr_len = value;
+        self.buf.resize(self.mtu as usize + self.offset, 0);
+    }
+
+    /// Receive a packet along with its virtio header, the device must have `IFF_VNET_HDR` enabled.
+    pub fn recv_with_hdr(&self, buf: &mut [u8]) -> std::io::Result<(VirtioNetHdr, usize)> {
+        if self.vnet_hdr_len == 0 {
+            return Err(vnet_hdr_disabled());
+        }
+        let mut prefix = [0_u8; MAX_PREFIX_LEN];
+        let prefix = &mut prefix[..self.offset];
+        let amount = self
+            .fd
+            .read_vectored(&mut [IoSliceMut::new(prefix), IoSliceMut::new(buf)]);
+        let amount = self.counters.read(amount, self.offset)?;
+        if amount < self.offset {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
+        }
+        let hdr = VirtioNetHdr::decode(&prefix[self.offset - self.vnet_hdr_len..]);
+        Ok((hdr.unwrap_or_default(), amount - self.offset))
+    }
+
+    /// Receive as many packets as available without blocking into `bufs`, storing their
+    /// lengths in `sizes`, and return the number of packets received.
+    ///
+    /// An error happening after the first packet is reported by the next call.
+    ///
+    /// [Note: Only one packet is received if the descriptor is in blocking mode, as telling
+    /// whether another one is queued would cost a syscall per packet. -- end note]
+    pub fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        if let Some(err) = self.error.lock().unwrap().take() {
+            return Err(err);
+        }
+        let count = bufs.len().min(sizes.len());
+        if count == 0 {
+            return Ok(0);
+        }
+        sizes[0] = self.recv(bufs[0].as_mut())?;
+        if !self.fd.is_nonblock() {
+            return Ok(1);
+        }
+        for i in 1..count {
+            match self.recv(bufs[i].as_mut()) {
+                Ok(size) => sizes[i] = size,
+                Err(err) if err.kind() == std::io::ErrorKind::WouldBlock => return Ok(i),
+                Err(err) => {
+                    *self.error.lock().unwrap() = Some(err);
+                    return Ok(i);
+                }
+            }
+        }
+        Ok(count)
+    }
+
+    /// Receive a packet, possibly a super-packet of an offloading device, into `buf` and
+    /// append the packets it is made of to `segments`, returning how many were appended.
+    ///
+    /// [Note: `buf` must be large enough for a super-packet, that is 65535 bytes. -- end note]
+    pub fn recv_segments(
+        &self,
+        buf: &mut [u8],
+        segments: &mut Vec<Vec<u8>>,
+    ) -> std::io::Result<usize> {
+        let (hdr, len) = self.recv_with_hdr(buf)?;
+        let count = segments.len();
+        crate::offload::gso_split(&hdr, &buf[..len], segments)?;
+        Ok(segments.len() - count)
+    }
+
+    /// Receive a packet along with the packet information and virtio header in front of it.
+    #[cfg(all(any(target_os = "linux", target_os = "macos"), feature = "async"))]
+    pub(crate) fn recv_frame(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        self.counters.read(self.fd.read(buf), self.offset)
+    }
+
     pub(crate) fn recv(&self, mut in_buf: &mut [u8]) -> std::io::Result<usize> {
-        const STACK_BUF_LEN: usize = crate::DEFAULT_MTU as usize + PIL;
+        const STACK_BUF_LEN: usize =
+            crate::DEFAULT_MTU as usize + crate::MAX_ETHERNET_HEADER_LENGTH + PIL;
         let in_buf_len = in_buf.len() + self.offset;
 
         // The following logic is to prevent dynamically allocating Vec on every recv
@@ -100,7 +295,7 @@ impl Reader {
         } else {
             &mut *in_buf
         };
-        let amount = self.fd.read(either_buf)?;
+        let amount = self.counters.read(self.fd.read(either_buf), self.offset)?;
         if self.offset != 0 {
             in_buf.put_slice(&local_buf[self.offset..amount]);
         }
@@ -119,7 +314,7 @@ impl Read for Reader {
         } else {
             &mut *buf
         };
-        let amount = self.fd.read(either_buf)?;
+        let amount = self.counters.read(self.fd.read(either_buf), self.offset)?;
         if self.offset != 0 {
             buf.put_slice(&self.buf[self.offset..amount]);
         }
@@ -139,16 +334,153 @@ pub struct Writer {
     pub(crate) offset: usize,
     pub(crate) buf: Vec<u8>,
     pub(crate) mtu: u16,
+    pub(crate) packet_information: bool,
+    pub(crate) vnet_hdr_len: usize,
+    /// Whether the device accepts UDP super-packets.
+    pub(crate) uso: bool,
+    /// Whether the device carries Ethernet frames rather than IP packets.
+    pub(crate) l2: bool,
+    pub(crate) counters: Counters,
+    /// The error which ended the last batch sent, reported by the next call.
+    pub(crate) error: Mutex<Option<std::io::Error>>,
 }
 
 impl Writer {
+    /// Get the counters of the packets written so far.
+    pub fn stats(&self) -> IoStats {
+        self.counters.snapshot()
+    }
+
     pub(crate) fn set_mtu(&mut self, value: u16) {
         self.mtu = value;
         self.buf.resize(value as usize + self.offset, 0);
     }
 
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_vnet_hdr_len(&mut self, value: usize) {
+        self.offset = self.offset - self.vnet_hdr_len + value;
+        self.vnet_hdr_len = value;
+        self.buf.resize(self.mtu as usize + self.offset, 0);
+    }
+
+    /// Fill `prefix` with the packet information and the virtio header expected in front of `buf`.
+    pub(crate) fn fill_prefix(
+        &self,
+        prefix: &mut [u8],
+        buf: &[u8],
+        hdr: &VirtioNetHdr,
+    ) -> std::io::Result<()> {
+        let (pi, vnet_hdr) = prefix.split_at_mut(self.offset - self.vnet_hdr_len);
+        if self.packet_information && self.l2 {
+            pi.copy_from_slice(&frame_packet_information(buf)?);
+        } else if self.packet_information {
+            let ipv6 = is_ipv6(buf)?;
+            if let Some(header) = generate_packet_information(true, ipv6) {
+                pi.copy_from_slice(header.as_ref());
+            }
+        }
+        hdr.encode(vnet_hdr);
+        Ok(())
+    }
+
+    /// Send a packet along with its virtio header, the device must have `IFF_VNET_HDR` enabled.
+    pub fn send_with_hdr(&self, hdr: &VirtioNetHdr, buf: &[u8]) -> std::io::Result<usize> {
+        if self.vnet_hdr_len == 0 {
+            return Err(vnet_hdr_disabled());
+        }
+        let mut prefix = [0_u8; MAX_PREFIX_LEN];
+        let prefix = &mut prefix[..self.offset];
+        self.fill_prefix(prefix, buf, hdr)?;
+        let amount = self
+            .fd
+            .write_vectored(&[IoSlice::new(prefix), IoSlice::new(buf)]);
+        let len = self.offset + buf.len();
+        let amount = self.counters.write(amount, len, self.offset)?;
+        Ok(amount.saturating_sub(self.offset))
+    }
+
+    /// Send packets until the descriptor would block, and return the number of packets sent.
+    ///
+    /// An error is only returned if the first packet cannot be sent, otherwise it is
+    /// reported by the next call.
+    pub fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        if let Some(err) = self.error.lock().unwrap().take() {
+            return Err(err);
+        }
+        for (i, packet) in packets.iter().enumerate() {
+            match self.send(packet.as_ref()) {
+                Ok(_) => {}
+                Err(err) if i == 0 => return Err(err),
+                Err(err) => return Ok(self.end_batch(i, err)),
+            }
+        }
+        Ok(packets.len())
+    }
+
+    /// End a batch after `count` packets, keeping `err` for the next call unless it only
+    /// tells that the descriptor would block.
+    fn end_batch(&self, count: usize, err: std::io::Error) -> usize {
+        if err.kind() != std::io::ErrorKind::WouldBlock {
+            *self.error.lock().unwrap() = Some(err);
+        }
+        count
+    }
+
+    /// Send a batch of packets, coalescing the consecutive TCP segments of a flow into
+    /// super-packets, returning the number of packets of `packets` sent.
+    ///
+    /// UDP datagrams of a flow are coalesced too when the device has the USO offloads.
+    ///
+    /// An error is only returned if the first super-packet cannot be sent, otherwise the
+    /// packets of the super-packets sent so far are counted, and it is reported by the next call.
+    pub fn send_coalesced(&self, packets: &[&[u8]]) -> std::io::Result<usize> {
+        if self.vnet_hdr_len == 0 {
+            return Err(vnet_hdr_disabled());
+        }
+        if let Some(err) = self.error.lock().unwrap().take() {
+            return Err(err);
+        }
+        let mut sent = 0;
+        for (hdr, packet) in crate::offload::gro_coalesce(packets, self.uso) {
+            match self.send_with_hdr(&hdr, &packet) {
+                Ok(_) => {}
+                Err(err) if sent == 0 => return Err(err),
+                Err(err) => return Ok(self.end_batch(sent, err)),
+            }
+            // Every segment but the last one of a super-packet carries `gso_size` bytes.
+            sent += match hdr.gso_type {
+                VirtioNetHdr::GSO_NONE => 1,
+                _ => (packet.len() - hdr.hdr_len as usize).div_ceil(hdr.gso_size as usize),
+            };
+        }
+        Ok(sent)
+    }
+
+    /// Send a super-packet described by `hdr`, which is segmented in userspace when it is
+    /// a UDP one and the device lacks the USO offloads, returning the number of packets sent.
+    pub fn send_gso(&self, hdr: &VirtioNetHdr, packet: &[u8]) -> std::io::Result<usize> {
+        if hdr.gso_type & !VirtioNetHdr::GSO_ECN != VirtioNetHdr::GSO_UDP_L4 || self.uso {
+            self.send_with_hdr(hdr, packet)?;
+            return Ok(1);
+        }
+        let mut segments = Vec::new();
+        crate::offload::gso_split(hdr, packet, &mut segments)?;
+        for segment in &segments {
+            self.send_with_hdr(&VirtioNetHdr::default(), segment)?;
+        }
+        Ok(segments.len())
+    }
+
+    /// Send a packet preceded by its packet information and virtio header as is.
+    #[cfg(all(any(target_os = "linux", target_os = "macos"), feature = "async"))]
+    pub(crate) fn send_frame(&self, buf: &[u8]) -> std::io::Result<usize> {
+        self.counters
+            .write(self.fd.write(buf), buf.len(), self.offset)
+    }
+
     pub(crate) fn send(&self, in_buf: &[u8]) -> std::io::Result<usize> {
-        const STACK_BUF_LEN: usize = crate::DEFAULT_MTU as usize + PIL;
+        const STACK_BUF_LEN: usize =
+            crate::DEFAULT_MTU as usize + crate::MAX_ETHERNET_HEADER_LENGTH + PIL;
         let in_buf_len = in_buf.len() + self.offset;
 
         // The following logic is to prevent dynamically allocating Vec on every send
@@ -165,18 +497,15 @@ impl Writer {
         };
 
         let either_buf = if self.offset != 0 {
-            let ipv6 = is_ipv6(in_buf)?;
-            if let Some(header) = generate_packet_information(true, ipv6) {
-                (&mut local_buf[..self.offset]).put_slice(header.as_ref());
-                (&mut local_buf[self.offset..in_buf_len]).put_slice(in_buf);
-                local_buf
-            } else {
-                in_buf
-            }
+            let hdr = VirtioNetHdr::default();
+            self.fill_prefix(&mut local_buf[..self.offset], in_buf, &hdr)?;
+            (&mut local_buf[self.offset..in_buf_len]).put_slice(in_buf);
+            &local_buf[..in_buf_len]
         } else {
             in_buf
         };
-        let amount = self.fd.write(either_buf)?;
+        let amount = self.fd.write(either_buf);
+        let amount = self.counters.write(amount, either_buf.len(), self.offset)?;
         Ok(amount - self.offset)
     }
 }
@@ -184,22 +513,22 @@ impl Writer {
 impl Write for Writer {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         let buf = if self.offset != 0 {
-            let ipv6 = is_ipv6(buf)?;
-            if let Some(header) = generate_packet_information(true, ipv6) {
-                let len = self.offset + buf.len();
-                if len > self.buf.len() {
-                    self.buf.resize(len, 0_u8);
-                }
-                (&mut self.buf[..self.offset]).put_slice(header.as_ref());
-                (&mut self.buf[self.offset..len]).put_slice(buf);
-                &self.buf[..len]
-            } else {
-                buf
+            let mut prefix = [0_u8; MAX_PREFIX_LEN];
+            let prefix = &mut prefix[..self.offset];
+            self.fill_prefix(prefix, buf, &VirtioNetHdr::default())?;
+            let len = self.offset + buf.len();
+            if len > self.buf.len() {
+                self.buf.resize(len, 0_u8);
             }
+            (&mut self.buf[..self.offset]).put_slice(prefix);
+            (&mut self.buf[self.offset..len]).put_slice(buf);
+            &self.buf[..len]
         } else {
             buf
         };
-        let amount = self.fd.write(buf)?;
+        let amount = self
+            .counters
+            .write(self.fd.write(buf), buf.len(), self.offset)?;
         Ok(amount - self.offset)
     }
 
@@ -231,12 +560,21 @@ impl Tun {
                 offset,
                 buf: vec![0; mtu as usize + offset],
                 mtu,
+                vnet_hdr_len: 0,
+                counters: Counters::default(),
+                error: Mutex::default(),
             },
             writer: Writer {
                 fd,
                 offset,
                 buf: vec![0; mtu as usize + offset],
                 mtu,
+                packet_information,
+                vnet_hdr_len: 0,
+                uso: false,
+                l2: false,
+                counters: Counters::default(),
+                error: Mutex::default(),
             },
             mtu,
             packet_information,
@@ -257,10 +595,23 @@ impl Tun {
         self.mtu
     }
 
+    /// Set the length of the virtio header in front of every packet, zero disables it.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_vnet_hdr_len(&mut self, value: usize) {
+        self.reader.set_vnet_hdr_len(value);
+        self.writer.set_vnet_hdr_len(value);
+    }
+
     pub fn packet_information(&self) -> bool {
         self.packet_information
     }
 
+    /// Set whether the device carries Ethernet frames, which is the case of TAP devices.
+    #[cfg(target_os = "linux")]
+    pub(crate) fn set_l2(&mut self, value: bool) {
+        self.writer.l2 = value;
+    }
+
     pub fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
         self.reader.recv(buf)
     }
@@ -268,6 +619,47 @@ impl Tun {
     pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
         self.writer.send(buf)
     }
+
+    pub fn recv_many<B: AsMut<[u8]>>(
+        &self,
+        bufs: &mut [B],
+        sizes: &mut [usize],
+    ) -> std::io::Result<usize> {
+        self.reader.recv_many(bufs, sizes)
+    }
+
+    pub fn send_many<B: AsRef<[u8]>>(&self, packets: &[B]) -> std::io::Result<usize> {
+        self.writer.send_many(packets)
+    }
+
+    pub fn recv_with_hdr(&self, buf: &mut [u8]) -> std::io::Result<(VirtioNetHdr, usize)> {
+        self.reader.recv_with_hdr(buf)
+    }
+
+    pub fn send_with_hdr(&self, hdr: &VirtioNetHdr, buf: &[u8]) -> std::io::Result<usize> {
+        self.writer.send_with_hdr(hdr, buf)
+    }
+
+    pub fn recv_segments(
+        &self,
+        buf: &mut [u8],
+        segments: &mut Vec<Vec<u8>>,
+    ) -> std::io::Result<usize> {
+        self.reader.recv_segments(buf, segments)
+    }
+
+    pub fn send_coalesced(&self, packets: &[&[u8]]) -> std::io::Result<usize> {
+        self.writer.send_coalesced(packets)
+    }
+
+    pub fn send_gso(&self, hdr: &VirtioNetHdr, packet: &[u8]) -> std::io::Result<usize> {
+        self.writer.send_gso(hdr, packet)
+    }
+
+    /// Get the counters of the packets read and written so far.
+    pub fn stats(&self) -> (IoStats, IoStats) {
+        (self.reader.stats(), self.writer.stats())
+    }
 }
 
 impl Read for Tun {
@@ -0,0 +1,154 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+use crate::error::{Error, Result};
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+/// An IP network, made of an address and a prefix length.
+#[derive(Clone, Copy, Debug, Eq, PartialEq, Hash)]
+pub struct Cidr {
+    addr: IpAddr,
+    prefix_len: u8,
+}
+
+impl Cidr {
+    /// Create a new network, the host bits of `addr` are cleared.
+    pub fn new(addr: IpAddr, prefix_len: u8) -> Result<Self> {
+        let addr = match addr {
+            IpAddr::V4(addr) if prefix_len <= 32 => {
+                let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+                IpAddr::V4(Ipv4Addr::from(u32::from(addr) & mask))
+            }
+            IpAddr::V6(addr) if prefix_len <= 128 => {
+                let mask = u128::MAX.checked_shl(128 - prefix_len as u32).unwrap_or(0);
+                IpAddr::V6(Ipv6Addr::from(u128::from(addr) & mask))
+            }
+            _ => return Err(Error::InvalidAddress),
+        };
+        Ok(Cidr { addr, prefix_len })
+    }
+
+    /// Get the network address.
+    pub fn addr(&self) -> IpAddr {
+        self.addr
+    }
+
+    /// Get the prefix length.
+    pub fn prefix_len(&self) -> u8 {
+        self.prefix_len
+    }
+}
+
+/// A bare address is parsed as a host network.
+impl FromStr for Cidr {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let (addr, prefix_len) = match s.split_once('/') {
+            Some((addr, prefix_len)) => (addr, Some(prefix_len)),
+            None => (s, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| Error::InvalidAddress)?;
+        let prefix_len = match prefix_len {
+            Some(prefix_len) => prefix_len.parse().map_err(|_| Error::InvalidAddress)?,
+            None if addr.is_ipv6() => 128,
+            None => 32,
+        };
+        Cidr::new(addr, prefix_len)
+    }
+}
+
+impl fmt::Display for Cidr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.addr, self.prefix_len)
+    }
+}
+
+/// A route through the interface.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Route {
+    /// The destination network.
+    pub destination: Cidr,
+    /// The next hop, or `None` for a directly connected network.
+    pub gateway: Option<IpAddr>,
+    /// The route metric, lower values are preferred.
+    pub metric: u32,
+}
+
+/// Build the arguments of the BSD `route` command for the given action.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub(crate) fn route_command(
+    action: &str,
+    tun_name: &str,
+    destination: Cidr,
+    gateway: Option<IpAddr>,
+) -> Result<()> {
+    let family = if destination.addr().is_ipv6() {
+        "-inet6"
+    } else {
+        "-inet"
+    };
+    let network = destination.to_string();
+    let gateway = gateway.map(|gateway| gateway.to_string());
+    // command: route -n add -inet -net 10.1.0.0/16 10.0.0.1
+    // command: route -n add -inet -net 10.1.0.0/16 -interface utun3
+    let mut args = vec!["-n", action, family, "-net", &network];
+    match gateway.as_ref() {
+        Some(gateway) => args.push(gateway),
+        None => args.extend(["-interface", tun_name]),
+    }
+    crate::run_command::run_command("route", &args)?;
+    log::info!("route {}", args.join(" "));
+    Ok(())
+}
+
+/// Add an address to the interface with the BSD `ifconfig` command, keeping the existing ones.
+#[cfg(any(target_os = "macos", target_os = "freebsd"))]
+pub(crate) fn add_alias_command(tun_name: &str, addr: IpAddr, prefix_len: u8) -> Result<()> {
+    Cidr::new(addr, prefix_len)?;
+    let address = addr.to_string();
+    let prefix_len = prefix_len.to_string();
+    let network = format!("{address}/{prefix_len}");
+    // command: ifconfig utun3 inet 10.0.0.2/24 10.0.0.2 alias
+    // command: ifconfig utun3 inet6 fd00::2 prefixlen 64 alias
+    let args = match addr {
+        // The TUN interfaces are point-to-point and require a destination, use the address itself.
+        IpAddr::V4(_) => vec![tun_name, "inet", &network, &address, "alias"],
+        IpAddr::V6(_) => vec![
+            tun_name,
+            "inet6",
+            &address,
+            "prefixlen",
+            &prefix_len,
+            "alias",
+        ],
+    };
+    crate::run_command::run_command("ifconfig", &args)?;
+    log::info!("ifconfig {}", args.join(" "));
+    Ok(())
+}
+
+#[test]
+fn test_cidr() {
+    let cidr: Cidr = "10.0.0.9/24".parse().unwrap();
+    assert_eq!("10.0.0.0/24", cidr.to_string());
+
+    let cidr: Cidr = "fd00::1".parse().unwrap();
+    assert_eq!(128, cidr.prefix_len());
+
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert_eq!("::/0", "fd00::1/0".parse::<Cidr>().unwrap().to_string());
+}
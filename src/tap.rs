@@ -0,0 +1,435 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+//! Exchange of IP packets over a TAP device, answering the neighbor resolution of the gateway.
+
+use crate::error::Result;
+use crate::offload::{checksum_add, checksum_fold, pseudo_header_sum};
+use crate::platform::posix::is_ipv6;
+use crate::platform::Device;
+use crate::AbstractDevice;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+const ETHER_HEADER_LEN: usize = 14;
+const ETHERTYPE_IPV4: [u8; 2] = [0x08, 0x00];
+const ETHERTYPE_ARP: [u8; 2] = [0x08, 0x06];
+const ETHERTYPE_IPV6: [u8; 2] = [0x86, 0xdd];
+
+/// Hardware type Ethernet, protocol type IPv4, address lengths 6 and 4.
+const ARP_ETHER_IPV4: [u8; 6] = [0, 1, 8, 0, 6, 4];
+const ARP_LEN: usize = 28;
+const ARP_REQUEST: u8 = 1;
+const ARP_REPLY: u8 = 2;
+
+const IPV6_HEADER_LEN: usize = 40;
+const IPPROTO_ICMPV6: u8 = 58;
+const NDP_HOP_LIMIT: u8 = 255;
+const ND_NEIGHBOR_SOLICIT: u8 = 135;
+const ND_NEIGHBOR_ADVERT: u8 = 136;
+const ND_NEIGHBOR_LEN: usize = 24;
+const ND_OPT_SOURCE_LINKADDR: u8 = 1;
+const ND_OPT_TARGET_LINKADDR: u8 = 2;
+const ND_NA_FLAG_ROUTER: u8 = 0x80;
+const ND_NA_FLAG_SOLICITED: u8 = 0x40;
+const ND_NA_FLAG_OVERRIDE: u8 = 0x20;
+
+/// The longest reply, a neighbor advertisement with the target link-layer address option.
+const MAX_REPLY_LEN: usize = ETHER_HEADER_LEN + IPV6_HEADER_LEN + ND_NEIGHBOR_LEN + 8;
+
+/// The frames up to the default MTU are built on the stack.
+const STACK_FRAME_LEN: usize = crate::DEFAULT_MTU as usize + crate::MAX_ETHERNET_HEADER_LENGTH;
+
+/// What to do with a received frame.
+enum Incoming {
+    /// The frame carries an IP packet.
+    Packet,
+    /// The frame is a neighbor resolution to answer with the reply of the given length.
+    Reply(usize),
+    /// The frame is not for the IP stack.
+    Ignore,
+}
+
+/// A frame buffer, on the stack unless larger than what the default MTU allows.
+#[allow(clippy::large_enum_variant)]
+enum FrameBuf {
+    Stack([u8; STACK_FRAME_LEN], usize),
+    Heap(Vec<u8>),
+}
+
+impl FrameBuf {
+    fn new(len: usize) -> Self {
+        if len > STACK_FRAME_LEN {
+            FrameBuf::Heap(vec![0; len])
+        } else {
+            FrameBuf::Stack([0; STACK_FRAME_LEN], len)
+        }
+    }
+
+    fn as_mut(&mut self) -> &mut [u8] {
+        match self {
+            FrameBuf::Stack(buf, len) => &mut buf[..*len],
+            FrameBuf::Heap(buf) => buf,
+        }
+    }
+}
+
+/// An adapter exchanging IP packets over a `Layer::L2` device, like a `Layer::L3` one.
+///
+/// The Ethernet header is removed from the received frames and added to the sent packets,
+/// which go from the synthetic MAC address of the gateway to the MAC address of the interface.
+/// The ARP requests and IPv6 neighbor solicitations for the gateway addresses are answered
+/// with the synthetic MAC address, every other frame not carrying an IP packet is dropped.
+pub struct TapAdapter<D> {
+    device: D,
+    host_mac: [u8; 6],
+    gateway_mac: [u8; 6],
+    gateways: Vec<IpAddr>,
+}
+
+impl<D> TapAdapter<D> {
+    /// The synthetic MAC address of the gateway unless set, a locally administered one.
+    pub const DEFAULT_GATEWAY_MAC: [u8; 6] = [0x02, 0x54, 0x55, 0x4e, 0x00, 0x01];
+
+    /// Create a new `TapAdapter` sending the packets to the interface with the MAC address `host_mac`.
+    pub fn with_host_mac(device: D, host_mac: [u8; 6]) -> Self {
+        TapAdapter {
+            device,
+            host_mac,
+            gateway_mac: Self::DEFAULT_GATEWAY_MAC,
+            gateways: Vec::new(),
+        }
+    }
+
+    /// Add an address of the gateway, whose neighbor resolution is answered.
+    pub fn add_gateway(&mut self, address: IpAddr) -> &mut Self {
+        if !self.gateways.contains(&address) {
+            self.gateways.push(address);
+        }
+        self
+    }
+
+    /// Set the synthetic MAC address of the gateway.
+    pub fn gateway_mac(&mut self, value: [u8; 6]) -> &mut Self {
+        self.gateway_mac = value;
+        self
+    }
+
+    /// Returns a reference to the device.
+    pub fn get_ref(&self) -> &D {
+        &self.device
+    }
+
+    /// Returns a mutable reference to the device.
+    pub fn get_mut(&mut self) -> &mut D {
+        &mut self.device
+    }
+
+    /// Consumes the adapter, returning the device.
+    pub fn into_inner(self) -> D {
+        self.device
+    }
+
+    /// Build the Ethernet header of the IP packet `buf`.
+    fn header(&self, buf: &[u8]) -> std::io::Result<[u8; ETHER_HEADER_LEN]> {
+        let ethertype = if is_ipv6(buf)? {
+            ETHERTYPE_IPV6
+        } else {
+            ETHERTYPE_IPV4
+        };
+        let mut header = [0; ETHER_HEADER_LEN];
+        header[..6].copy_from_slice(&self.host_mac);
+        header[6..12].copy_from_slice(&self.gateway_mac);
+        header[12..].copy_from_slice(&ethertype);
+        Ok(header)
+    }
+
+    /// Decide what to do with the received `frame`, building the reply into `reply`.
+    fn incoming(&self, frame: &[u8], reply: &mut [u8; MAX_REPLY_LEN]) -> Incoming {
+        if frame.len() < ETHER_HEADER_LEN {
+            return Incoming::Ignore;
+        }
+        // Only the unicast frames for the gateway and the multicast ones are received.
+        if frame[..6] != self.gateway_mac && frame[0] & 1 == 0 {
+            return Incoming::Ignore;
+        }
+        let payload = &frame[ETHER_HEADER_LEN..];
+        match [frame[12], frame[13]] {
+            ETHERTYPE_ARP => self.arp(payload, reply),
+            ETHERTYPE_IPV4 if payload.first().is_some_and(|b| b >> 4 == 4) => Incoming::Packet,
+            ETHERTYPE_IPV6 if payload.first().is_some_and(|b| b >> 4 == 6) => {
+                if is_neighbor_discovery(payload) {
+                    self.neighbor_solicitation(&frame[6..12], payload, reply)
+                } else {
+                    Incoming::Packet
+                }
+            }
+            _ => Incoming::Ignore,
+        }
+    }
+
+    /// Answer an ARP request for a gateway address.
+    fn arp(&self, arp: &[u8], reply: &mut [u8; MAX_REPLY_LEN]) -> Incoming {
+        if arp.len() < ARP_LEN || arp[..6] != ARP_ETHER_IPV4 || arp[7] != ARP_REQUEST {
+            return Incoming::Ignore;
+        }
+        let (sender_mac, sender_ip, target_ip) = (&arp[8..14], &arp[14..18], &arp[24..28]);
+        let target = Ipv4Addr::new(target_ip[0], target_ip[1], target_ip[2], target_ip[3]);
+        // Gratuitous announcements of the sender are not requests.
+        if !self.gateways.contains(&IpAddr::V4(target)) || sender_ip == target_ip {
+            return Incoming::Ignore;
+        }
+
+        reply[..6].copy_from_slice(sender_mac);
+        reply[6..12].copy_from_slice(&self.gateway_mac);
+        reply[12..14].copy_from_slice(&ETHERTYPE_ARP);
+        let arp_reply = &mut reply[ETHER_HEADER_LEN..ETHER_HEADER_LEN + ARP_LEN];
+        arp_reply[..6].copy_from_slice(&ARP_ETHER_IPV4);
+        arp_reply[6..8].copy_from_slice(&[0, ARP_REPLY]);
+        arp_reply[8..14].copy_from_slice(&self.gateway_mac);
+        arp_reply[14..18].copy_from_slice(target_ip);
+        arp_reply[18..24].copy_from_slice(sender_mac);
+        arp_reply[24..28].copy_from_slice(sender_ip);
+        Incoming::Reply(ETHER_HEADER_LEN + ARP_LEN)
+    }
+
+    /// Answer a neighbor solicitation for a gateway address, sent from `source_mac`.
+    fn neighbor_solicitation(
+        &self,
+        source_mac: &[u8],
+        ip: &[u8],
+        reply: &mut [u8; MAX_REPLY_LEN],
+    ) -> Incoming {
+        let len = u16::from_be_bytes([ip[4], ip[5]]) as usize;
+        if len < ND_NEIGHBOR_LEN || ip.len() < IPV6_HEADER_LEN + len {
+            return Incoming::Ignore;
+        }
+        let icmp = &ip[IPV6_HEADER_LEN..IPV6_HEADER_LEN + len];
+        let sum = pseudo_header_sum(ip, IPPROTO_ICMPV6, len) + checksum_add(0, icmp);
+        if icmp[0] != ND_NEIGHBOR_SOLICIT || icmp[1] != 0 || checksum_fold(sum) != 0xffff {
+            return Incoming::Ignore;
+        }
+        let target: [u8; 16] = icmp[8..24].try_into().unwrap();
+        if !self.gateways.contains(&IpAddr::V6(Ipv6Addr::from(target))) {
+            return Incoming::Ignore;
+        }
+
+        let mut options = &icmp[ND_NEIGHBOR_LEN..];
+        let mut source_mac = source_mac;
+        while options.len() >= 8 && options[1] != 0 && options.len() >= options[1] as usize * 8 {
+            if options[0] == ND_OPT_SOURCE_LINKADDR && options[1] == 1 {
+                source_mac = &options[2..8];
+            }
+            options = &options[options[1] as usize * 8..];
+        }
+
+        // Duplicate address detection is answered to all the nodes.
+        let dad = ip[8..24] == Ipv6Addr::UNSPECIFIED.octets();
+        let (destination, destination_mac, flags) = if dad {
+            let all_nodes = Ipv6Addr::new(0xff02, 0, 0, 0, 0, 0, 0, 1).octets();
+            let mac = [0x33, 0x33, 0, 0, 0, 1];
+            (all_nodes, mac, ND_NA_FLAG_ROUTER | ND_NA_FLAG_OVERRIDE)
+        } else {
+            let flags = ND_NA_FLAG_ROUTER | ND_NA_FLAG_SOLICITED | ND_NA_FLAG_OVERRIDE;
+            (
+                ip[8..24].try_into().unwrap(),
+                source_mac.try_into().unwrap(),
+                flags,
+            )
+        };
+
+        reply[..6].copy_from_slice(&destination_mac);
+        reply[6..12].copy_from_slice(&self.gateway_mac);
+        reply[12..14].copy_from_slice(&ETHERTYPE_IPV6);
+        let advert_len = ND_NEIGHBOR_LEN + 8;
+        let ip_reply = &mut reply[ETHER_HEADER_LEN..];
+        ip_reply[..4].copy_from_slice(&[0x60, 0, 0, 0]);
+        ip_reply[4..6].copy_from_slice(&(advert_len as u16).to_be_bytes());
+        ip_reply[6] = IPPROTO_ICMPV6;
+        ip_reply[7] = NDP_HOP_LIMIT;
+        ip_reply[8..24].copy_from_slice(&target);
+        ip_reply[24..40].copy_from_slice(&destination);
+        let advert = &mut ip_reply[IPV6_HEADER_LEN..];
+        advert[..8].copy_from_slice(&[ND_NEIGHBOR_ADVERT, 0, 0, 0, flags, 0, 0, 0]);
+        advert[8..24].copy_from_slice(&target);
+        advert[24..26].copy_from_slice(&[ND_OPT_TARGET_LINKADDR, 1]);
+        advert[26..32].copy_from_slice(&self.gateway_mac);
+        let sum = pseudo_header_sum(ip_reply, IPPROTO_ICMPV6, advert_len)
+            + checksum_add(0, &ip_reply[IPV6_HEADER_LEN..]);
+        ip_reply[IPV6_HEADER_LEN + 2..IPV6_HEADER_LEN + 4]
+            .copy_from_slice(&(!checksum_fold(sum)).to_be_bytes());
+        Incoming::Reply(MAX_REPLY_LEN)
+    }
+}
+
+/// Whether the IPv6 packet `ip` is a neighbor solicitation or advertisement, which a
+/// `Layer::L3` device never carries.
+fn is_neighbor_discovery(ip: &[u8]) -> bool {
+    ip.len() > IPV6_HEADER_LEN
+        && ip[6] == IPPROTO_ICMPV6
+        && ip[7] == NDP_HOP_LIMIT
+        && matches!(
+            ip[IPV6_HEADER_LEN],
+            ND_NEIGHBOR_SOLICIT | ND_NEIGHBOR_ADVERT
+        )
+}
+
+impl<D: AsRef<dyn AbstractDevice + 'static>> TapAdapter<D> {
+    /// Create a new `TapAdapter` wrapping around a `Layer::L2` `Device` or `AsyncDevice`,
+    /// sending the packets to the MAC address of the interface.
+    pub fn new(device: D) -> Result<Self> {
+        let host_mac = device.as_ref().mac_address()?;
+        Ok(Self::with_host_mac(device, host_mac))
+    }
+}
+
+impl TapAdapter<Device> {
+    /// Receive an IP packet, answering the neighbor resolutions received meanwhile.
+    pub fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut frame = FrameBuf::new(buf.len() + ETHER_HEADER_LEN);
+        let frame = frame.as_mut();
+        let mut reply = [0; MAX_REPLY_LEN];
+        loop {
+            let len = self.device.recv(frame)?;
+            match self.incoming(&frame[..len], &mut reply) {
+                Incoming::Packet => {
+                    let packet = &frame[ETHER_HEADER_LEN..len];
+                    buf[..packet.len()].copy_from_slice(packet);
+                    return Ok(packet.len());
+                }
+                Incoming::Reply(len) => {
+                    self.device.send(&reply[..len])?;
+                }
+                Incoming::Ignore => {}
+            }
+        }
+    }
+
+    /// Send an IP packet.
+    pub fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut frame = FrameBuf::new(buf.len() + ETHER_HEADER_LEN);
+        let frame = frame.as_mut();
+        frame[..ETHER_HEADER_LEN].copy_from_slice(&self.header(buf)?);
+        frame[ETHER_HEADER_LEN..].copy_from_slice(buf);
+        let amount = self.device.send(frame)?;
+        Ok(amount.saturating_sub(ETHER_HEADER_LEN))
+    }
+}
+
+#[cfg(feature = "async")]
+impl TapAdapter<crate::AsyncDevice> {
+    /// Receive an IP packet, answering the neighbor resolutions received meanwhile.
+    pub async fn recv(&self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let mut frame = FrameBuf::new(buf.len() + ETHER_HEADER_LEN);
+        let frame = frame.as_mut();
+        let mut reply = [0; MAX_REPLY_LEN];
+        loop {
+            let len = self.device.recv(frame).await?;
+            match self.incoming(&frame[..len], &mut reply) {
+                Incoming::Packet => {
+                    let packet = &frame[ETHER_HEADER_LEN..len];
+                    buf[..packet.len()].copy_from_slice(packet);
+                    return Ok(packet.len());
+                }
+                Incoming::Reply(len) => {
+                    self.device.send(&reply[..len]).await?;
+                }
+                Incoming::Ignore => {}
+            }
+        }
+    }
+
+    /// Send an IP packet.
+    pub async fn send(&self, buf: &[u8]) -> std::io::Result<usize> {
+        let mut frame = FrameBuf::new(buf.len() + ETHER_HEADER_LEN);
+        let frame = frame.as_mut();
+        frame[..ETHER_HEADER_LEN].copy_from_slice(&self.header(buf)?);
+        frame[ETHER_HEADER_LEN..].copy_from_slice(buf);
+        let amount = self.device.send(frame).await?;
+        Ok(amount.saturating_sub(ETHER_HEADER_LEN))
+    }
+}
+
+#[test]
+fn test_neighbor_resolution() {
+    let host_mac = [0x02, 0, 0, 0, 0, 1];
+    let mut adapter = TapAdapter::with_host_mac((), host_mac);
+    adapter.add_gateway("10.0.0.254".parse().unwrap());
+    adapter.add_gateway("fd00::fe".parse().unwrap());
+    let gateway_mac = TapAdapter::<()>::DEFAULT_GATEWAY_MAC;
+    let mut reply = [0; MAX_REPLY_LEN];
+
+    let mut arp = vec![0xff; 6];
+    arp.extend_from_slice(&host_mac);
+    arp.extend_from_slice(&ETHERTYPE_ARP);
+    arp.extend_from_slice(&ARP_ETHER_IPV4);
+    arp.extend_from_slice(&[0, ARP_REQUEST]);
+    arp.extend_from_slice(&host_mac);
+    arp.extend_from_slice(&[10, 0, 0, 1, 0, 0, 0, 0, 0, 0, 10, 0, 0, 254]);
+    let Incoming::Reply(len) = adapter.incoming(&arp, &mut reply) else {
+        panic!("ARP request not answered");
+    };
+    assert_eq!(42, len);
+    assert_eq!(host_mac, reply[..6]);
+    assert_eq!([0, ARP_REPLY], reply[20..22]);
+    assert_eq!(gateway_mac, reply[22..28]);
+    assert_eq!([10, 0, 0, 254], reply[28..32]);
+    assert_eq!([10, 0, 0, 1], reply[38..42]);
+    arp[41] = 253;
+    assert!(matches!(
+        adapter.incoming(&arp, &mut reply),
+        Incoming::Ignore
+    ));
+
+    let mut ns = vec![0x33, 0x33, 0xff, 0, 0, 0xfe];
+    ns.extend_from_slice(&host_mac);
+    ns.extend_from_slice(&ETHERTYPE_IPV6);
+    ns.extend_from_slice(&[0x60, 0, 0, 0, 0, 32, IPPROTO_ICMPV6, NDP_HOP_LIMIT]);
+    ns.extend_from_slice(&"fd00::1".parse::<Ipv6Addr>().unwrap().octets());
+    ns.extend_from_slice(&"ff02::1:ff00:fe".parse::<Ipv6Addr>().unwrap().octets());
+    ns.extend_from_slice(&[ND_NEIGHBOR_SOLICIT, 0, 0, 0, 0, 0, 0, 0]);
+    ns.extend_from_slice(&"fd00::fe".parse::<Ipv6Addr>().unwrap().octets());
+    ns.extend_from_slice(&[ND_OPT_SOURCE_LINKADDR, 1]);
+    ns.extend_from_slice(&host_mac);
+    let ip = &ns[ETHER_HEADER_LEN..];
+    let sum = pseudo_header_sum(ip, IPPROTO_ICMPV6, 32) + checksum_add(0, &ip[40..]);
+    ns[56..58].copy_from_slice(&(!checksum_fold(sum)).to_be_bytes());
+    let Incoming::Reply(len) = adapter.incoming(&ns, &mut reply) else {
+        panic!("neighbor solicitation not answered");
+    };
+    assert_eq!(86, len);
+    assert_eq!(host_mac, reply[..6]);
+    let ip = &reply[ETHER_HEADER_LEN..];
+    assert_eq!(ip[8..24], "fd00::fe".parse::<Ipv6Addr>().unwrap().octets());
+    assert_eq!(ip[24..40], "fd00::1".parse::<Ipv6Addr>().unwrap().octets());
+    assert_eq!(ND_NEIGHBOR_ADVERT, ip[40]);
+    assert_eq!(gateway_mac, ip[66..72]);
+    let sum = pseudo_header_sum(ip, IPPROTO_ICMPV6, 32) + checksum_add(0, &ip[40..]);
+    assert_eq!(0xffff, checksum_fold(sum));
+
+    let mut packet = vec![0; 20];
+    packet[0] = 0x45;
+    let header = adapter.header(&packet).unwrap();
+    let mut frame = header.to_vec();
+    frame.extend_from_slice(&packet);
+    assert_eq!(host_mac, header[..6]);
+    assert!(matches!(
+        adapter.incoming(&frame, &mut reply),
+        Incoming::Ignore
+    ));
+    frame[..6].copy_from_slice(&gateway_mac);
+    assert!(matches!(
+        adapter.incoming(&frame, &mut reply),
+        Incoming::Packet
+    ));
+}
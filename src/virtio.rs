@@ -0,0 +1,104 @@
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//                    Version 2, December 2004
+//
+// Copyleft (ↄ) meh. <meh@schizofreni.co> | http://meh.schizofreni.co
+//
+// Everyone is permitted to copy and distribute verbatim or modified
+// copies of this license document, and changing it is allowed as long
+// as the name is changed.
+//
+//            DO WHAT THE FUCK YOU WANT TO PUBLIC LICENSE
+//   TERMS AND CONDITIONS FOR COPYING, DISTRIBUTION AND MODIFICATION
+//
+//  0. You just DO WHAT THE FUCK YOU WANT TO.
+
+/// The `virtio_net_hdr` in front of every packet of a device with `IFF_VNET_HDR`.
+///
+/// It is always encoded in little-endian byte order, which is what the kernel uses
+/// on little-endian hosts and what tun2 requests on big-endian ones.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Hash)]
+pub struct VirtioNetHdr {
+    pub flags: u8,
+    pub gso_type: u8,
+    pub hdr_len: u16,
+    pub gso_size: u16,
+    pub csum_start: u16,
+    pub csum_offset: u16,
+    /// Only present in the 12 bytes version of the header.
+    pub num_buffers: u16,
+}
+
+impl VirtioNetHdr {
+    /// Length of the header, as used by default by the kernel.
+    pub const LEN: usize = 10;
+    /// Length of the header with the `num_buffers` field.
+    pub const MRG_RXBUF_LEN: usize = 12;
+
+    /// The checksum starting at `csum_start` must be computed and stored at `csum_start + csum_offset`.
+    pub const F_NEEDS_CSUM: u8 = 1;
+    /// The checksum of the packet has already been validated.
+    pub const F_DATA_VALID: u8 = 2;
+
+    pub const GSO_NONE: u8 = 0;
+    pub const GSO_TCPV4: u8 = 1;
+    pub const GSO_UDP: u8 = 3;
+    pub const GSO_TCPV6: u8 = 4;
+    pub const GSO_UDP_L4: u8 = 5;
+    pub const GSO_ECN: u8 = 0x80;
+
+    /// Decode a header from the beginning of `buf`, which must hold at least [`VirtioNetHdr::LEN`] bytes.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() < Self::LEN {
+            return None;
+        }
+        let u16_at = |i: usize| u16::from_le_bytes([buf[i], buf[i + 1]]);
+        Some(VirtioNetHdr {
+            flags: buf[0],
+            gso_type: buf[1],
+            hdr_len: u16_at(2),
+            gso_size: u16_at(4),
+            csum_start: u16_at(6),
+            csum_offset: u16_at(8),
+            num_buffers: if buf.len() >= Self::MRG_RXBUF_LEN {
+                u16_at(10)
+            } else {
+                0
+            },
+        })
+    }
+
+    /// Encode the header into `buf`, filling as many bytes as its length allows,
+    /// any byte past the 12 bytes header is zeroed.
+    pub fn encode(&self, buf: &mut [u8]) {
+        let mut bytes = [0_u8; Self::MRG_RXBUF_LEN];
+        bytes[0] = self.flags;
+        bytes[1] = self.gso_type;
+        bytes[2..4].copy_from_slice(&self.hdr_len.to_le_bytes());
+        bytes[4..6].copy_from_slice(&self.gso_size.to_le_bytes());
+        bytes[6..8].copy_from_slice(&self.csum_start.to_le_bytes());
+        bytes[8..10].copy_from_slice(&self.csum_offset.to_le_bytes());
+        bytes[10..12].copy_from_slice(&self.num_buffers.to_le_bytes());
+
+        let len = buf.len().min(bytes.len());
+        buf[..len].copy_from_slice(&bytes[..len]);
+        buf[len..].fill(0);
+    }
+}
+
+#[test]
+fn test_virtio_net_hdr() {
+    let hdr = VirtioNetHdr {
+        flags: VirtioNetHdr::F_NEEDS_CSUM,
+        gso_type: VirtioNetHdr::GSO_TCPV4,
+        hdr_len: 40,
+        gso_size: 1448,
+        csum_start: 20,
+        csum_offset: 16,
+        num_buffers: 0,
+    };
+    let mut buf = [0xff_u8; VirtioNetHdr::LEN];
+    hdr.encode(&mut buf);
+    assert_eq!([1, 1, 40, 0, 0xa8, 0x05, 20, 0, 16, 0], buf);
+    assert_eq!(Some(hdr), VirtioNetHdr::decode(&buf));
+    assert_eq!(None, VirtioNetHdr::decode(&buf[..8]));
+}